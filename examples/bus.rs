@@ -34,6 +34,7 @@ async fn main() {
             body: MessageBody::AuthenticationRequest {
                 user: "foo".into(),
                 password: "bar".into(),
+                address: None,
             },
             topic: Some("example".into()),
             is_request: false,
@@ -58,7 +59,7 @@ async fn main() {
             subbroker
                 .send(Message {
                     id: Uuid::new_v4(),
-                    body: MessageBody::AuthenticationResponse(Ok("response to foo".into())),
+                    body: MessageBody::DebugMessage("response to foo".into()),
                     topic: Some(reply_topic.clone()),
                     is_request: false,
                     timestamp: chrono::Utc::now().timestamp_millis() as u64,
@@ -74,6 +75,7 @@ async fn main() {
             body: MessageBody::AuthenticationRequest {
                 user: "baz".into(),
                 password: "qux".into(),
+                address: None,
             },
             topic: Some("example".into()),
             is_request: true,
@@ -0,0 +1,3 @@
+ALTER TABLE inventories_x_buildings DROP COLUMN IF EXISTS queue_position;
+
+ALTER TABLE inventories DROP COLUMN IF EXISTS construction_slots;
\ No newline at end of file
@@ -0,0 +1,5 @@
+ALTER TABLE inventories
+    ADD COLUMN construction_slots INT NOT NULL DEFAULT 1;
+
+ALTER TABLE inventories_x_buildings
+    ADD COLUMN queue_position INT NULL;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE inventories_x_buildings DROP COLUMN IF EXISTS level;
\ No newline at end of file
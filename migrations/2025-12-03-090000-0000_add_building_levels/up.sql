@@ -0,0 +1,2 @@
+ALTER TABLE inventories_x_buildings
+    ADD COLUMN level INT NOT NULL DEFAULT 1;
\ No newline at end of file
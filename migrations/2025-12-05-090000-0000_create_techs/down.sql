@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS inventories_x_techs;
+
+DROP TABLE IF EXISTS techs CASCADE;
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE techs (
+    slug TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE inventories_x_techs (
+    inventory_id UUID NOT NULL,
+    tech_slug TEXT NOT NULL,
+    researched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (inventory_id, tech_slug),
+    FOREIGN KEY (inventory_id) REFERENCES inventories(id) ON DELETE CASCADE,
+    FOREIGN KEY (tech_slug) REFERENCES techs(slug) ON DELETE CASCADE
+);
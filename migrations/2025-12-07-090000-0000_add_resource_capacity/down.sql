@@ -0,0 +1,4 @@
+ALTER TABLE resources
+    DROP COLUMN IF EXISTS overflow_rate,
+    DROP COLUMN IF EXISTS overflow_resource,
+    DROP COLUMN IF EXISTS base_capacity;
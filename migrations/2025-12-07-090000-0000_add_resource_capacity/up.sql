@@ -0,0 +1,4 @@
+ALTER TABLE resources
+    ADD COLUMN base_capacity INT NOT NULL DEFAULT 1000,
+    ADD COLUMN overflow_resource TEXT NULL REFERENCES resources(slug) ON DELETE SET NULL,
+    ADD COLUMN overflow_rate REAL NOT NULL DEFAULT 1.0;
@@ -0,0 +1 @@
+ALTER TABLE inventories_x_buildings DROP COLUMN IF EXISTS condition;
\ No newline at end of file
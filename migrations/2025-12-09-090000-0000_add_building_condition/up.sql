@@ -0,0 +1,2 @@
+ALTER TABLE inventories_x_buildings
+    ADD COLUMN condition INT NOT NULL DEFAULT 100;
\ No newline at end of file
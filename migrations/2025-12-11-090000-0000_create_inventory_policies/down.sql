@@ -0,0 +1 @@
+DROP TABLE IF EXISTS inventory_policies;
\ No newline at end of file
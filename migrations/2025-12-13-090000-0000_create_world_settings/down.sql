@@ -0,0 +1 @@
+DROP TABLE IF EXISTS world_settings;
\ No newline at end of file
@@ -0,0 +1,10 @@
+CREATE TABLE world_settings (
+    id INT PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    tick_seq BIGINT NOT NULL DEFAULT 0,
+    last_tick_at TIMESTAMPTZ,
+    catch_up_mode TEXT NOT NULL DEFAULT 'catch_up',
+    catch_up_batch INT NOT NULL DEFAULT 20,
+    max_catch_up_ticks BIGINT NOT NULL DEFAULT 86400
+);
+
+INSERT INTO world_settings (id) VALUES (1);
@@ -0,0 +1 @@
+ALTER TABLE world_settings DROP COLUMN IF EXISTS ticks_per_day, DROP COLUMN IF EXISTS days_per_season;
\ No newline at end of file
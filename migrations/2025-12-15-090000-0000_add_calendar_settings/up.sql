@@ -0,0 +1,3 @@
+ALTER TABLE world_settings
+    ADD COLUMN ticks_per_day INT NOT NULL DEFAULT 60,
+    ADD COLUMN days_per_season INT NOT NULL DEFAULT 10;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS account_sessions_family_id_idx;
+ALTER TABLE account_sessions DROP COLUMN IF EXISTS family_id, DROP COLUMN IF EXISTS rotated_at;
@@ -0,0 +1,7 @@
+ALTER TABLE account_sessions ADD COLUMN family_id UUID;
+UPDATE account_sessions SET family_id = id;
+ALTER TABLE account_sessions
+    ALTER COLUMN family_id SET NOT NULL,
+    ADD COLUMN rotated_at TIMESTAMP NULL;
+
+CREATE INDEX account_sessions_family_id_idx ON account_sessions (family_id);
@@ -0,0 +1,5 @@
+ALTER TABLE accounts
+    DROP CONSTRAINT IF EXISTS accounts_state_check,
+    DROP CONSTRAINT IF EXISTS accounts_role_check,
+    DROP COLUMN IF EXISTS state_reason,
+    DROP COLUMN IF EXISTS role;
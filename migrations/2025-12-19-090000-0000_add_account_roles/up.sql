@@ -0,0 +1,5 @@
+ALTER TABLE accounts
+    ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'player',
+    ADD COLUMN state_reason TEXT NULL,
+    ADD CONSTRAINT accounts_role_check CHECK (role IN ('player', 'moderator', 'admin')),
+    ADD CONSTRAINT accounts_state_check CHECK (state IN ('active', 'suspended', 'banned'));
@@ -1,10 +1,21 @@
-use crate::persistence::Query;
+use crate::persistence::{Query, QueryResponse};
 use uuid::Uuid;
 
+use crate::auth::api_key::MAX_LIFETIME_DAYS;
+use crate::auth::model::AuthTokens;
+use crate::auth::permission::{AccountState, AccountStatus, Permission, PermissionSet};
+use crate::auth::throttle::LoginThrottle;
+use crate::auth::ticket::{TicketStore, TICKET_LIFETIME_SECONDS};
+use crate::auth::token::{self, TokenClaims};
+use crate::auth::verification::{self, Recipient, TokenPurpose};
+use crate::error::{AccountError, AuthError};
 use crate::messaging::{
     broker::MessageBroker,
     model::{Message, MessageBody},
 };
+use crate::model::{AccountRequest, ApiKeyInfo, SessionInfo};
+
+use super::inventory::InventoryActorHandler;
 
 pub struct AuthActorHandler {
     pub id: Uuid,
@@ -64,7 +75,7 @@ impl AuthActorHandler {
         broker: &MessageBroker,
         username: &str,
         password: &str,
-    ) -> Result<String, anyhow::Error> {
+    ) -> Result<AuthTokens, AuthError> {
         let response = broker
             .request(Message {
                 id: Uuid::new_v4(),
@@ -74,35 +85,527 @@ impl AuthActorHandler {
                 }),
                 topic: Some("persistence".into()),
                 is_request: true,
-                timestamp: chrono::Utc::now().timestamp_millis() as u64,
+                timestamp: crate::clock::now().timestamp_millis() as u64,
             })
-            .await?;
+            .await
+            .map_err(|e| AuthError::Internal(e.to_string()))?;
 
         tracing::debug!(
             response = format!("{response:?}"),
             "received response from persistence layer"
         );
 
-        if let Some(msg) = response {
-            if let MessageBody::PersistenceQueryResponse(result) = msg.body {
-                match result {
-                    crate::persistence::QueryResponse::AuthSuccess(token) => {
-                        return Ok(token);
-                    }
-                    crate::persistence::QueryResponse::AuthFailed(reason) => {
-                        tracing::warn!("Authentication failed: {}", reason);
-                        return Err(anyhow::anyhow!("Authentication failed: {}", reason));
-                    }
-                    _ => {
-                        tracing::warn!("Unexpected response to Auth query");
+        match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AuthSuccess(tokens))) => {
+                Ok(tokens)
+            }
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(e))) => Err(e),
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))) => {
+                Err(blocked(status))
+            }
+            Some(_) => Err(AuthError::Internal(
+                "unexpected response to Auth query".into(),
+            )),
+            None => Err(AuthError::Internal(
+                "no response received for Auth query".into(),
+            )),
+        }
+    }
+
+    /// Creates the account and starts the actor for its new inventory.
+    async fn register(
+        broker: &MessageBroker,
+        account: AccountRequest,
+    ) -> Result<Uuid, AccountError> {
+        let account = account.normalize()?;
+
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::CreateAccount(account)),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+        match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountCreated {
+                account_id,
+                inventory_id,
+            })) => {
+                let inventory_actor = InventoryActorHandler { id: inventory_id };
+                let inventory_broker = broker.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = inventory_actor.listen(inventory_broker).await {
+                        tracing::error!("inventory actor {} stopped: {}", inventory_id, e);
                     }
+                });
+
+                // the account is usable without a verified address, so a failed mail can be
+                // requested again later
+                if let Err(e) = Self::send_account_mail(
+                    broker,
+                    Recipient::Account(account_id),
+                    TokenPurpose::VerifyEmail,
+                )
+                .await
+                {
+                    tracing::warn!(
+                        account_id = account_id.to_string(),
+                        "failed to send verification mail: {}",
+                        e
+                    );
                 }
+
+                Ok(account_id)
             }
-        } else {
-            tracing::warn!("No response received for Auth query");
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountCreationFailed(
+                e,
+            ))) => Err(e),
+            _ => Err(AccountError::Internal(
+                "unexpected response to CreateAccount query".into(),
+            )),
+        }
+    }
+
+    /// Issues a token for the recipient and mails it in the background, only its hash is stored.
+    /// Delivery failures are logged, a slow mail server mustn't hold up the actor.
+    async fn send_account_mail(
+        broker: &MessageBroker,
+        recipient: Recipient,
+        purpose: TokenPurpose,
+    ) -> Result<(), AccountError> {
+        let token = token::generate_refresh_token();
+
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::IssueAccountToken {
+                    recipient,
+                    purpose,
+                    token_hash: token::hash_token(&token),
+                }),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+        let recipient = match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenIssued(
+                recipient,
+            ))) => recipient,
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenFailed(e))) => {
+                return Err(e)
+            }
+            _ => {
+                return Err(AccountError::Internal(
+                    "unexpected response to IssueAccountToken query".into(),
+                ))
+            }
+        };
+
+        tokio::spawn(async move {
+            let outbox = crate::mail::outbox();
+            match outbox
+                .send(verification::compose(&outbox, purpose, &recipient, &token))
+                .await
+            {
+                Ok(()) => tracing::info!(
+                    account_id = recipient.account_id.to_string(),
+                    purpose = purpose.as_str(),
+                    "sent account mail"
+                ),
+                Err(e) => tracing::error!(
+                    account_id = recipient.account_id.to_string(),
+                    purpose = purpose.as_str(),
+                    "sending account mail failed: {}",
+                    e
+                ),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Redeems a mailed token. A password reset signs every session of the account out.
+    async fn redeem_account_token(
+        broker: &MessageBroker,
+        query: Query,
+    ) -> Result<Uuid, AccountError> {
+        let reset = matches!(query, Query::ResetPassword { .. });
+
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(query),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+        let account_id = match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenRedeemed(
+                account_id,
+            ))) => account_id,
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenFailed(e))) => {
+                return Err(e)
+            }
+            _ => {
+                return Err(AccountError::Internal(
+                    "unexpected response to account token query".into(),
+                ))
+            }
+        };
+
+        if reset {
+            Self::logout(broker, account_id, None)
+                .await
+                .map_err(|e| AccountError::Internal(e.to_string()))?;
         }
 
-        Err(anyhow::anyhow!("Authentication failed"))
+        Ok(account_id)
+    }
+
+    /// Stores a key limited to the caller's permissions. Keys can't create further keys, so a
+    /// leaked one can be revoked for good.
+    async fn create_api_key(
+        broker: &MessageBroker,
+        caller: TokenClaims,
+        name: String,
+        (scopes, expires_in_days): (Option<PermissionSet>, Option<i64>),
+        (prefix, key_hash): (String, String),
+    ) -> Result<ApiKeyInfo, AccountError> {
+        if caller.scope.is_some() {
+            return Err(AccountError::Forbidden(
+                "API keys cannot manage API keys".into(),
+            ));
+        }
+
+        let name = name.trim().to_string();
+        if !(1..=100).contains(&name.chars().count()) {
+            return Err(AccountError::InvalidApiKey(
+                "name must be between 1 and 100 characters".into(),
+            ));
+        }
+
+        let allowed = PermissionSet::from(caller.role);
+        let scopes = scopes.unwrap_or(allowed);
+        if !scopes.is_subset(&allowed) {
+            return Err(AccountError::Forbidden(format!(
+                "a {} cannot grant {}",
+                caller.role,
+                scopes
+                    .iter()
+                    .filter(|permission| !allowed.contains(*permission))
+                    .map(|permission| permission.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let expires_at = match expires_in_days {
+            Some(days) if !(1..=MAX_LIFETIME_DAYS).contains(&days) => {
+                return Err(AccountError::InvalidApiKey(format!(
+                    "expires_in_days must be between 1 and {}",
+                    MAX_LIFETIME_DAYS
+                )))
+            }
+            Some(days) => Some((chrono::Utc::now() + chrono::Duration::days(days)).naive_utc()),
+            None => None,
+        };
+
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::CreateApiKey {
+                    account_id: caller.account_id,
+                    name,
+                    scopes,
+                    prefix,
+                    key_hash,
+                    expires_at,
+                }),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+        match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::ApiKey(key))) => {
+                tracing::info!(
+                    account_id = key.account_id.to_string(),
+                    prefix = key.prefix,
+                    "created API key"
+                );
+                Ok(key)
+            }
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyFailed(e))) => Err(e),
+            _ => Err(AccountError::Internal(
+                "unexpected response to CreateApiKey query".into(),
+            )),
+        }
+    }
+
+    async fn api_keys(
+        broker: &MessageBroker,
+        account_id: Uuid,
+    ) -> Result<Vec<ApiKeyInfo>, AccountError> {
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::GetApiKeys { account_id }),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+        match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeys(keys))) => Ok(keys),
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyFailed(e))) => Err(e),
+            _ => Err(AccountError::Internal(
+                "unexpected response to GetApiKeys query".into(),
+            )),
+        }
+    }
+
+    /// Revokes one of the caller's keys and, like a logout, tells the connections using it to
+    /// close. An API key may only revoke itself.
+    async fn revoke_api_key(
+        broker: &MessageBroker,
+        caller: TokenClaims,
+        key_id: Uuid,
+    ) -> Result<(), AccountError> {
+        if caller.scope.is_some() && caller.session_id != key_id {
+            return Err(AccountError::Forbidden(
+                "an API key can only revoke itself".into(),
+            ));
+        }
+
+        let account_id = caller.account_id;
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::RevokeApiKey { account_id, key_id }),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+        match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyRevoked(_))) => {}
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyFailed(e))) => {
+                return Err(e)
+            }
+            _ => {
+                return Err(AccountError::Internal(
+                    "unexpected response to RevokeApiKey query".into(),
+                ))
+            }
+        }
+
+        tracing::info!(
+            account_id = account_id.to_string(),
+            key_id = key_id.to_string(),
+            "revoked API key"
+        );
+        Self::close_sessions(broker, &[key_id])
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))
+    }
+
+    /// Rotates the refresh token, revoking its session and closing the connections using it
+    /// when the token was already used before.
+    async fn refresh(broker: &MessageBroker, token_hash: String) -> Result<AuthTokens, AuthError> {
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::RefreshSession { token_hash }),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+        match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::SessionRefreshed(
+                tokens,
+            ))) => Ok(tokens),
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::RefreshTokenReused(
+                session_id,
+            ))) => {
+                tracing::warn!(
+                    session_id = session_id.to_string(),
+                    "refresh token was already used, session revoked"
+                );
+                broker
+                    .send(Message::new(
+                        MessageBody::SessionRevoked { session_id },
+                        Some(format!("out:session:{}", session_id)),
+                        false,
+                    ))
+                    .await
+                    .map_err(|e| AuthError::Internal(e.to_string()))?;
+
+                Err(AuthError::InvalidCredentials)
+            }
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))) => {
+                Err(blocked(status))
+            }
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::RefreshFailed(e))) => {
+                tracing::warn!("Refresh failed: {}", e);
+                Err(AuthError::InvalidCredentials)
+            }
+            _ => Err(AuthError::Internal(
+                "unexpected response to RefreshSession query".into(),
+            )),
+        }
+    }
+
+    async fn sessions(
+        broker: &MessageBroker,
+        account_id: Uuid,
+    ) -> Result<Vec<SessionInfo>, anyhow::Error> {
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::GetSessions { account_id }),
+                Some("persistence".into()),
+            ))
+            .await?;
+
+        match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::Sessions(sessions))) => {
+                Ok(sessions)
+            }
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::SessionsFailed(e))) => {
+                Err(anyhow::anyhow!(e))
+            }
+            _ => Err(anyhow::anyhow!("unexpected response to GetSessions query")),
+        }
+    }
+
+    /// Revokes the sessions and tells the connections using them to close.
+    async fn logout(
+        broker: &MessageBroker,
+        account_id: Uuid,
+        session_id: Option<Uuid>,
+    ) -> Result<Vec<Uuid>, anyhow::Error> {
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(Query::RevokeSessions {
+                    account_id,
+                    session_id,
+                }),
+                Some("persistence".into()),
+            ))
+            .await?;
+
+        let revoked = match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::SessionsRevoked(
+                revoked,
+            ))) => revoked,
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::SessionsFailed(e))) => {
+                return Err(anyhow::anyhow!(e))
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unexpected response to RevokeSessions query"
+                ))
+            }
+        };
+
+        Self::close_sessions(broker, &revoked).await?;
+
+        Ok(revoked)
+    }
+
+    /// Tells the connections of the sessions or API keys to close.
+    async fn close_sessions(
+        broker: &MessageBroker,
+        session_ids: &[Uuid],
+    ) -> Result<(), anyhow::Error> {
+        for session_id in session_ids {
+            broker
+                .send(Message::new(
+                    MessageBody::SessionRevoked {
+                        session_id: *session_id,
+                    },
+                    Some(format!("out:session:{}", session_id)),
+                    false,
+                ))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies a state or role change the caller holds the permission for. Blocking an account
+    /// or changing its role revokes its sessions and closes the connections of its API keys, so
+    /// it is logged out or picks up the new role when it comes back. The keys themselves stay,
+    /// they are refused while the account is blocked.
+    async fn update_account(
+        broker: &MessageBroker,
+        caller: TokenClaims,
+        query: Query,
+    ) -> Result<AccountStatus, AccountError> {
+        let permission = match &query {
+            Query::SetAccountState { state, .. } => state.required_permission(),
+            Query::SetAccountRole { .. } => Permission::ManageRoles,
+            _ => return Err(AccountError::Internal("not an account update".into())),
+        };
+        if !caller.can(permission) {
+            return Err(AccountError::Forbidden(format!(
+                "missing permission {}",
+                permission
+            )));
+        }
+        let revoke = !matches!(
+            query,
+            Query::SetAccountState {
+                state: AccountState::Active,
+                ..
+            }
+        );
+
+        let response = broker
+            .request(Message::new_request(
+                MessageBody::PersistenceQueryRequest(query),
+                Some("persistence".into()),
+            ))
+            .await
+            .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+        let status = match response.map(|msg| msg.body) {
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountUpdated(status))) => {
+                status
+            }
+            Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountUpdateFailed(e))) => {
+                return Err(e)
+            }
+            _ => {
+                return Err(AccountError::Internal(
+                    "unexpected response to account update".into(),
+                ))
+            }
+        };
+
+        tracing::info!(
+            caller = caller.account_id.to_string(),
+            account_id = status.account_id.to_string(),
+            role = status.role.as_str(),
+            state = status.state.as_str(),
+            "updated account"
+        );
+
+        if revoke {
+            Self::logout(broker, status.account_id, None)
+                .await
+                .map_err(|e| AccountError::Internal(e.to_string()))?;
+
+            let now = chrono::Utc::now().naive_utc();
+            let keys = Self::api_keys(broker, status.account_id)
+                .await?
+                .into_iter()
+                .filter(|key| key.expires_at.is_none_or(|expires_at| expires_at > now))
+                .map(|key| key.id)
+                .collect::<Vec<_>>();
+            Self::close_sessions(broker, &keys)
+                .await
+                .map_err(|e| AccountError::Internal(e.to_string()))?;
+        }
+
+        Ok(status)
     }
 
     pub async fn listen(&self, broker: MessageBroker) -> Result<(), anyhow::Error> {
@@ -115,19 +618,34 @@ impl AuthActorHandler {
         };
 
         let subbroker = broker.clone();
+        // messages are handled one at a time, so checks and updates of the throttle don't race
+        let mut throttle = LoginThrottle::default();
+        let mut tickets = TicketStore::default();
         while let Some(msg) = rx.recv().await {
-            tracing::info!("Received auth message: {:?}", msg);
+            tracing::trace!(?msg, "received auth message");
 
             let reply_topic = msg.reply_topic();
             match msg.body {
-                MessageBody::AuthenticationRequest { user, password } => {
-                    let response = match Self::authenticate(&broker, &user, &password).await {
-                        Ok(token) => Ok(token),
-                        Err(e) => {
-                            tracing::error!("Authentication error: {}", e);
-                            Err("Authentication failed".into())
-                        }
+                MessageBody::AuthenticationRequest {
+                    user,
+                    password,
+                    address,
+                } => {
+                    let response = match throttle.check(&user, address, chrono::Utc::now()) {
+                        Some(refusal) => Err(AuthError::Throttled(refusal)),
+                        None => Self::authenticate(&broker, &user, &password).await,
                     };
+                    match &response {
+                        // a blocked account still gave the right password
+                        Ok(_) | Err(AuthError::AccountBlocked { .. }) => throttle.succeeded(&user),
+                        Err(AuthError::InvalidCredentials) => {
+                            throttle.failed(&user, address, chrono::Utc::now())
+                        }
+                        Err(_) => {}
+                    }
+                    if let Err(e) = &response {
+                        tracing::warn!(?address, "Authentication failed: {}", e);
+                    }
 
                     subbroker
                         .send(Message {
@@ -135,10 +653,250 @@ impl AuthActorHandler {
                             body: MessageBody::AuthenticationResponse(response),
                             topic: Some(reply_topic),
                             is_request: false,
-                            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+                            timestamp: crate::clock::now().timestamp_millis() as u64,
                         })
                         .await?;
                 }
+                MessageBody::RefreshRequest { token_hash } => {
+                    let response = Self::refresh(&broker, token_hash).await;
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::RefreshResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::RegistrationRequest(account) => {
+                    tracing::info!("Registration attempt for user: {}", account.username);
+
+                    let response = Self::register(&broker, account).await;
+                    if let Err(e) = &response {
+                        tracing::warn!("Registration failed: {}", e);
+                    }
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::RegistrationResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::SessionsRequest { account_id } => {
+                    let response = Self::sessions(&broker, account_id)
+                        .await
+                        .map_err(|e| e.to_string());
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::SessionsResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::LogoutRequest {
+                    account_id,
+                    session_id,
+                } => {
+                    let response = Self::logout(&broker, account_id, session_id)
+                        .await
+                        .map_err(|e| e.to_string());
+                    match &response {
+                        Ok(revoked) => tracing::info!(
+                            account_id = account_id.to_string(),
+                            "revoked {} sessions",
+                            revoked.len()
+                        ),
+                        Err(e) => tracing::warn!("Logout failed: {}", e),
+                    }
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::LogoutResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::AccountStateRequest {
+                    caller,
+                    account_id,
+                    state,
+                    reason,
+                } => {
+                    let query = Query::SetAccountState {
+                        caller,
+                        account_id,
+                        state,
+                        reason,
+                    };
+                    let response = Self::update_account(&broker, caller, query).await;
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::AccountUpdateResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::AccountRoleRequest {
+                    caller,
+                    account_id,
+                    role,
+                } => {
+                    let query = Query::SetAccountRole {
+                        caller_role: caller.role,
+                        account_id,
+                        role,
+                    };
+                    let response = Self::update_account(&broker, caller, query).await;
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::AccountUpdateResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::AccountMailRequest { recipient, purpose } => {
+                    let response = Self::send_account_mail(&broker, recipient, purpose).await;
+                    if let Err(e) = &response {
+                        tracing::warn!(purpose = purpose.as_str(), "Account mail failed: {}", e);
+                    }
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::AccountMailResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::VerifyEmailRequest { token_hash } => {
+                    let response =
+                        Self::redeem_account_token(&broker, Query::VerifyEmail { token_hash })
+                            .await;
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::AccountTokenResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::ResetPasswordRequest {
+                    token_hash,
+                    password,
+                } => {
+                    let response = match crate::model::validate_password(password.expose()) {
+                        Ok(()) => {
+                            Self::redeem_account_token(
+                                &broker,
+                                Query::ResetPassword {
+                                    token_hash,
+                                    password,
+                                },
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::AccountTokenResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::CreateApiKeyRequest {
+                    caller,
+                    name,
+                    scopes,
+                    expires_in_days,
+                    prefix,
+                    key_hash,
+                } => {
+                    let response = Self::create_api_key(
+                        &broker,
+                        caller,
+                        name,
+                        (scopes, expires_in_days),
+                        (prefix, key_hash),
+                    )
+                    .await;
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::ApiKeyResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::ApiKeysRequest { account_id } => {
+                    let response = Self::api_keys(&broker, account_id).await;
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::ApiKeysResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::RevokeApiKeyRequest { caller, key_id } => {
+                    let response = Self::revoke_api_key(&broker, caller, key_id).await;
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::RevokeApiKeyResponse(response),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::LockoutsRequest => {
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::LockoutsResponse(throttle.lockouts(chrono::Utc::now())),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::ConnectionTicketRequest {
+                    claims,
+                    ticket_hash,
+                } => {
+                    tickets.insert(ticket_hash, claims, chrono::Utc::now());
+
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::ConnectionTicketResponse(Ok(TICKET_LIFETIME_SECONDS)),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
+                MessageBody::RedeemTicketRequest { ticket_hash } => {
+                    subbroker
+                        .send(Message::new(
+                            MessageBody::RedeemTicketResponse(
+                                tickets.redeem(&ticket_hash, chrono::Utc::now()),
+                            ),
+                            Some(reply_topic),
+                            false,
+                        ))
+                        .await?;
+                }
                 _ => tracing::warn!("Unexpected message body: {:?}", msg.body),
             }
         }
@@ -158,3 +916,10 @@ impl AuthActorHandler {
     //     Ok(())
     // }
 }
+
+fn blocked(status: AccountStatus) -> AuthError {
+    AuthError::AccountBlocked {
+        state: status.state,
+        reason: status.reason,
+    }
+}
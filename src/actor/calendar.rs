@@ -0,0 +1,83 @@
+use uuid::Uuid;
+
+use crate::messaging::{
+    broker::MessageBroker,
+    model::{Message, MessageBody},
+};
+use crate::model::{Calendar, TickPhase};
+
+use super::{ack_tick_phase, load_world_settings, register_tick_phases};
+
+/// Turns tick sequence numbers into game dates and announces new days and seasons on the
+/// `global` topic during the pre-tick phase, before any actor produces or consumes.
+pub struct CalendarActorHandler {
+    pub id: Uuid,
+}
+
+impl Default for CalendarActorHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CalendarActorHandler {
+    pub fn new() -> Self {
+        CalendarActorHandler { id: Uuid::new_v4() }
+    }
+
+    pub async fn listen(&self, broker: MessageBroker) -> Result<(), anyhow::Error> {
+        let calendar = match load_world_settings(&broker).await {
+            Ok(settings) => Calendar::from(&settings),
+            Err(e) => {
+                tracing::warn!(
+                    "failed to load world settings, using the default calendar: {}",
+                    e
+                );
+                Calendar::default()
+            }
+        };
+
+        let (sub_id, mut tick_rx) = broker
+            .subscribe("ticks")
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to subscribe to tick channel: {}", e))?;
+        tracing::debug!(
+            actor_id = self.id.to_string(),
+            sub_id = sub_id.to_string(),
+            ?calendar,
+            "calendar actor subscribed to tick messages"
+        );
+
+        register_tick_phases(&broker, self.id, vec![TickPhase::PreTick]).await?;
+
+        while let Some(msg) = tick_rx.recv().await {
+            let MessageBody::Tick {
+                seq,
+                phase: TickPhase::PreTick,
+                ..
+            } = msg.body
+            else {
+                continue;
+            };
+
+            for event in calendar.events(seq) {
+                tracing::info!(seq, ?event, "calendar event");
+
+                broker
+                    .send(Message::new(
+                        MessageBody::CalendarEvent(event),
+                        Some("global".into()),
+                        false,
+                    ))
+                    .await?;
+            }
+
+            ack_tick_phase(&broker, self.id, seq, TickPhase::PreTick).await?;
+        }
+
+        let _ = broker.unsubscribe(sub_id).await;
+        let _ = register_tick_phases(&broker, self.id, Vec::new()).await;
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::messaging::broker::MessageBroker;
 use crate::messaging::model::{Message, MessageBody};
+use crate::model::{BuildingCommand, PolicyOutcome, ProductionReport, Season, UpkeepReport};
 use crate::persistence::{Query, QueryResponse};
 use uuid::Uuid;
 
@@ -20,9 +21,9 @@ pub async fn handle_build_request(
 
     match response {
         Ok(Some(Message {
-            body: MessageBody::PersistenceQueryResponse(QueryResponse::CreateBuilding(building_id)),
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::CreateBuilding(building)),
             ..
-        })) => MessageBody::BuildResponse(Ok(building_id)),
+        })) => MessageBody::BuildResponse(Ok(building)),
 
         Ok(Some(Message {
             body: MessageBody::PersistenceQueryResponse(QueryResponse::CreateBuildingFailed(e)),
@@ -38,3 +39,335 @@ pub async fn handle_build_request(
         }
     }
 }
+
+/// Forwards a build queue query to persistence and wraps the resulting queue for the client.
+pub async fn handle_build_queue_request(broker: &MessageBroker, query: Query) -> MessageBody {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(query),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::BuildQueue(queue)),
+            ..
+        })) => MessageBody::BuildQueueResponse(Ok(queue)),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::BuildQueueFailed(e)),
+            ..
+        })) => MessageBody::BuildQueueResponse(Err(format!("Build queue request failed: {}", e))),
+
+        Ok(Some(_)) => MessageBody::BuildQueueResponse(Err("Unexpected response type".into())),
+
+        Ok(None) => {
+            MessageBody::BuildQueueResponse(Err("No response received from persistence".into()))
+        }
+
+        Err(e) => MessageBody::BuildQueueResponse(Err(format!(
+            "Failed to send persistence request: {}",
+            e
+        ))),
+    }
+}
+
+pub async fn handle_building_command(
+    broker: &MessageBroker,
+    inventory_id: Uuid,
+    building_id: Uuid,
+    command: BuildingCommand,
+) -> MessageBody {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::ApplyBuildingCommand {
+                inventory_id,
+                building_id,
+                command,
+            }),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::BuildingCommand(outcome)),
+            ..
+        })) => MessageBody::BuildingCommandResponse(Ok(outcome)),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::BuildingCommandFailed(e)),
+            ..
+        })) => MessageBody::BuildingCommandResponse(Err(format!(
+            "Failed to {} building: {}",
+            command, e
+        ))),
+
+        Ok(Some(_)) => MessageBody::BuildingCommandResponse(Err("Unexpected response type".into())),
+
+        Ok(None) => MessageBody::BuildingCommandResponse(Err(
+            "No response received from persistence".into(),
+        )),
+
+        Err(e) => MessageBody::BuildingCommandResponse(Err(format!(
+            "Failed to send persistence request: {}",
+            e
+        ))),
+    }
+}
+
+pub async fn handle_blueprints_request(broker: &MessageBroker, inventory_id: Uuid) -> MessageBody {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::GetBlueprintAvailability { inventory_id }),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body:
+                MessageBody::PersistenceQueryResponse(QueryResponse::BlueprintAvailability(blueprints)),
+            ..
+        })) => MessageBody::BlueprintsResponse(Ok(blueprints)),
+
+        Ok(Some(Message {
+            body:
+                MessageBody::PersistenceQueryResponse(QueryResponse::BlueprintAvailabilityFailed(e)),
+            ..
+        })) => MessageBody::BlueprintsResponse(Err(format!("Failed to list blueprints: {}", e))),
+
+        Ok(Some(_)) => MessageBody::BlueprintsResponse(Err("Unexpected response type".into())),
+
+        Ok(None) => {
+            MessageBody::BlueprintsResponse(Err("No response received from persistence".into()))
+        }
+
+        Err(e) => MessageBody::BlueprintsResponse(Err(format!(
+            "Failed to send persistence request: {}",
+            e
+        ))),
+    }
+}
+
+pub async fn handle_resources_request(broker: &MessageBroker, inventory_id: Uuid) -> MessageBody {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::GetResources { inventory_id }),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::Resources(stock)),
+            ..
+        })) => MessageBody::ResourcesResponse(Ok(stock)),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::ResourcesFailed(e)),
+            ..
+        })) => MessageBody::ResourcesResponse(Err(format!("Failed to list resources: {}", e))),
+
+        Ok(Some(_)) => MessageBody::ResourcesResponse(Err("Unexpected response type".into())),
+
+        Ok(None) => {
+            MessageBody::ResourcesResponse(Err("No response received from persistence".into()))
+        }
+
+        Err(e) => MessageBody::ResourcesResponse(Err(format!(
+            "Failed to send persistence request: {}",
+            e
+        ))),
+    }
+}
+
+/// Runs the inventory's production recipes for the current tick, with the modifiers of the
+/// current season. Failures are logged and skipped so a broken recipe doesn't stop the actor.
+pub async fn handle_production_tick(
+    broker: &MessageBroker,
+    inventory_id: Uuid,
+    season: Season,
+) -> Option<ProductionReport> {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::RunProduction {
+                inventory_id,
+                season,
+            }),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::Production(report)),
+            ..
+        })) => Some(report),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::ProductionFailed(e)),
+            ..
+        })) => {
+            tracing::warn!(%inventory_id, "production run failed: {}", e);
+            None
+        }
+
+        Ok(_) => {
+            tracing::warn!(%inventory_id, "unexpected response to production run");
+            None
+        }
+
+        Err(e) => {
+            tracing::error!(%inventory_id, "failed to send production request: {}", e);
+            None
+        }
+    }
+}
+
+/// Charges the upkeep due this tick. Failures are logged and skipped like production runs.
+pub async fn handle_upkeep_tick(
+    broker: &MessageBroker,
+    inventory_id: Uuid,
+    seq: u64,
+) -> Option<UpkeepReport> {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::ApplyUpkeep { inventory_id, seq }),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::Upkeep(report)),
+            ..
+        })) => Some(report),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::UpkeepFailed(e)),
+            ..
+        })) => {
+            tracing::warn!(%inventory_id, "upkeep run failed: {}", e);
+            None
+        }
+
+        Ok(_) => {
+            tracing::warn!(%inventory_id, "unexpected response to upkeep run");
+            None
+        }
+
+        Err(e) => {
+            tracing::error!(%inventory_id, "failed to send upkeep request: {}", e);
+            None
+        }
+    }
+}
+
+/// Forwards a policy query to persistence and wraps the inventory's policy list for the client.
+pub async fn handle_policies_request(broker: &MessageBroker, query: Query) -> MessageBody {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(query),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::Policies(policies)),
+            ..
+        })) => MessageBody::PoliciesResponse(Ok(policies)),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::PoliciesFailed(e)),
+            ..
+        })) => MessageBody::PoliciesResponse(Err(format!("Policy request failed: {}", e))),
+
+        Ok(Some(_)) => MessageBody::PoliciesResponse(Err("Unexpected response type".into())),
+
+        Ok(None) => {
+            MessageBody::PoliciesResponse(Err("No response received from persistence".into()))
+        }
+
+        Err(e) => {
+            MessageBody::PoliciesResponse(Err(format!("Failed to send persistence request: {}", e)))
+        }
+    }
+}
+
+pub async fn handle_policy_dry_run(
+    broker: &MessageBroker,
+    inventory_id: Uuid,
+    policy_id: Option<Uuid>,
+) -> MessageBody {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::DryRunPolicies {
+                inventory_id,
+                policy_id,
+            }),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::PolicyOutcomes(outcomes)),
+            ..
+        })) => MessageBody::PolicyReport(Ok(outcomes)),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::PolicyOutcomesFailed(e)),
+            ..
+        })) => MessageBody::PolicyReport(Err(format!("Policy dry run failed: {}", e))),
+
+        Ok(Some(_)) => MessageBody::PolicyReport(Err("Unexpected response type".into())),
+
+        Ok(None) => MessageBody::PolicyReport(Err("No response received from persistence".into())),
+
+        Err(e) => {
+            MessageBody::PolicyReport(Err(format!("Failed to send persistence request: {}", e)))
+        }
+    }
+}
+
+/// Evaluates the policies due this tick. Failures are logged and skipped like production runs.
+pub async fn handle_policy_tick(
+    broker: &MessageBroker,
+    inventory_id: Uuid,
+    seq: u64,
+) -> Option<Vec<PolicyOutcome>> {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::EvaluatePolicies { inventory_id, seq }),
+            Some("persistence".into()),
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::PolicyOutcomes(outcomes)),
+            ..
+        })) => Some(outcomes),
+
+        Ok(Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::PolicyOutcomesFailed(e)),
+            ..
+        })) => {
+            tracing::warn!(%inventory_id, "policy evaluation failed: {}", e);
+            None
+        }
+
+        Ok(_) => {
+            tracing::warn!(%inventory_id, "unexpected response to policy evaluation");
+            None
+        }
+
+        Err(e) => {
+            tracing::error!(%inventory_id, "failed to send policy request: {}", e);
+            None
+        }
+    }
+}
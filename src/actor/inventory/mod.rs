@@ -5,10 +5,20 @@ use uuid::Uuid;
 
 use crate::messaging::broker::MessageBroker;
 use crate::messaging::model::{Message, MessageBody};
+use crate::model::{Calendar, StalledBuilding, TickPhase};
 use crate::persistence::Query;
 
+use super::{ack_tick_phase, load_world_settings, register_tick_phases};
+
 mod handler;
 
+/// Phases of each tick the inventory actor does work in, in the order the ticker runs them.
+const TICK_PHASES: [TickPhase; 3] = [
+    TickPhase::Production,
+    TickPhase::Consumption,
+    TickPhase::Resolution,
+];
+
 pub struct InventoryActorHandler {
     pub id: Uuid,
 }
@@ -55,10 +65,27 @@ impl InventoryActorHandler {
             tick_sub_id
         );
 
+        // the season of a tick comes from the same calendar the calendar actor announces
+        let calendar = match load_world_settings(&broker).await {
+            Ok(settings) => Calendar::from(&settings),
+            Err(e) => {
+                tracing::warn!(
+                    actor_id = self.id.to_string(),
+                    "failed to load world settings, using the default calendar: {}",
+                    e
+                );
+                Calendar::default()
+            }
+        };
+
+        register_tick_phases(&broker, self.id, TICK_PHASES.to_vec()).await?;
+
         let receivers = vec![inventory_rx, tick_rx];
         let mut streams = select_all(receivers.into_iter().map(ReceiverStream::new));
 
         let subbroker = broker.clone();
+        // Stalled buildings from the last production run, used to only notify clients on changes
+        let mut stalled = Vec::new();
         while let Some(msg) = streams.next().await {
             tracing::trace!(?msg, "received inventory message");
 
@@ -82,35 +109,295 @@ impl InventoryActorHandler {
 
                     subbroker.send(reply).await?;
                 }
-                MessageBody::Tick { seq, timestamp } => {
+                MessageBody::BuildQueueRequest { inventory_id } => {
+                    let response = handler::handle_build_queue_request(
+                        &subbroker,
+                        Query::GetBuildQueue { inventory_id },
+                    )
+                    .await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::ReorderBuildQueueRequest {
+                    inventory_id,
+                    building_id,
+                    position,
+                } => {
+                    tracing::info!(
+                        "Received queue reorder for inventory: {}, building: {}, position: {}",
+                        inventory_id,
+                        building_id,
+                        position
+                    );
+
+                    let response = handler::handle_build_queue_request(
+                        &subbroker,
+                        Query::ReorderBuildQueue {
+                            inventory_id,
+                            building_id,
+                            position,
+                        },
+                    )
+                    .await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::RemoveFromBuildQueueRequest {
+                    inventory_id,
+                    building_id,
+                } => {
+                    tracing::info!(
+                        "Received queue removal for inventory: {}, building: {}",
+                        inventory_id,
+                        building_id
+                    );
+
+                    let response = handler::handle_build_queue_request(
+                        &subbroker,
+                        Query::RemoveFromBuildQueue {
+                            inventory_id,
+                            building_id,
+                        },
+                    )
+                    .await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::BuildingCommandRequest {
+                    inventory_id,
+                    building_id,
+                    command,
+                } => {
+                    tracing::info!(
+                        "Received {} command for inventory: {}, building: {}",
+                        command,
+                        inventory_id,
+                        building_id
+                    );
+
+                    let response = handler::handle_building_command(
+                        &subbroker,
+                        inventory_id,
+                        building_id,
+                        command,
+                    )
+                    .await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::BlueprintsRequest { inventory_id } => {
+                    let response =
+                        handler::handle_blueprints_request(&subbroker, inventory_id).await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::ResourcesRequest { inventory_id } => {
+                    let response =
+                        handler::handle_resources_request(&subbroker, inventory_id).await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::PoliciesRequest { inventory_id } => {
+                    let response = handler::handle_policies_request(
+                        &subbroker,
+                        Query::GetPolicies { inventory_id },
+                    )
+                    .await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::CreatePolicyRequest {
+                    inventory_id,
+                    name,
+                    rule,
+                    interval_ticks,
+                } => {
+                    tracing::info!(
+                        "Received new policy for inventory: {}, name: {}",
+                        inventory_id,
+                        name
+                    );
+
+                    let response = handler::handle_policies_request(
+                        &subbroker,
+                        Query::CreatePolicy {
+                            inventory_id,
+                            name,
+                            rule,
+                            interval_ticks,
+                        },
+                    )
+                    .await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::DeletePolicyRequest {
+                    inventory_id,
+                    policy_id,
+                } => {
+                    let response = handler::handle_policies_request(
+                        &subbroker,
+                        Query::DeletePolicy {
+                            inventory_id,
+                            policy_id,
+                        },
+                    )
+                    .await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::PolicyDryRunRequest {
+                    inventory_id,
+                    policy_id,
+                } => {
+                    let response =
+                        handler::handle_policy_dry_run(&subbroker, inventory_id, policy_id).await;
+
+                    let reply = Message::new(response, Some(reply_topic.clone()), false);
+
+                    subbroker.send(reply).await?;
+                }
+                MessageBody::Tick {
+                    seq,
+                    timestamp,
+                    phase,
+                } => {
                     tracing::trace!(
                         seq,
+                        ?phase,
                         timestamp = format!("{}", timestamp.to_rfc3339()),
                         actor_id = self.id.to_string(),
                         "Inventory actor received tick"
                     );
 
-                    let response = subbroker
-                        .request(Message::new_request(
-                            MessageBody::PersistenceQueryRequest(Query::ProgressBuildings {
-                                inventory_id: self.id,
-                            }),
-                            Some("persistence".into()),
-                        ))
-                        .await?;
+                    if matches!(phase, TickPhase::PreTick | TickPhase::PostTick) {
+                        continue;
+                    }
 
-                    tracing::trace!(
-                        actor_id = self.id.to_string(),
-                        "Inventory actor processed tick {}: persistence response: {:?}",
-                        seq,
-                        response
-                    );
+                    // a failed phase is logged and still acked, so the ticker never waits on it
+                    if let Err(e) = self
+                        .run_tick_phase(&subbroker, &calendar, &mut stalled, seq, phase)
+                        .await
+                    {
+                        tracing::error!(
+                            actor_id = self.id.to_string(),
+                            seq,
+                            ?phase,
+                            "inventory tick phase failed: {}",
+                            e
+                        );
+                    }
+
+                    if let Err(e) = ack_tick_phase(&subbroker, self.id, seq, phase).await {
+                        tracing::error!(
+                            actor_id = self.id.to_string(),
+                            seq,
+                            ?phase,
+                            "failed to ack tick phase: {}",
+                            e
+                        );
+                    }
                 }
                 _ => tracing::warn!("Unexpected message body: {:?}", msg.body),
             }
         }
 
         let _ = broker.unsubscribe(sub_id).await;
+        let _ = register_tick_phases(&broker, self.id, Vec::new()).await;
+
+        Ok(())
+    }
+
+    /// Runs the inventory's work for one tick phase.
+    async fn run_tick_phase(
+        &self,
+        subbroker: &MessageBroker,
+        calendar: &Calendar,
+        stalled: &mut Vec<StalledBuilding>,
+        seq: u64,
+        phase: TickPhase,
+    ) -> Result<(), anyhow::Error> {
+        match phase {
+            TickPhase::Production => {
+                let response = subbroker
+                    .request(Message::new_request(
+                        MessageBody::PersistenceQueryRequest(Query::ProgressBuildings {
+                            inventory_id: self.id,
+                        }),
+                        Some("persistence".into()),
+                    ))
+                    .await?;
+
+                tracing::trace!(
+                    actor_id = self.id.to_string(),
+                    "Inventory actor processed tick {}: persistence response: {:?}",
+                    seq,
+                    response
+                );
+
+                if let Some(report) =
+                    handler::handle_production_tick(subbroker, self.id, calendar.date(seq).season)
+                        .await
+                {
+                    if report.stalled != *stalled {
+                        *stalled = report.stalled.clone();
+
+                        subbroker
+                            .send(Message::new(
+                                MessageBody::ProductionReport(report),
+                                Some(format!("out:inventory:{}", self.id)),
+                                false,
+                            ))
+                            .await?;
+                    }
+                }
+            }
+            TickPhase::Consumption => {
+                if let Some(report) = handler::handle_upkeep_tick(subbroker, self.id, seq).await {
+                    if !report.degraded.is_empty() {
+                        subbroker
+                            .send(Message::new(
+                                MessageBody::UpkeepReport(report),
+                                Some(format!("out:inventory:{}", self.id)),
+                                false,
+                            ))
+                            .await?;
+                    }
+                }
+            }
+            TickPhase::Resolution => {
+                if let Some(outcomes) = handler::handle_policy_tick(subbroker, self.id, seq).await {
+                    if outcomes.iter().any(|o| o.executed) {
+                        subbroker
+                            .send(Message::new(
+                                MessageBody::PolicyReport(Ok(outcomes)),
+                                Some(format!("out:inventory:{}", self.id)),
+                                false,
+                            ))
+                            .await?;
+                    }
+                }
+            }
+            TickPhase::PreTick | TickPhase::PostTick => {}
+        }
 
         Ok(())
     }
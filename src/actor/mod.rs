@@ -1,5 +1,83 @@
+use uuid::Uuid;
+
+use crate::messaging::{
+    broker::MessageBroker,
+    model::{Message, MessageBody},
+};
+use crate::model::{TickPhase, WorldSettings};
+use crate::persistence::{Query, QueryResponse};
+
 pub mod model;
 
 pub mod auth;
+pub mod calendar;
 pub mod inventory;
+pub mod scheduler;
 pub mod ticker;
+
+pub(crate) async fn load_world_settings(
+    broker: &MessageBroker,
+) -> Result<WorldSettings, anyhow::Error> {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::GetWorldSettings),
+            Some("persistence".into()),
+        ))
+        .await?;
+
+    match response {
+        Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::WorldSettings(settings)),
+            ..
+        }) => Ok(settings),
+        Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::WorldSettingsFailed(e)),
+            ..
+        }) => Err(anyhow::anyhow!(e)),
+        _ => Err(anyhow::anyhow!(
+            "unexpected response to world settings query"
+        )),
+    }
+}
+
+/// Registers an actor with the ticker for `phases`, an empty list unregisters it.
+pub(crate) async fn register_tick_phases(
+    broker: &MessageBroker,
+    participant: Uuid,
+    phases: Vec<TickPhase>,
+) -> Result<(), anyhow::Error> {
+    broker
+        .send(Message::new(
+            MessageBody::TickRegistration {
+                participant,
+                phases,
+            },
+            Some("ack:ticker".into()),
+            false,
+        ))
+        .await?;
+
+    Ok(())
+}
+
+/// Tells the ticker the actor is done with `phase` of tick `seq`.
+pub(crate) async fn ack_tick_phase(
+    broker: &MessageBroker,
+    participant: Uuid,
+    seq: u64,
+    phase: TickPhase,
+) -> Result<(), anyhow::Error> {
+    broker
+        .send(Message::new(
+            MessageBody::TickAck {
+                participant,
+                seq,
+                phase,
+            },
+            Some("ack:ticker".into()),
+            false,
+        ))
+        .await?;
+
+    Ok(())
+}
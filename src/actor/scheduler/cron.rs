@@ -0,0 +1,125 @@
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+/// A five field cron expression: minute, hour, day of month, month and day of week (0 is
+/// Sunday). Fields accept `*`, numbers, ranges `a-b`, lists `a,b` and steps `*/n` or `a-b/n`.
+/// Unlike classic cron, day of month and day of week must both match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields in cron expression '{}', found {}",
+                expression,
+                fields.len()
+            ));
+        };
+
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: parse_field(weekdays, 0, 6)?,
+        })
+    }
+
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+
+        bit(self.minutes, time.minute())
+            && bit(self.hours, time.hour())
+            && bit(self.days, time.day())
+            && bit(self.months, time.month())
+            && bit(self.weekdays, time.weekday().num_days_from_sunday())
+    }
+}
+
+/// Parses one field into a bit mask of the allowed values.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in cron field '{}'", field))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, field)?, parse_value(end, field)?),
+                None => {
+                    let value = parse_value(range, field)?;
+                    (value, value)
+                }
+            },
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!(
+                "cron field '{}' is outside of {}-{}",
+                field, min, max
+            ));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+fn parse_value(value: &str, field: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' in cron field '{}'", value, field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cron_matches() {
+        let hourly = CronSchedule::parse("0 * * * *").unwrap();
+        let workdays = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+
+        // 2025-12-01 is a Monday
+        let monday_nine = Utc.with_ymd_and_hms(2025, 12, 1, 9, 0, 0).unwrap();
+        let monday_late = Utc.with_ymd_and_hms(2025, 12, 1, 18, 30, 0).unwrap();
+        let sunday = Utc.with_ymd_and_hms(2025, 11, 30, 10, 45, 0).unwrap();
+
+        assert!(hourly.matches(monday_nine));
+        assert!(!hourly.matches(monday_late));
+        assert!(workdays.matches(monday_nine));
+        assert!(!workdays.matches(monday_late));
+        assert!(!workdays.matches(sunday));
+    }
+
+    #[test]
+    fn test_cron_rejects_invalid_expressions() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+        assert!(CronSchedule::parse("0,30 0 1 1,7 *").is_ok());
+    }
+}
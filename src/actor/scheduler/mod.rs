@@ -0,0 +1,352 @@
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::messaging::{
+    broker::MessageBroker,
+    model::{Message, MessageBody},
+};
+use crate::model::{
+    JobRecord, JobResult, JobSchedule, MaintenanceReport, MaintenanceTask, TickPhase,
+};
+use crate::persistence::{Query, QueryResponse};
+
+pub use cron::CronSchedule;
+
+mod cron;
+
+const CONTROL_TOPIC: &str = "in:scheduler";
+/// How often cron schedules are checked, often enough that no matching minute is missed.
+const CRON_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Runs named maintenance jobs. Tick jobs are checked on every post-tick phase, cron jobs
+/// against wall time so they keep running while the ticker is paused or slow. Jobs run through
+/// the persistence actor in the background, a job that is still running when it comes due
+/// again is skipped rather than started twice.
+pub struct SchedulerActorHandler {
+    pub id: Uuid,
+    jobs: Vec<Job>,
+}
+
+struct Job {
+    record: JobRecord,
+    trigger: Trigger,
+    /// Minute since the epoch a cron job last came due in, so it runs once per matching minute.
+    last_minute: Option<i64>,
+}
+
+enum Trigger {
+    EveryTicks(u64),
+    Cron(CronSchedule),
+}
+
+/// What the scheduler is checking its jobs for.
+#[derive(Debug, Clone, Copy)]
+enum Due {
+    Tick(u64),
+    Time(DateTime<Utc>),
+}
+
+impl Job {
+    fn is_due(&mut self, due: Due) -> bool {
+        match (&self.trigger, due) {
+            (Trigger::EveryTicks(ticks), Due::Tick(seq)) => seq.is_multiple_of(*ticks),
+            (Trigger::Cron(cron), Due::Time(now)) => {
+                let minute = now.timestamp().div_euclid(60);
+                if self.last_minute == Some(minute) || !cron.matches(now) {
+                    return false;
+                }
+                self.last_minute = Some(minute);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for SchedulerActorHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulerActorHandler {
+    pub fn new() -> Self {
+        SchedulerActorHandler {
+            id: Uuid::new_v4(),
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Adds a job, failing on duplicate names and invalid schedules.
+    pub fn register(
+        &mut self,
+        name: &str,
+        schedule: JobSchedule,
+        task: MaintenanceTask,
+    ) -> Result<(), String> {
+        if self.jobs.iter().any(|job| job.record.name == name) {
+            return Err(format!("job '{}' is already registered", name));
+        }
+
+        let trigger = match &schedule {
+            JobSchedule::EveryTicks { ticks: 0 } => {
+                return Err(format!("job '{}' must run every 1 or more ticks", name))
+            }
+            JobSchedule::EveryTicks { ticks } => Trigger::EveryTicks(*ticks),
+            JobSchedule::Cron { expression } => Trigger::Cron(CronSchedule::parse(expression)?),
+        };
+
+        self.jobs.push(Job {
+            record: JobRecord {
+                name: name.into(),
+                task,
+                schedule,
+                running: false,
+                runs: 0,
+                skipped: 0,
+                last_started_at: None,
+                last_result: None,
+            },
+            trigger,
+            last_minute: None,
+        });
+
+        Ok(())
+    }
+
+    pub fn jobs(&self) -> Vec<JobRecord> {
+        self.jobs.iter().map(|job| job.record.clone()).collect()
+    }
+
+    pub async fn listen(mut self, broker: MessageBroker) -> Result<(), anyhow::Error> {
+        let (tick_sub_id, mut tick_rx) = broker
+            .subscribe("ticks")
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to subscribe to tick channel: {}", e))?;
+        let (control_sub_id, mut control_rx) = broker
+            .subscribe(CONTROL_TOPIC)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to subscribe to scheduler channel: {}", e))?;
+        tracing::debug!(
+            actor_id = self.id.to_string(),
+            jobs = self.jobs.len(),
+            "scheduler actor subscribed to tick and control messages"
+        );
+
+        let (done_tx, mut done_rx) = mpsc::channel::<(usize, JobResult)>(self.jobs.len().max(1));
+        let mut cron_interval = tokio::time::interval(CRON_CHECK_INTERVAL);
+        cron_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                msg = tick_rx.recv() => {
+                    let Some(msg) = msg else { break };
+                    let MessageBody::Tick {
+                        seq,
+                        phase: TickPhase::PostTick,
+                        ..
+                    } = msg.body
+                    else {
+                        continue;
+                    };
+
+                    self.run_due_jobs(&broker, &done_tx, Due::Tick(seq));
+                }
+                _ = cron_interval.tick() => {
+                    // wall time, a virtual clock only moves with ticks
+                    self.run_due_jobs(&broker, &done_tx, Due::Time(Utc::now()));
+                }
+                Some(msg) = control_rx.recv() => {
+                    let reply_topic = msg.reply_topic();
+                    let MessageBody::SchedulerStatusRequest = msg.body else {
+                        tracing::warn!("Unexpected scheduler message body: {:?}", msg.body);
+                        continue;
+                    };
+
+                    let reply = Message::new(
+                        MessageBody::SchedulerStatus(Ok(self.jobs())),
+                        Some(reply_topic),
+                        false,
+                    );
+                    if let Err(e) = broker.send(reply).await {
+                        tracing::error!("failed to reply to scheduler status request: {}", e);
+                    }
+                }
+                Some((index, result)) = done_rx.recv() => {
+                    let record = &mut self.jobs[index].record;
+                    if result.success {
+                        tracing::info!(job = record.name, result = result.message, "scheduled job finished");
+                    } else {
+                        tracing::warn!(job = record.name, error = result.message, "scheduled job failed");
+                    }
+
+                    record.running = false;
+                    record.last_result = Some(result);
+                }
+            }
+        }
+
+        let _ = broker.unsubscribe(tick_sub_id).await;
+        let _ = broker.unsubscribe(control_sub_id).await;
+
+        Ok(())
+    }
+
+    fn run_due_jobs(
+        &mut self,
+        broker: &MessageBroker,
+        done_tx: &mpsc::Sender<(usize, JobResult)>,
+        due: Due,
+    ) {
+        let now = crate::clock::now();
+
+        for (index, job) in self.jobs.iter_mut().enumerate() {
+            if !job.is_due(due) {
+                continue;
+            }
+
+            if job.record.running {
+                tracing::warn!(
+                    job = job.record.name,
+                    ?due,
+                    "skipping scheduled job, previous run is still going"
+                );
+                job.record.skipped += 1;
+                continue;
+            }
+
+            tracing::debug!(job = job.record.name, ?due, "starting scheduled job");
+            job.record.running = true;
+            job.record.runs += 1;
+            job.record.last_started_at = Some(now);
+
+            let broker = broker.clone();
+            let done_tx = done_tx.clone();
+            let task = job.record.task;
+            tokio::spawn(async move {
+                let result = match run_maintenance(&broker, task).await {
+                    Ok(report) => JobResult {
+                        success: true,
+                        message: report.summary,
+                        finished_at: crate::clock::now(),
+                    },
+                    Err(e) => JobResult {
+                        success: false,
+                        message: e.to_string(),
+                        finished_at: crate::clock::now(),
+                    },
+                };
+
+                let _ = done_tx.send((index, result)).await;
+            });
+        }
+    }
+}
+
+async fn run_maintenance(
+    broker: &MessageBroker,
+    task: MaintenanceTask,
+) -> Result<MaintenanceReport, anyhow::Error> {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::RunMaintenance { task }),
+            Some("persistence".into()),
+        ))
+        .await?;
+
+    match response {
+        Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::Maintenance(report)),
+            ..
+        }) => Ok(report),
+        Some(Message {
+            body: MessageBody::PersistenceQueryResponse(QueryResponse::MaintenanceFailed(e)),
+            ..
+        }) => Err(anyhow::anyhow!(e)),
+        _ => Err(anyhow::anyhow!("unexpected response to maintenance query")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_register_rejects_invalid_jobs() {
+        let mut scheduler = SchedulerActorHandler::new();
+
+        assert!(scheduler
+            .register(
+                "compact",
+                JobSchedule::EveryTicks { ticks: 10 },
+                MaintenanceTask::CompactBuildings,
+            )
+            .is_ok());
+        assert!(scheduler
+            .register(
+                "compact",
+                JobSchedule::EveryTicks { ticks: 20 },
+                MaintenanceTask::CompactBuildings,
+            )
+            .is_err());
+        assert!(scheduler
+            .register(
+                "never",
+                JobSchedule::EveryTicks { ticks: 0 },
+                MaintenanceTask::DailyStats,
+            )
+            .is_err());
+        assert!(scheduler
+            .register(
+                "broken",
+                JobSchedule::Cron {
+                    expression: "every day".into(),
+                },
+                MaintenanceTask::DailyStats,
+            )
+            .is_err());
+
+        assert_eq!(scheduler.jobs().len(), 1);
+    }
+
+    #[test]
+    fn test_jobs_come_due() {
+        let mut scheduler = SchedulerActorHandler::new();
+        scheduler
+            .register(
+                "compact",
+                JobSchedule::EveryTicks { ticks: 10 },
+                MaintenanceTask::CompactBuildings,
+            )
+            .unwrap();
+        scheduler
+            .register(
+                "hourly",
+                JobSchedule::Cron {
+                    expression: "0 * * * *".into(),
+                },
+                MaintenanceTask::PurgeExpiredSessions,
+            )
+            .unwrap();
+
+        let on_the_hour = Utc.with_ymd_and_hms(2025, 12, 1, 9, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2025, 12, 1, 9, 0, 30).unwrap();
+
+        let [every_ticks, hourly] = &mut scheduler.jobs[..] else {
+            unreachable!()
+        };
+
+        assert!(every_ticks.is_due(Due::Tick(20)));
+        assert!(!every_ticks.is_due(Due::Tick(21)));
+        assert!(!every_ticks.is_due(Due::Time(on_the_hour)));
+
+        // cron jobs follow wall time, not ticks
+        assert!(!hourly.is_due(Due::Tick(60)));
+        assert!(hourly.is_due(Due::Time(on_the_hour)));
+        // only once per matching minute, however often it is checked
+        assert!(!hourly.is_due(Due::Time(later)));
+        assert!(hourly.is_due(Due::Time(on_the_hour + chrono::Duration::hours(1))));
+    }
+}
@@ -1,16 +1,30 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use uuid::Uuid;
 
+use crate::clock::{self, SharedClock};
 use crate::messaging::{
     broker::MessageBroker,
     model::{Message, MessageBody},
 };
+use crate::model::{
+    CatchUpMode, TickOverrun, TickPhase, TickerCommand, TickerState, TimedOutPhase, WorldSettings,
+};
+use crate::persistence::Query;
+
+use super::load_world_settings;
 
 pub struct TickerActorHandler {
     pub id: Uuid,
     seq: Arc<Mutex<u64>>,
+    clock: SharedClock,
+    control_rx: Option<mpsc::Receiver<Message>>,
+    ack_rx: Option<mpsc::Receiver<Message>>,
 }
 
 impl Default for TickerActorHandler {
@@ -20,52 +34,238 @@ impl Default for TickerActorHandler {
 }
 
 const TICKER_INTERVAL_MILLISECS: u64 = 1000;
+const PHASE_TIMEOUT_MILLISECS: u64 = 2000;
+const CONTROL_TOPIC: &str = "in:ticker";
+const ACK_TOPIC: &str = "ack:ticker";
+const OVERRUN_TOPIC: &str = "out:ticker";
+/// Ticks sent back to back when stepping or fast-forwarding before pausing for subscribers.
+const TICK_BATCH: u64 = 20;
+/// Pause between tick batches so subscribers can work through their queues.
+const TICK_BATCH_PAUSE_MILLISECS: u64 = 250;
+
+/// The ticker's adjustable pace, changed through [`TickerCommand`]s.
+#[derive(Debug, Clone, PartialEq)]
+struct Cadence {
+    paused: bool,
+    interval_ms: u64,
+    phase_timeout_ms: u64,
+    fast_forward: u64,
+}
+
+impl Default for Cadence {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            interval_ms: TICKER_INTERVAL_MILLISECS,
+            phase_timeout_ms: PHASE_TIMEOUT_MILLISECS,
+            fast_forward: 0,
+        }
+    }
+}
+
+impl Cadence {
+    /// Applies a command and returns the number of ticks to send immediately.
+    fn apply(&mut self, command: TickerCommand) -> Result<u64, String> {
+        match command {
+            TickerCommand::Pause => self.paused = true,
+            TickerCommand::Resume => self.paused = false,
+            TickerCommand::SetInterval { millis: 0 } => {
+                return Err("interval must be at least 1 ms".into())
+            }
+            TickerCommand::SetInterval { millis } => self.interval_ms = millis,
+            TickerCommand::SetPhaseTimeout { millis: 0 } => {
+                return Err("phase timeout must be at least 1 ms".into())
+            }
+            TickerCommand::SetPhaseTimeout { millis } => self.phase_timeout_ms = millis,
+            TickerCommand::Step { count } => return Ok(count),
+            TickerCommand::FastForward { count } => self.fast_forward += count,
+            TickerCommand::Status => {}
+        }
+        Ok(0)
+    }
+}
+
+/// Actors that registered on the ack topic, with the phases each of them takes part in.
+#[derive(Default)]
+struct Participants {
+    phases: HashMap<Uuid, Vec<TickPhase>>,
+}
+
+impl Participants {
+    /// Applies a registration and returns the acknowledgement carried by the message, if any.
+    fn handle(&mut self, body: MessageBody) -> Option<(Uuid, u64, TickPhase)> {
+        match body {
+            MessageBody::TickRegistration {
+                participant,
+                phases,
+            } => {
+                if phases.is_empty() {
+                    tracing::debug!(%participant, "tick participant unregistered");
+                    self.phases.remove(&participant);
+                } else {
+                    tracing::debug!(%participant, ?phases, "tick participant registered");
+                    self.phases.insert(participant, phases);
+                }
+                None
+            }
+            MessageBody::TickAck {
+                participant,
+                seq,
+                phase,
+            } => Some((participant, seq, phase)),
+            other => {
+                tracing::warn!("Unexpected ticker ack message body: {:?}", other);
+                None
+            }
+        }
+    }
+
+    fn expected(&self, phase: TickPhase) -> HashSet<Uuid> {
+        self.phases
+            .iter()
+            .filter(|(_, phases)| phases.contains(&phase))
+            .map(|(participant, _)| *participant)
+            .collect()
+    }
+}
+
+/// Everything the running ticker task owns.
+struct TickLoop {
+    broker: MessageBroker,
+    seq: Arc<Mutex<u64>>,
+    clock: SharedClock,
+    acks: mpsc::Receiver<Message>,
+    participants: Participants,
+    cadence: Cadence,
+    overruns: u64,
+}
 
 impl TickerActorHandler {
     pub fn new() -> Self {
+        Self::with_clock(clock::shared())
+    }
+
+    /// A ticker stamping its ticks with `clock`. A virtual clock starts the ticker paused and
+    /// moves forward by one interval per tick, so ticks only happen on step or fast-forward.
+    pub fn with_clock(clock: SharedClock) -> Self {
         TickerActorHandler {
             id: Uuid::new_v4(),
             seq: Arc::new(Mutex::new(0)),
+            clock,
+            control_rx: None,
+            ack_rx: None,
         }
     }
 
-    pub async fn start(&self, broker: MessageBroker) -> Result<JoinHandle<()>, anyhow::Error> {
-        let seq = Arc::clone(&self.seq);
-        let broker = broker.clone();
+    /// Subscribes to the control and ack topics. Call this before starting the actors that
+    /// register as tick participants so their registrations aren't missed.
+    pub async fn subscribe(&mut self, broker: &MessageBroker) -> Result<(), anyhow::Error> {
+        if self.control_rx.is_none() {
+            let (_, control_rx) = broker.subscribe(CONTROL_TOPIC).await?;
+            self.control_rx = Some(control_rx);
+        }
+        if self.ack_rx.is_none() {
+            let (_, ack_rx) = broker.subscribe(ACK_TOPIC).await?;
+            self.ack_rx = Some(ack_rx);
+        }
+        Ok(())
+    }
+
+    pub async fn start(&mut self, broker: MessageBroker) -> Result<JoinHandle<()>, anyhow::Error> {
+        self.subscribe(&broker).await?;
+
+        let (Some(mut control_rx), Some(acks)) = (self.control_rx.take(), self.ack_rx.take())
+        else {
+            return Err(anyhow::anyhow!("ticker is already started"));
+        };
+
+        let mut ticker = TickLoop {
+            broker: broker.clone(),
+            seq: Arc::clone(&self.seq),
+            clock: Arc::clone(&self.clock),
+            acks,
+            participants: Participants::default(),
+            cadence: Cadence {
+                paused: self.clock.is_virtual(),
+                ..Cadence::default()
+            },
+            overruns: 0,
+        };
 
         let handle = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(
-                TICKER_INTERVAL_MILLISECS,
-            ));
+            match load_world_settings(&ticker.broker).await {
+                Ok(settings) => {
+                    set_seq(&ticker.seq, settings.tick_seq.max(0) as u64);
+                    ticker.catch_up(&settings).await;
+                }
+                Err(e) => {
+                    tracing::warn!("failed to load world settings, starting from seq 0: {}", e);
+                }
+            }
+
+            let mut interval = new_interval(ticker.cadence.interval_ms);
             loop {
-                interval.tick().await;
-
-                // Increment and get seq
-                let current_seq = {
-                    let mut seq_guard = match seq.lock() {
-                        Ok(guard) => guard,
-                        Err(poisoned) => {
-                            tracing::error!("mutex poisoned, recovering");
-                            poisoned.into_inner()
+                tokio::select! {
+                    biased;
+
+                    Some(msg) = control_rx.recv() => {
+                        let reply_topic = msg.reply_topic();
+                        let MessageBody::TickerControl(command) = msg.body else {
+                            tracing::warn!("Unexpected ticker message body: {:?}", msg.body);
+                            continue;
+                        };
+                        tracing::info!(?command, "received ticker command");
+
+                        let interval_ms = ticker.cadence.interval_ms;
+                        let result = match ticker.cadence.apply(command) {
+                            Ok(count) => {
+                                ticker.send_ticks(count).await;
+                                Ok(ticker.state())
+                            }
+                            Err(e) => Err(e),
+                        };
+                        if ticker.cadence.interval_ms != interval_ms {
+                            interval = new_interval(ticker.cadence.interval_ms);
                         }
-                    };
-                    *seq_guard = seq_guard.wrapping_add(1);
-                    *seq_guard
-                };
 
-                let msg = Message::new(
-                    MessageBody::Tick {
-                        seq: current_seq,
-                        timestamp: chrono::Utc::now(),
-                    },
-                    Some("ticks".into()),
-                    false,
-                );
+                        let reply =
+                            Message::new(MessageBody::TickerStatus(result), Some(reply_topic), false);
+                        if let Err(e) = ticker.broker.send(reply).await {
+                            tracing::error!("failed to reply to ticker command: {}", e);
+                        }
+                    }
+                    Some(msg) = ticker.acks.recv() => {
+                        // acks outside of a tick are late ones for phases that already timed out
+                        ticker.participants.handle(msg.body);
+                    }
+                    _ = std::future::ready(()), if ticker.cadence.fast_forward > 0 => {
+                        let count = ticker.cadence.fast_forward.min(TICK_BATCH);
+                        ticker.send_ticks(count).await;
+                        ticker.cadence.fast_forward -= count;
 
-                if let Err(e) = broker.send(msg).await {
-                    tracing::error!("failed to publish ticker tick message: {}", e);
-                } else {
-                    tracing::trace!("sent ticker tick message");
+                        if ticker.cadence.fast_forward == 0 {
+                            interval.reset();
+                        } else {
+                            pause_between_batches().await;
+                        }
+                    }
+                    _ = interval.tick(), if !ticker.cadence.paused => {
+                        let current_seq = next_seq(&ticker.seq);
+                        let started = Instant::now();
+                        let timestamp = ticker.next_timestamp();
+                        let timed_out = ticker.run_tick(current_seq, timestamp).await;
+
+                        let elapsed_ms = started.elapsed().as_millis() as u64;
+                        if elapsed_ms > ticker.cadence.interval_ms || !timed_out.is_empty() {
+                            ticker.report_overrun(TickOverrun {
+                                seq: current_seq,
+                                elapsed_ms,
+                                interval_ms: ticker.cadence.interval_ms,
+                                timed_out,
+                            })
+                            .await;
+                        }
+                    }
                 }
             }
         });
@@ -73,3 +273,416 @@ impl TickerActorHandler {
         Ok(handle)
     }
 }
+
+impl TickLoop {
+    fn state(&self) -> TickerState {
+        TickerState {
+            seq: read_seq(&self.seq),
+            paused: self.cadence.paused,
+            interval_ms: self.cadence.interval_ms,
+            phase_timeout_ms: self.cadence.phase_timeout_ms,
+            fast_forward_remaining: self.cadence.fast_forward,
+            participants: self.participants.phases.len(),
+            overruns: self.overruns,
+        }
+    }
+
+    /// The timestamp for the next tick, moving a virtual clock forward by one interval first.
+    fn next_timestamp(&self) -> DateTime<Utc> {
+        if self.clock.is_virtual() {
+            self.clock.advance(chrono::Duration::milliseconds(
+                self.cadence.interval_ms as i64,
+            ));
+        }
+        self.clock.now()
+    }
+
+    /// Runs every phase of tick `seq` in order and returns the participants that didn't
+    /// acknowledge their phase in time.
+    async fn run_tick(&mut self, seq: u64, timestamp: DateTime<Utc>) -> Vec<TimedOutPhase> {
+        // pick up registrations that arrived since the last tick
+        while let Ok(msg) = self.acks.try_recv() {
+            self.participants.handle(msg.body);
+        }
+
+        let mut timed_out = Vec::new();
+        for phase in TickPhase::ALL {
+            let msg = Message::new(
+                MessageBody::Tick {
+                    seq,
+                    timestamp,
+                    phase,
+                },
+                Some("ticks".into()),
+                false,
+            );
+
+            if let Err(e) = self.broker.send(msg).await {
+                tracing::error!("failed to publish ticker tick message: {}", e);
+                return timed_out;
+            }
+            tracing::trace!(seq, ?phase, "sent ticker tick message");
+
+            timed_out.extend(
+                self.wait_for_acks(seq, phase)
+                    .await
+                    .into_iter()
+                    .map(|participant| TimedOutPhase { participant, phase }),
+            );
+        }
+
+        let save = Message::new(
+            MessageBody::PersistenceQueryRequest(Query::SaveTick { seq, timestamp }),
+            Some("persistence".into()),
+            false,
+        );
+        if let Err(e) = self.broker.send(save).await {
+            tracing::error!("failed to persist ticker seq: {}", e);
+        }
+
+        timed_out
+    }
+
+    async fn wait_for_acks(&mut self, seq: u64, phase: TickPhase) -> Vec<Uuid> {
+        let mut pending = self.participants.expected(phase);
+        let deadline =
+            Instant::now() + tokio::time::Duration::from_millis(self.cadence.phase_timeout_ms);
+
+        while !pending.is_empty() {
+            match tokio::time::timeout_at(deadline, self.acks.recv()).await {
+                Ok(Some(msg)) => {
+                    if let Some((participant, ack_seq, ack_phase)) =
+                        self.participants.handle(msg.body)
+                    {
+                        if ack_seq == seq && ack_phase == phase {
+                            pending.remove(&participant);
+                        }
+                    }
+                    // participants that unregistered mid-phase are no longer waited on
+                    pending
+                        .retain(|participant| self.participants.phases.contains_key(participant));
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    tracing::warn!(seq, ?phase, ?pending, "tick phase timed out");
+                    break;
+                }
+            }
+        }
+
+        pending.into_iter().collect()
+    }
+
+    async fn report_overrun(&mut self, overrun: TickOverrun) {
+        self.overruns += 1;
+        tracing::warn!(
+            seq = overrun.seq,
+            elapsed_ms = overrun.elapsed_ms,
+            interval_ms = overrun.interval_ms,
+            timed_out = overrun.timed_out.len(),
+            "tick overran"
+        );
+
+        let msg = Message::new(
+            MessageBody::TickOverrun(overrun),
+            Some(OVERRUN_TOPIC.into()),
+            false,
+        );
+        if let Err(e) = self.broker.send(msg).await {
+            tracing::error!("failed to publish tick overrun: {}", e);
+        }
+    }
+
+    /// Sends `count` ticks back to back, pausing between batches.
+    async fn send_ticks(&mut self, count: u64) {
+        for n in 1..=count {
+            let current_seq = next_seq(&self.seq);
+            let timestamp = self.next_timestamp();
+            self.run_tick(current_seq, timestamp).await;
+
+            if n % TICK_BATCH == 0 && n < count {
+                pause_between_batches().await;
+            }
+        }
+    }
+
+    /// Replays the ticks missed since the last persisted tick, or skips them, as configured.
+    async fn catch_up(&mut self, settings: &WorldSettings) {
+        let Some(last_tick_at) = settings.last_tick_at else {
+            return;
+        };
+
+        let now = self.clock.now();
+        let missed = missed_ticks(
+            last_tick_at.and_utc(),
+            now,
+            TICKER_INTERVAL_MILLISECS,
+            settings.max_catch_up_ticks.max(0) as u64,
+        );
+        if missed == 0 {
+            return;
+        }
+
+        if settings.mode() == CatchUpMode::Skip {
+            tracing::info!(missed, "skipping ticks missed while offline");
+            return;
+        }
+
+        tracing::info!(missed, "catching up on ticks missed while offline");
+
+        let batch = settings.catch_up_batch.max(1) as u64;
+        for n in 1..=missed {
+            let current_seq = next_seq(&self.seq);
+            let behind = (missed - n) * TICKER_INTERVAL_MILLISECS;
+            self.run_tick(
+                current_seq,
+                now - chrono::Duration::milliseconds(behind as i64),
+            )
+            .await;
+
+            if n % batch == 0 {
+                pause_between_batches().await;
+            }
+        }
+
+        tracing::info!(missed, "caught up on missed ticks");
+    }
+}
+
+fn new_interval(millis: u64) -> tokio::time::Interval {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(millis));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    interval
+}
+
+async fn pause_between_batches() {
+    tokio::time::sleep(tokio::time::Duration::from_millis(
+        TICK_BATCH_PAUSE_MILLISECS,
+    ))
+    .await;
+}
+
+fn lock_seq(seq: &Mutex<u64>) -> std::sync::MutexGuard<'_, u64> {
+    match seq.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            tracing::error!("mutex poisoned, recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+fn read_seq(seq: &Mutex<u64>) -> u64 {
+    *lock_seq(seq)
+}
+
+fn set_seq(seq: &Mutex<u64>, value: u64) {
+    *lock_seq(seq) = value;
+}
+
+// Increment and get seq
+fn next_seq(seq: &Mutex<u64>) -> u64 {
+    let mut seq_guard = lock_seq(seq);
+    *seq_guard = seq_guard.wrapping_add(1);
+    *seq_guard
+}
+
+/// Number of whole ticks that fit between `last` and `now`, capped at `max`.
+fn missed_ticks(last: DateTime<Utc>, now: DateTime<Utc>, interval_ms: u64, max: u64) -> u64 {
+    let elapsed = (now - last).num_milliseconds().max(0) as u64;
+    (elapsed / interval_ms).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missed_ticks() {
+        let last = Utc::now();
+
+        assert_eq!(
+            missed_ticks(last, last + chrono::Duration::milliseconds(4500), 1000, 100),
+            4
+        );
+        assert_eq!(
+            missed_ticks(last, last + chrono::Duration::hours(1), 1000, 100),
+            100
+        );
+        assert_eq!(
+            missed_ticks(last, last - chrono::Duration::seconds(5), 1000, 100),
+            0
+        );
+    }
+
+    #[test]
+    fn test_cadence_commands() {
+        let mut cadence = Cadence::default();
+
+        assert_eq!(cadence.apply(TickerCommand::Pause), Ok(0));
+        assert!(cadence.paused);
+        assert_eq!(cadence.apply(TickerCommand::Step { count: 3 }), Ok(3));
+        assert_eq!(
+            cadence.apply(TickerCommand::SetInterval { millis: 250 }),
+            Ok(0)
+        );
+        assert!(cadence
+            .apply(TickerCommand::SetInterval { millis: 0 })
+            .is_err());
+        assert_eq!(
+            cadence.apply(TickerCommand::FastForward { count: 50 }),
+            Ok(0)
+        );
+        assert!(cadence
+            .apply(TickerCommand::SetPhaseTimeout { millis: 0 })
+            .is_err());
+        assert_eq!(cadence.apply(TickerCommand::Resume), Ok(0));
+
+        assert_eq!(
+            cadence,
+            Cadence {
+                paused: false,
+                interval_ms: 250,
+                phase_timeout_ms: PHASE_TIMEOUT_MILLISECS,
+                fast_forward: 50,
+            }
+        );
+    }
+
+    #[test]
+    fn test_participant_registration() {
+        let mut participants = Participants::default();
+        let inventory = Uuid::new_v4();
+        let persistence = Uuid::new_v4();
+
+        participants.handle(MessageBody::TickRegistration {
+            participant: inventory,
+            phases: vec![TickPhase::Production, TickPhase::Consumption],
+        });
+        participants.handle(MessageBody::TickRegistration {
+            participant: persistence,
+            phases: vec![TickPhase::PostTick],
+        });
+
+        assert_eq!(
+            participants.expected(TickPhase::Production),
+            HashSet::from([inventory])
+        );
+        assert!(participants.expected(TickPhase::PreTick).is_empty());
+
+        let ack = participants.handle(MessageBody::TickAck {
+            participant: inventory,
+            seq: 3,
+            phase: TickPhase::Production,
+        });
+        assert_eq!(ack, Some((inventory, 3, TickPhase::Production)));
+
+        participants.handle(MessageBody::TickRegistration {
+            participant: inventory,
+            phases: vec![],
+        });
+        assert!(participants.expected(TickPhase::Production).is_empty());
+    }
+
+    /// Steps a fresh ticker through `ticks` ticks in a deterministic run and places a building
+    /// on every phase, the way a participant creates entities while the world runs.
+    async fn simulate(
+        seed: u64,
+        ticks: u64,
+    ) -> Vec<(u64, DateTime<Utc>, TickPhase, Uuid, chrono::NaiveDateTime)> {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        let _run =
+            crate::rng::DeterministicRun::begin(seed, Arc::new(clock::VirtualClock::new(start)))
+                .await;
+
+        let (broker, mut handler) = MessageBroker::new();
+        tokio::spawn(async move { handler.start().await });
+
+        // no database, the ticker starts from seq 0
+        let (_, mut persistence) = broker.subscribe("persistence").await.unwrap();
+        let persistence_broker = broker.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = persistence.recv().await {
+                if msg.is_request {
+                    let reply = Message::new(
+                        MessageBody::PersistenceQueryResponse(
+                            crate::persistence::QueryResponse::WorldSettingsFailed(
+                                "no database".into(),
+                            ),
+                        ),
+                        Some(msg.reply_topic()),
+                        false,
+                    );
+                    persistence_broker.send(reply).await.unwrap();
+                }
+            }
+        });
+        let (_, mut ticks_rx) = broker.subscribe("ticks").await.unwrap();
+
+        let mut ticker = TickerActorHandler::new();
+        ticker.start(broker.clone()).await.unwrap();
+
+        let status = broker
+            .request(Message::new_request(
+                MessageBody::TickerControl(TickerCommand::Step { count: ticks }),
+                Some(CONTROL_TOPIC.into()),
+            ))
+            .await
+            .unwrap();
+        assert!(matches!(
+            status.map(|msg| msg.body),
+            Some(MessageBody::TickerStatus(Ok(TickerState { seq, .. }))) if seq == ticks
+        ));
+
+        let inventory = Uuid::nil();
+        let mut output = Vec::new();
+        for _ in 0..ticks * TickPhase::ALL.len() as u64 {
+            let Some(Message {
+                body:
+                    MessageBody::Tick {
+                        seq,
+                        timestamp,
+                        phase,
+                    },
+                ..
+            }) = ticks_rx.recv().await
+            else {
+                panic!("expected a tick");
+            };
+            let building = crate::persistence::inventory_repository::new_building(
+                inventory,
+                "farm".into(),
+                None,
+            );
+            output.push((seq, timestamp, phase, building.id, building.created_at));
+        }
+        output
+    }
+
+    #[tokio::test]
+    async fn test_seeded_run_replays_identically() {
+        let first = simulate(7, 10).await;
+        let second = simulate(7, 10).await;
+
+        assert_eq!(first.len(), 10 * TickPhase::ALL.len());
+        assert_eq!(first, second);
+        assert_eq!(
+            first.last().map(|(seq, timestamp, ..)| (*seq, *timestamp)),
+            Some((
+                10,
+                DateTime::<Utc>::UNIX_EPOCH
+                    + chrono::Duration::milliseconds(10 * TICKER_INTERVAL_MILLISECS as i64)
+            ))
+        );
+        assert_ne!(simulate(8, 10).await, first);
+    }
+
+    #[test]
+    fn test_next_seq_continues_from_persisted_value() {
+        let seq = Mutex::new(0);
+        set_seq(&seq, 41);
+
+        assert_eq!(next_seq(&seq), 42);
+        assert_eq!(next_seq(&seq), 43);
+    }
+}
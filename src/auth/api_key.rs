@@ -0,0 +1,85 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+use crate::auth::token::{self, TokenClaims};
+use crate::error::AuthError;
+use crate::messaging::{
+    broker::MessageBroker,
+    model::{Message, MessageBody},
+};
+use crate::persistence::{Query, QueryResponse};
+
+/// Every key starts with it, which tells keys from access tokens and makes leaked keys easy to
+/// find.
+pub const KEY_PREFIX: &str = "adk_";
+
+/// Active keys an account can hold at once.
+pub const MAX_KEYS_PER_ACCOUNT: i64 = 20;
+
+/// Keys may be valid for a year at most, or never expire.
+pub const MAX_LIFETIME_DAYS: i64 = 365;
+
+/// A new key as `adk_<prefix>_<secret>` together with its prefix. The prefix identifies the
+/// key in listings, only the hash of the whole key is stored.
+pub fn generate() -> (String, String) {
+    let mut bytes = [0u8; 4];
+    OsRng.fill_bytes(&mut bytes);
+    let prefix = hex::encode(bytes);
+
+    let key = format!(
+        "{}{}_{}",
+        KEY_PREFIX,
+        prefix,
+        token::generate_refresh_token()
+    );
+    (key, prefix)
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(KEY_PREFIX)
+}
+
+/// Checks that the key exists, is neither expired nor revoked and that its account isn't
+/// blocked, in which case the error is an `AuthError`. The claims carry the key's id as session
+/// and the account's current role.
+pub async fn validate(key: &str, broker: &MessageBroker) -> Result<TokenClaims, anyhow::Error> {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::AuthenticateApiKey {
+                key_hash: token::hash_token(key),
+            }),
+            Some("persistence".into()),
+        ))
+        .await?;
+
+    match response.map(|msg| msg.body) {
+        Some(MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyAuthenticated(claims))) => {
+            Ok(claims)
+        }
+        Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))) => {
+            Err(AuthError::AccountBlocked {
+                state: status.state,
+                reason: status.reason,
+            }
+            .into())
+        }
+        Some(MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(e))) => Err(e.into()),
+        _ => Err(anyhow::anyhow!("Unexpected response to API key query")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_key() {
+        let (key, prefix) = generate();
+
+        assert!(is_api_key(&key));
+        assert!(key.starts_with(&format!("adk_{}_", prefix)));
+        assert_eq!(prefix.len(), 8);
+        assert_eq!(key.len(), KEY_PREFIX.len() + 8 + 1 + 64);
+        assert_ne!(generate().0, key);
+        assert!(!is_api_key("v4.local.abc"));
+    }
+}
@@ -1,55 +1,1038 @@
-use axum::{extract::State, Json};
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
 use serde_json::json;
+use uuid::Uuid;
 
-use crate::{auth::model::AuthRequest, messaging::model::Message, AppState};
+use crate::{
+    auth::{
+        api_key,
+        keys::{self, TokenMode},
+        model::{
+            AccountRoleUpdate, AccountStateUpdate, ApiKeyCreate, AuthRequest, AuthTokens,
+            EmailVerification, PasswordReset, PasswordResetMail, RefreshRequest,
+        },
+        permission::{AccountStatus, Permission},
+        token::{self, TokenClaims},
+        verification::{Recipient, TokenPurpose},
+    },
+    error::{AccountError, AuthError},
+    messaging::model::{Message, MessageBody},
+    model::AccountRequest,
+    AppState,
+};
 
 pub async fn handle_login(
     State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
     Json(payload): Json<AuthRequest>,
-) -> Json<serde_json::Value> {
+) -> Response {
     tracing::info!("Login attempt for user: {}", payload.user);
 
-    let user = state
+    let response = state
         .broker
         .request(Message::new(
-            crate::messaging::model::MessageBody::AuthenticationRequest {
+            MessageBody::AuthenticationRequest {
                 user: payload.user,
                 password: payload.password,
+                address: Some(address.ip()),
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    let result = match response {
+        Ok(Some(Message {
+            body: MessageBody::AuthenticationResponse(result),
+            ..
+        })) => result,
+        Ok(_) => Err(AuthError::Internal(
+            "no or unexpected authentication response".into(),
+        )),
+        Err(err) => Err(AuthError::Internal(err.to_string())),
+    };
+
+    match result {
+        Ok(tokens) => {
+            tracing::info!("Authentication successful");
+            (StatusCode::OK, Json(tokens_json(&tokens))).into_response()
+        }
+        Err(AuthError::Throttled(refusal)) => (
+            [(header::RETRY_AFTER, refusal.retry_after.to_string())],
+            auth_error_response("Authentication failed", AuthError::Throttled(refusal)),
+        )
+            .into_response(),
+        Err(err) => auth_error_response("Authentication failed", err).into_response(),
+    }
+}
+
+/// Blocked accounts learn why with a 403 and throttled logins when to retry with a 429, the
+/// same for every username. Anything else is a bare 401 or 500.
+fn auth_error_response(message: &str, err: AuthError) -> (StatusCode, Json<serde_json::Value>) {
+    match err {
+        AuthError::AccountBlocked { state, reason } => (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": message, "state": state, "reason": reason })),
+        ),
+        AuthError::Throttled(refusal) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({
+                "error": message,
+                "locked": refusal.locked,
+                "retry_after": refusal.retry_after,
+            })),
+        ),
+        AuthError::InvalidCredentials => {
+            tracing::warn!("{}: invalid credentials", message);
+            (StatusCode::UNAUTHORIZED, Json(json!({ "error": message })))
+        }
+        AuthError::Internal(err) => {
+            tracing::error!("{}: {}", message, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": message })),
+            )
+        }
+    }
+}
+
+/// `token` holds the access token for clients written before refresh tokens existed.
+fn tokens_json(tokens: &AuthTokens) -> serde_json::Value {
+    json!({
+        "token": tokens.access_token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
+    })
+}
+
+pub async fn handle_refresh(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::RefreshRequest {
+                token_hash: token::hash_token(&payload.refresh_token),
             },
             Some("auth".to_string()),
             true,
         ))
         .await;
 
-    match user {
-        Ok(response) => {
-            if response.is_none() {
-                tracing::warn!("Authentication failed: No response received");
-                return Json(json!({ "error": "Authentication failed" }));
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::RefreshResponse(Ok(tokens)),
+            ..
+        })) => (StatusCode::OK, Json(tokens_json(&tokens))),
+        Ok(Some(Message {
+            body: MessageBody::RefreshResponse(Err(err)),
+            ..
+        })) => auth_error_response("Refresh failed", err),
+        other => {
+            tracing::error!("Refresh failed: {:?}", other);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Refresh failed" })),
+            )
+        }
+    }
+}
+
+pub async fn handle_register(
+    State(state): State<AppState>,
+    Json(payload): Json<AccountRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    tracing::info!("Registration attempt for user: {}", payload.username);
+
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::RegistrationRequest(payload),
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    let result = match response {
+        Ok(Some(Message {
+            body: MessageBody::RegistrationResponse(result),
+            ..
+        })) => result,
+        Ok(_) => {
+            tracing::warn!("Registration failed: no or unexpected response");
+            Err(AccountError::Internal("no registration response".into()))
+        }
+        Err(err) => Err(AccountError::Internal(err.to_string())),
+    };
+
+    match result {
+        Ok(account_id) => (StatusCode::CREATED, Json(json!({ "id": account_id }))),
+        Err(err @ (AccountError::UsernameTaken | AccountError::EmailTaken)) => (
+            StatusCode::CONFLICT,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        Err(err) if err.is_client_error() => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        Err(err) => {
+            tracing::error!("Registration failed: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Registration failed" })),
+            )
+        }
+    }
+}
+
+/// Validates the bearer token of a request, answering 401 when it isn't usable and 403 when
+/// the account is blocked.
+async fn authorize(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<TokenClaims, (StatusCode, Json<serde_json::Value>)> {
+    token::validate_headers(headers, &state.broker)
+        .await
+        .map_err(|e| match e.downcast::<AuthError>() {
+            Ok(err) => auth_error_response("Unauthorized", err),
+            Err(e) => {
+                tracing::warn!("Unauthorized request: {}", e);
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(json!({ "error": "Unauthorized" })),
+                )
             }
+        })
+}
+
+/// Like `authorize`, additionally answering 403 when the role or the API key's scope lacks the
+/// permission.
+async fn require(
+    state: &AppState,
+    headers: &HeaderMap,
+    permission: Permission,
+) -> Result<TokenClaims, (StatusCode, Json<serde_json::Value>)> {
+    let claims = authorize(state, headers).await?;
+    if !claims.can(permission) {
+        tracing::warn!(
+            account_id = claims.account_id.to_string(),
+            role = claims.role.as_str(),
+            "missing permission {}",
+            permission
+        );
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Forbidden", "permission": permission })),
+        ));
+    }
+
+    Ok(claims)
+}
+
+/// Like `authorize`, additionally answering 403 for API keys. Sessions belong to the people
+/// signing in, a bot's key must not sign its owner out or list where they are signed in.
+async fn authorize_session(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<TokenClaims, (StatusCode, Json<serde_json::Value>)> {
+    let claims = authorize(state, headers).await?;
+    if claims.scope.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "API keys cannot manage sessions" })),
+        ));
+    }
+
+    Ok(claims)
+}
+
+pub async fn handle_logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match authorize_session(&state, &headers).await {
+        Ok(claims) => revoke_sessions(&state, claims.account_id, Some(claims.session_id)).await,
+        Err(response) => response,
+    }
+}
+
+pub async fn handle_logout_all(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match authorize_session(&state, &headers).await {
+        Ok(claims) => revoke_sessions(&state, claims.account_id, None).await,
+        Err(response) => response,
+    }
+}
+
+async fn revoke_sessions(
+    state: &AppState,
+    account_id: Uuid,
+    session_id: Option<Uuid>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::LogoutRequest {
+                account_id,
+                session_id,
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::LogoutResponse(Ok(revoked)),
+            ..
+        })) => (StatusCode::OK, Json(json!({ "revoked": revoked }))),
+        Ok(Some(Message {
+            body: MessageBody::LogoutResponse(Err(err)),
+            ..
+        })) => {
+            tracing::error!("Logout failed: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Logout failed" })),
+            )
+        }
+        other => {
+            tracing::error!("Logout failed: {:?}", other);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Logout failed" })),
+            )
+        }
+    }
+}
+
+pub async fn handle_sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let claims = match authorize_session(&state, &headers).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::SessionsRequest {
+                account_id: claims.account_id,
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::SessionsResponse(Ok(sessions)),
+            ..
+        })) => {
+            let sessions = sessions
+                .into_iter()
+                .map(|session| {
+                    json!({
+                        "id": session.id,
+                        "created_at": session.created_at,
+                        "refreshed_at": session.updated_at,
+                        "expires_at": session.expires_at,
+                        "current": session.id == claims.session_id,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            (StatusCode::OK, Json(json!({ "sessions": sessions })))
+        }
+        other => {
+            tracing::error!("Listing sessions failed: {:?}", other);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Listing sessions failed" })),
+            )
+        }
+    }
+}
+
+/// Publishes the Ed25519 keys tokens are signed with as `k4.public` PASERKs, so other services
+/// can verify tokens on their own. Symmetric keys are never exposed.
+pub async fn handle_public_keys() -> (StatusCode, Json<serde_json::Value>) {
+    let ring = keys::keyring();
+    if ring.mode() != TokenMode::Public {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Tokens are not signed with public keys" })),
+        );
+    }
+
+    let keys = ring
+        .keys()
+        .iter()
+        .map(|key| {
+            json!({
+                "kid": key.id,
+                "paserk": key.paserk(),
+                "current": key.id == ring.current().id,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    (StatusCode::OK, Json(json!({ "keys": keys })))
+}
+
+pub async fn handle_account_state(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<AccountStateUpdate>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let caller = match require(&state, &headers, payload.state.required_permission()).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+
+    update_account(
+        &state,
+        MessageBody::AccountStateRequest {
+            caller,
+            account_id,
+            state: payload.state,
+            reason: payload.reason,
+        },
+    )
+    .await
+}
+
+pub async fn handle_account_role(
+    State(state): State<AppState>,
+    Path(account_id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<AccountRoleUpdate>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let caller = match require(&state, &headers, Permission::ManageRoles).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+
+    update_account(
+        &state,
+        MessageBody::AccountRoleRequest {
+            caller,
+            account_id,
+            role: payload.role,
+        },
+    )
+    .await
+}
+
+async fn update_account(
+    state: &AppState,
+    request: MessageBody,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let response = state
+        .broker
+        .request(Message::new(request, Some("auth".to_string()), true))
+        .await;
+
+    let result: Result<AccountStatus, AccountError> = match response {
+        Ok(Some(Message {
+            body: MessageBody::AccountUpdateResponse(result),
+            ..
+        })) => result,
+        Ok(_) => Err(AccountError::Internal("no account update response".into())),
+        Err(err) => Err(AccountError::Internal(err.to_string())),
+    };
+
+    match result {
+        Ok(status) => (StatusCode::OK, Json(json!(status))),
+        Err(AccountError::NotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Account not found" })),
+        ),
+        Err(err @ AccountError::Forbidden(_)) => (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        Err(err) => {
+            tracing::error!("Account update failed: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Account update failed" })),
+            )
+        }
+    }
+}
+
+pub async fn handle_lockouts(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    if let Err(response) = require(&state, &headers, Permission::ViewLockouts).await {
+        return response;
+    }
+
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::LockoutsRequest,
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
 
-            match response.unwrap().body {
-                crate::messaging::model::MessageBody::AuthenticationResponse(result) => {
-                    match result {
-                        Ok(token) => {
-                            tracing::info!("Authentication successful");
-                            Json(json!({ "token": token }))
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::LockoutsResponse(lockouts),
+            ..
+        })) => (StatusCode::OK, Json(json!({ "lockouts": lockouts }))),
+        other => {
+            tracing::error!("Listing lockouts failed: {:?}", other);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Listing lockouts failed" })),
+            )
+        }
+    }
+}
+
+pub async fn handle_scheduler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    if let Err(response) = require(&state, &headers, Permission::ViewScheduler).await {
+        return response;
+    }
+
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::SchedulerStatusRequest,
+            Some("in:scheduler".to_string()),
+            true,
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::SchedulerStatus(Ok(jobs)),
+            ..
+        })) => (StatusCode::OK, Json(json!({ "jobs": jobs }))),
+        other => {
+            tracing::error!("Listing scheduled jobs failed: {:?}", other);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Listing scheduled jobs failed" })),
+            )
+        }
+    }
+}
+
+/// Issues a single-use ticket for `GET /rtc?ticket=...`, for browsers which can't send the
+/// access token with the WebSocket handshake.
+pub async fn handle_connection_ticket(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let claims = match authorize(&state, &headers).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+    // tickets are checked against a session, bots send their key with the handshake instead
+    if claims.scope.is_some() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "API keys connect with the Authorization header" })),
+        );
+    }
+
+    let ticket = token::generate_refresh_token();
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::ConnectionTicketRequest {
+                claims,
+                ticket_hash: token::hash_token(&ticket),
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::ConnectionTicketResponse(Ok(expires_in)),
+            ..
+        })) => (
+            StatusCode::OK,
+            Json(json!({ "ticket": ticket, "expires_in": expires_in })),
+        ),
+        other => {
+            tracing::error!("Issuing connection ticket failed: {:?}", other);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Issuing connection ticket failed" })),
+            )
+        }
+    }
+}
+
+/// Email verification and password reset, the paths the account mails link to.
+pub fn account_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/auth/verify-email",
+            get(handle_verify_email_link).post(handle_verify_email),
+        )
+        .route("/auth/verify-email/request", post(handle_verification_mail))
+        .route("/auth/password-reset", post(handle_password_reset))
+        .route(
+            "/auth/password-reset/request",
+            post(handle_password_reset_mail),
+        )
+}
+
+/// Mails the signed in account a new verification link.
+pub async fn handle_verification_mail(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let claims = match authorize(&state, &headers).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+
+    match account_mail(
+        &state,
+        Recipient::Account(claims.account_id),
+        TokenPurpose::VerifyEmail,
+    )
+    .await
+    {
+        Ok(()) => (StatusCode::ACCEPTED, Json(json!({ "status": "sent" }))),
+        Err(err @ AccountError::EmailAlreadyVerified) => (
+            StatusCode::CONFLICT,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        Err(err) => {
+            tracing::error!("Sending verification mail failed: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Sending verification mail failed" })),
+            )
+        }
+    }
+}
+
+/// Accepts the token as JSON body or, for the mailed link, as query.
+pub async fn handle_verify_email_link(
+    State(state): State<AppState>,
+    Query(payload): Query<EmailVerification>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    handle_verify_email(State(state), Json(payload)).await
+}
+
+pub async fn handle_verify_email(
+    State(state): State<AppState>,
+    Json(payload): Json<EmailVerification>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let request = MessageBody::VerifyEmailRequest {
+        token_hash: token::hash_token(&payload.token),
+    };
+
+    match redeem_account_token(&state, request).await {
+        Ok(account_id) => (
+            StatusCode::OK,
+            Json(json!({ "id": account_id, "email_verified": true })),
+        ),
+        Err(response) => response,
+    }
+}
+
+/// Answers 202 whether or not the address belongs to an account and whatever went wrong, so it
+/// can't be used to find registered addresses.
+pub async fn handle_password_reset_mail(
+    State(state): State<AppState>,
+    Json(payload): Json<PasswordResetMail>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match account_mail(
+        &state,
+        Recipient::Email(payload.email),
+        TokenPurpose::ResetPassword,
+    )
+    .await
+    {
+        Ok(()) | Err(AccountError::NotFound) => {}
+        Err(err) => tracing::error!("Sending password reset mail failed: {}", err),
+    }
+
+    (StatusCode::ACCEPTED, Json(json!({ "status": "sent" })))
+}
+
+pub async fn handle_password_reset(
+    State(state): State<AppState>,
+    Json(payload): Json<PasswordReset>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let request = MessageBody::ResetPasswordRequest {
+        token_hash: token::hash_token(&payload.token),
+        password: payload.password,
+    };
+
+    match redeem_account_token(&state, request).await {
+        Ok(account_id) => (StatusCode::OK, Json(json!({ "id": account_id }))),
+        Err(response) => response,
+    }
+}
+
+async fn account_mail(
+    state: &AppState,
+    recipient: Recipient,
+    purpose: TokenPurpose,
+) -> Result<(), AccountError> {
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::AccountMailRequest { recipient, purpose },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::AccountMailResponse(result),
+            ..
+        })) => result,
+        Ok(_) => Err(AccountError::Internal("no account mail response".into())),
+        Err(err) => Err(AccountError::Internal(err.to_string())),
+    }
+}
+
+async fn redeem_account_token(
+    state: &AppState,
+    request: MessageBody,
+) -> Result<Uuid, (StatusCode, Json<serde_json::Value>)> {
+    let response = state
+        .broker
+        .request(Message::new(request, Some("auth".to_string()), true))
+        .await;
+
+    let result = match response {
+        Ok(Some(Message {
+            body: MessageBody::AccountTokenResponse(result),
+            ..
+        })) => result,
+        Ok(_) => Err(AccountError::Internal("no account token response".into())),
+        Err(err) => Err(AccountError::Internal(err.to_string())),
+    };
+
+    result.map_err(|err| match err {
+        err if err.is_client_error() => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        err => {
+            tracing::error!("Redeeming account token failed: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Redeeming token failed" })),
+            )
+        }
+    })
+}
+
+/// Creates an API key, which is only ever shown in this response.
+pub async fn handle_create_api_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<ApiKeyCreate>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let claims = match authorize(&state, &headers).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+
+    let (key, prefix) = api_key::generate();
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::CreateApiKeyRequest {
+                caller: claims,
+                name: payload.name,
+                scopes: payload.scopes,
+                expires_in_days: payload.expires_in_days,
+                prefix,
+                key_hash: token::hash_token(&key),
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    let result = match response {
+        Ok(Some(Message {
+            body: MessageBody::ApiKeyResponse(result),
+            ..
+        })) => result,
+        Ok(_) => Err(AccountError::Internal("no API key response".into())),
+        Err(err) => Err(AccountError::Internal(err.to_string())),
+    };
+
+    match result {
+        Ok(info) => {
+            let mut body = json!(info);
+            body["key"] = json!(key);
+            (StatusCode::CREATED, Json(body))
+        }
+        Err(err) => api_key_error_response(err),
+    }
+}
+
+pub async fn handle_api_keys(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let claims = match authorize(&state, &headers).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::ApiKeysRequest {
+                account_id: claims.account_id,
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    match response {
+        Ok(Some(Message {
+            body: MessageBody::ApiKeysResponse(Ok(keys)),
+            ..
+        })) => (
+            StatusCode::OK,
+            Json(json!({
+                "keys": keys,
+                "current": claims.scope.map(|_| claims.session_id),
+            })),
+        ),
+        other => {
+            tracing::error!("Listing API keys failed: {:?}", other);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Listing API keys failed" })),
+            )
+        }
+    }
+}
+
+/// Revokes one of the caller's keys, an API key may only revoke itself.
+pub async fn handle_revoke_api_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(key_id): Path<Uuid>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let claims = match authorize(&state, &headers).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+
+    let response = state
+        .broker
+        .request(Message::new(
+            MessageBody::RevokeApiKeyRequest {
+                caller: claims,
+                key_id,
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await;
+
+    let result = match response {
+        Ok(Some(Message {
+            body: MessageBody::RevokeApiKeyResponse(result),
+            ..
+        })) => result,
+        Ok(_) => Err(AccountError::Internal("no API key response".into())),
+        Err(err) => Err(AccountError::Internal(err.to_string())),
+    };
+
+    match result {
+        Ok(()) => (StatusCode::OK, Json(json!({ "revoked": key_id }))),
+        Err(err) => api_key_error_response(err),
+    }
+}
+
+fn api_key_error_response(err: AccountError) -> (StatusCode, Json<serde_json::Value>) {
+    match err {
+        AccountError::NotFound => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "API key not found" })),
+        ),
+        err @ AccountError::Forbidden(_) => (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        err if err.is_client_error() => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": err.to_string() })),
+        ),
+        err => {
+            tracing::error!("API key request failed: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "API key request failed" })),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::auth::permission::Role;
+    use crate::messaging::broker::MessageBroker;
+    use crate::model::SessionInfo;
+    use crate::persistence::QueryResponse;
+
+    /// Answers the account mail and token requests for `account_id` and reports its session as
+    /// active, in place of the auth actor and persistence.
+    async fn stub_backend(broker: &MessageBroker, account_id: Uuid) {
+        for topic in ["auth", "persistence"] {
+            let (_, mut rx) = broker.subscribe(topic).await.unwrap();
+            let broker = broker.clone();
+            tokio::spawn(async move {
+                while let Some(msg) = rx.recv().await {
+                    let reply_topic = msg.reply_topic();
+                    let body = match msg.body {
+                        MessageBody::AccountMailRequest { .. } => {
+                            MessageBody::AccountMailResponse(Ok(()))
                         }
-                        Err(err) => {
-                            tracing::warn!("Authentication failed: {}", err);
-                            Json(json!({ "error": "Authentication failed" }))
+                        MessageBody::VerifyEmailRequest { .. }
+                        | MessageBody::ResetPasswordRequest { .. } => {
+                            MessageBody::AccountTokenResponse(Ok(account_id))
                         }
-                    }
-                }
-                _ => {
-                    tracing::warn!("Unexpected message body in authentication response");
-                    Json(json!({ "error": "Authentication failed" }))
+                        MessageBody::PersistenceQueryRequest(
+                            crate::persistence::Query::GetSession { session_id },
+                        ) => MessageBody::PersistenceQueryResponse(QueryResponse::Session(Some(
+                            SessionInfo {
+                                id: session_id,
+                                account_id,
+                                created_at: None,
+                                updated_at: None,
+                                expires_at: None,
+                            },
+                        ))),
+                        _ => continue,
+                    };
+                    broker
+                        .send(Message::new(body, Some(reply_topic), false))
+                        .await
+                        .unwrap();
                 }
-            }
+            });
         }
-        Err(err) => {
-            tracing::warn!("Authentication failed: {}", err);
-            Json(json!({ "error": "Authentication failed" }))
+    }
+
+    #[tokio::test]
+    async fn test_account_routes() {
+        let (broker, mut handler) = MessageBroker::new();
+        tokio::spawn(async move { handler.start().await });
+        let account_id = Uuid::new_v4();
+        stub_backend(&broker, account_id).await;
+
+        let app = account_routes().with_state(AppState::from((
+            crate::websocket::api::Bouncer::new(&broker),
+            broker.clone(),
+        )));
+        let access_token = token::generate_token(
+            &TokenClaims {
+                account_id,
+                session_id: Uuid::new_v4(),
+                role: Role::Player,
+                scope: None,
+            },
+            chrono::Utc::now() + chrono::Duration::minutes(token::ACCESS_TOKEN_LIFETIME_MINUTES),
+        )
+        .unwrap();
+
+        let json = |method: &str, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let requests = [
+            (
+                Request::get("/auth/verify-email?token=abc")
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::OK,
+            ),
+            (
+                json("POST", "/auth/verify-email", json!({ "token": "abc" })),
+                StatusCode::OK,
+            ),
+            (
+                Request::post("/auth/verify-email/request")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::ACCEPTED,
+            ),
+            (
+                json(
+                    "POST",
+                    "/auth/password-reset/request",
+                    json!({ "email": "brvy@example.com" }),
+                ),
+                StatusCode::ACCEPTED,
+            ),
+            (
+                json(
+                    "POST",
+                    "/auth/password-reset",
+                    json!({ "token": "abc", "password": "correct horse battery" }),
+                ),
+                StatusCode::OK,
+            ),
+        ];
+
+        for (request, status) in requests {
+            let uri = request.uri().clone();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), status, "{}", uri);
         }
+
+        let response = app
+            .oneshot(
+                Request::post("/auth/verify-email/request")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 }
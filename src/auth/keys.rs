@@ -0,0 +1,303 @@
+use std::sync::{Arc, RwLock};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::SigningKey;
+use sha2::{Digest, Sha256};
+
+/// Only used when no keys are configured outside of production.
+static DEVELOPMENT_SECRET: &str = "your-secret-key";
+
+/// How tokens are protected, set with `PASETO_MODE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TokenMode {
+    /// `v4.local`, encrypted with a shared symmetric key.
+    #[default]
+    Local,
+    /// `v4.public`, signed with Ed25519 so other services can verify with the public key.
+    Public,
+}
+
+impl std::str::FromStr for TokenMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "local" => Ok(TokenMode::Local),
+            "public" => Ok(TokenMode::Public),
+            other => Err(anyhow::anyhow!(
+                "invalid PASETO_MODE '{}', use 'local' or 'public'",
+                other
+            )),
+        }
+    }
+}
+
+/// A secret key named by the id written to the token footer. It is the symmetric key in
+/// local mode and the Ed25519 seed in public mode.
+#[derive(Clone)]
+pub struct SecretKey {
+    pub id: String,
+    material: [u8; 32],
+}
+
+impl SecretKey {
+    pub fn new(id: &str, material: [u8; 32]) -> Result<Self, anyhow::Error> {
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            return Err(anyhow::anyhow!(
+                "invalid key id '{}', use letters, digits, '_', '-' and '.'",
+                id
+            ));
+        }
+
+        Ok(SecretKey {
+            id: id.into(),
+            material,
+        })
+    }
+
+    pub fn material(&self) -> &[u8; 32] {
+        &self.material
+    }
+
+    /// The Ed25519 secret key followed by its public key, as `v4.public` signing expects.
+    pub fn keypair(&self) -> [u8; 64] {
+        SigningKey::from_bytes(&self.material).to_keypair_bytes()
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        SigningKey::from_bytes(&self.material)
+            .verifying_key()
+            .to_bytes()
+    }
+
+    /// The public key serialized as a `k4.public` PASERK.
+    pub fn paserk(&self) -> String {
+        format!("k4.public.{}", URL_SAFE_NO_PAD.encode(self.public_key()))
+    }
+}
+
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The keys tokens are issued and validated with. New tokens always use the current key,
+/// tokens carrying the id of any other key in the ring keep validating.
+///
+/// To rotate, add a new key and make it current, the last listed key unless
+/// `PASETO_CURRENT_KEY` names another one. Once every token issued with the old key has
+/// expired, after the session lifetime for refreshed sessions, remove the old key to retire it.
+#[derive(Debug, Clone)]
+pub struct KeyRing {
+    mode: TokenMode,
+    keys: Vec<SecretKey>,
+    current: usize,
+}
+
+impl KeyRing {
+    pub fn new(
+        mode: TokenMode,
+        keys: Vec<SecretKey>,
+        current: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        if keys.is_empty() {
+            return Err(anyhow::anyhow!("no PASETO keys configured"));
+        }
+
+        for (index, key) in keys.iter().enumerate() {
+            if keys[..index].iter().any(|other| other.id == key.id) {
+                return Err(anyhow::anyhow!("duplicate PASETO key id '{}'", key.id));
+            }
+        }
+
+        let current = match current {
+            Some(id) => keys
+                .iter()
+                .position(|key| key.id == id)
+                .ok_or_else(|| anyhow::anyhow!("current PASETO key '{}' is not configured", id))?,
+            None => keys.len() - 1,
+        };
+
+        Ok(KeyRing {
+            mode,
+            keys,
+            current,
+        })
+    }
+
+    /// A single key derived from a well known secret, for local development only.
+    pub fn development(mode: TokenMode) -> Self {
+        let material = Sha256::digest(DEVELOPMENT_SECRET.as_bytes()).into();
+
+        KeyRing {
+            mode,
+            keys: vec![SecretKey {
+                id: "development".into(),
+                material,
+            }],
+            current: 0,
+        }
+    }
+
+    /// Parses `id=hex` entries separated by commas or new lines, where each key is 32 bytes
+    /// of hex. Blank lines and lines starting with `#` are skipped.
+    pub fn parse(spec: &str) -> Result<Vec<SecretKey>, anyhow::Error> {
+        spec.split([',', '\n'])
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+            .map(|entry| {
+                let (id, material) = entry
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("PASETO key entries must look like 'id=hex'"))?;
+
+                let material = hex::decode(material.trim())
+                    .map_err(|e| anyhow::anyhow!("invalid PASETO key '{}': {}", id.trim(), e))?
+                    .try_into()
+                    .map_err(|_| {
+                        anyhow::anyhow!("PASETO key '{}' must be 32 bytes long", id.trim())
+                    })?;
+
+                SecretKey::new(id.trim(), material)
+            })
+            .collect()
+    }
+
+    /// Loads keys from `PASETO_KEYS` and the file named by `PASETO_KEYS_FILE`. Without any
+    /// key the development key is used, unless `APP_ENV` is `production`.
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let mode = match std::env::var("PASETO_MODE") {
+            Ok(mode) => mode.parse()?,
+            Err(_) => TokenMode::default(),
+        };
+
+        let mut keys = match std::env::var("PASETO_KEYS") {
+            Ok(spec) => Self::parse(&spec)?,
+            Err(_) => Vec::new(),
+        };
+        if let Ok(path) = std::env::var("PASETO_KEYS_FILE") {
+            let spec = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("failed to read PASETO_KEYS_FILE {}: {}", path, e))?;
+            keys.extend(Self::parse(&spec)?);
+        }
+
+        if keys.is_empty() {
+            if std::env::var("APP_ENV").as_deref() == Ok("production") {
+                return Err(anyhow::anyhow!(
+                    "no PASETO keys configured, set PASETO_KEYS or PASETO_KEYS_FILE"
+                ));
+            }
+
+            tracing::warn!("no PASETO keys configured, using the development key");
+            return Ok(Self::development(mode));
+        }
+
+        Self::new(
+            mode,
+            keys,
+            std::env::var("PASETO_CURRENT_KEY").ok().as_deref(),
+        )
+    }
+
+    pub fn mode(&self) -> TokenMode {
+        self.mode
+    }
+
+    pub fn current(&self) -> &SecretKey {
+        &self.keys[self.current]
+    }
+
+    pub fn get(&self, id: &str) -> Option<&SecretKey> {
+        self.keys.iter().find(|key| key.id == id)
+    }
+
+    pub fn keys(&self) -> &[SecretKey] {
+        &self.keys
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref KEYS: RwLock<Arc<KeyRing>> = RwLock::new(Arc::new(KeyRing::development(TokenMode::Local)));
+}
+
+/// Replaces the process-wide key ring. Call it once at startup, before issuing any token.
+pub fn install(ring: KeyRing) {
+    match KEYS.write() {
+        Ok(mut guard) => *guard = Arc::new(ring),
+        Err(poisoned) => *poisoned.into_inner() = Arc::new(ring),
+    }
+}
+
+pub fn keyring() -> Arc<KeyRing> {
+    match KEYS.read() {
+        Ok(guard) => Arc::clone(&guard),
+        Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+    const NEW: &str = "0000000000000000000000000000000000000000000000000000000000000002";
+
+    #[test]
+    fn test_parse_keys() {
+        let keys = KeyRing::parse(&format!(
+            "# rotated in December\n2025-06={OLD}\n\n2025-12={NEW}\n"
+        ))
+        .unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1].id, "2025-12");
+        assert_eq!(keys[1].material()[31], 2);
+
+        assert!(KeyRing::parse("2025-06").is_err());
+        assert!(KeyRing::parse("2025-06=abc").is_err());
+        assert!(KeyRing::parse(&format!("bad id={OLD}")).is_err());
+    }
+
+    #[test]
+    fn test_current_key() {
+        let keys = KeyRing::parse(&format!("2025-06={OLD},2025-12={NEW}")).unwrap();
+
+        let ring = KeyRing::new(TokenMode::Local, keys.clone(), None).unwrap();
+        assert_eq!(ring.current().id, "2025-12");
+        assert!(ring.get("2025-06").is_some());
+
+        let ring = KeyRing::new(TokenMode::Local, keys.clone(), Some("2025-06")).unwrap();
+        assert_eq!(ring.current().id, "2025-06");
+
+        assert!(KeyRing::new(TokenMode::Local, keys.clone(), Some("2024-12")).is_err());
+        assert!(KeyRing::new(
+            TokenMode::Local,
+            vec![keys[0].clone(), keys[0].clone()],
+            None
+        )
+        .is_err());
+        assert!(KeyRing::new(TokenMode::Local, Vec::new(), None).is_err());
+    }
+
+    #[test]
+    fn test_public_key_paserk() {
+        let key = SecretKey::new("2025-12", [7; 32]).unwrap();
+
+        assert_eq!(&key.keypair()[32..], &key.public_key());
+        assert!(key.paserk().starts_with("k4.public."));
+        assert_eq!(
+            URL_SAFE_NO_PAD
+                .decode(key.paserk().trim_start_matches("k4.public."))
+                .unwrap(),
+            key.public_key()
+        );
+
+        assert_eq!("public".parse::<TokenMode>().unwrap(), TokenMode::Public);
+        assert!("symmetric".parse::<TokenMode>().is_err());
+    }
+}
@@ -1,3 +1,10 @@
+pub mod api_key;
 pub mod handler;
+pub mod keys;
 pub mod model;
+pub mod password;
+pub mod permission;
+pub mod throttle;
+pub mod ticket;
 pub mod token;
+pub mod verification;
@@ -3,3 +3,69 @@ pub struct AuthRequest {
     pub user: String,
     pub password: String,
 }
+
+/// Issued on login and on every refresh.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct AuthTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Seconds until the access token expires.
+    pub expires_in: i64,
+}
+
+impl std::fmt::Debug for AuthTokens {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthTokens")
+            .field("expires_in", &self.expires_in)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Body of `POST /admin/accounts/{id}/state`, the reason is shown to the account's owner.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct AccountStateUpdate {
+    pub state: crate::auth::permission::AccountState,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Body of `POST /admin/accounts/{id}/role`.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct AccountRoleUpdate {
+    pub role: crate::auth::permission::Role,
+}
+
+/// Body of `POST /auth/verify-email`, or its query when the mailed link is opened.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct EmailVerification {
+    pub token: String,
+}
+
+/// Body of `POST /auth/password-reset/request`.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct PasswordResetMail {
+    pub email: String,
+}
+
+/// Body of `POST /auth/password-reset`.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct PasswordReset {
+    pub token: String,
+    pub password: crate::auth::password::Password,
+}
+
+/// Body of `POST /auth/api-keys`. Without scopes the key gets every permission of the role.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct ApiKeyCreate {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Option<crate::auth::permission::PermissionSet>,
+    /// Days until the key expires, it never does when left out.
+    #[serde(default)]
+    pub expires_in_days: Option<i64>,
+}
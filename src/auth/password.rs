@@ -0,0 +1,291 @@
+use std::sync::RwLock;
+
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use sha2::{Digest, Sha256};
+
+/// Argon2id cost parameters used for new password hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        PasswordConfig {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl PasswordConfig {
+    /// Reads `PASSWORD_HASH_MEMORY_KIB`, `PASSWORD_HASH_ITERATIONS` and
+    /// `PASSWORD_HASH_PARALLELISM`, falling back to the Argon2 defaults for unset ones.
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let defaults = Self::default();
+        let var = |name: &str, default: u32| match std::env::var(name) {
+            Ok(value) => value
+                .parse::<u32>()
+                .map_err(|e| anyhow::anyhow!("invalid {}: {}", name, e)),
+            Err(_) => Ok(default),
+        };
+
+        let config = PasswordConfig {
+            memory_kib: var("PASSWORD_HASH_MEMORY_KIB", defaults.memory_kib)?,
+            iterations: var("PASSWORD_HASH_ITERATIONS", defaults.iterations)?,
+            parallelism: var("PASSWORD_HASH_PARALLELISM", defaults.parallelism)?,
+        };
+        config.params()?;
+
+        Ok(config)
+    }
+
+    fn params(&self) -> Result<Params, anyhow::Error> {
+        Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| anyhow::anyhow!("invalid password hash parameters: {}", e))
+    }
+
+    fn hasher(&self) -> Result<Argon2<'static>, anyhow::Error> {
+        Ok(Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            self.params()?,
+        ))
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CONFIG: RwLock<PasswordConfig> = RwLock::new(PasswordConfig::default());
+    /// Hash of a password nobody knows with the parameters it was made with, see
+    /// `verify_unknown`.
+    static ref DUMMY_HASH: RwLock<Option<(PasswordConfig, String)>> = RwLock::new(None);
+}
+
+/// Replaces the process-wide hashing cost. Existing hashes with other parameters are upgraded
+/// on the next successful login.
+pub fn install(config: PasswordConfig) {
+    match CONFIG.write() {
+        Ok(mut guard) => *guard = config,
+        Err(poisoned) => *poisoned.into_inner() = config,
+    }
+    // so the first unknown username isn't slower than the ones after it
+    dummy_hash(&config);
+}
+
+pub fn config() -> PasswordConfig {
+    match CONFIG.read() {
+        Ok(guard) => *guard,
+        Err(poisoned) => *poisoned.into_inner(),
+    }
+}
+
+/// A plaintext password on its way to be hashed. `Debug` leaves it out, so messages carrying
+/// it can be logged.
+#[derive(Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Password(String);
+
+impl Password {
+    pub fn new(password: impl Into<String>) -> Self {
+        Password(password.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password(..)")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Invalid,
+    Valid,
+    /// The password matches, but the stored hash is a legacy one or uses outdated parameters.
+    NeedsRehash,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, Verification::Invalid)
+    }
+}
+
+/// Hashes a password with a random salt into a PHC string.
+pub fn hash_password(password: &str) -> Result<String, anyhow::Error> {
+    hash_with(&config(), password)
+}
+
+pub fn verify_password(password: &str, stored: &str) -> Verification {
+    verify_with(&config(), password, stored)
+}
+
+/// Verifies the password against a dummy hash with the current parameters, so a login for an
+/// unknown username takes as long as one with a wrong password.
+pub fn verify_unknown(password: &str) {
+    let config = config();
+    match dummy_hash(&config) {
+        Some(hash) => {
+            verify_with(&config, password, &hash);
+        }
+        None => tracing::warn!("failed to create the dummy password hash"),
+    }
+}
+
+fn dummy_hash(config: &PasswordConfig) -> Option<String> {
+    let cached = match DUMMY_HASH.read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    if let Some((_, hash)) = cached.filter(|(cached, _)| cached == config) {
+        return Some(hash);
+    }
+
+    let hash = hash_with(config, "an-daghdha dummy password").ok()?;
+    match DUMMY_HASH.write() {
+        Ok(mut guard) => *guard = Some((*config, hash.clone())),
+        Err(poisoned) => *poisoned.into_inner() = Some((*config, hash.clone())),
+    }
+    Some(hash)
+}
+
+fn hash_with(config: &PasswordConfig, password: &str) -> Result<String, anyhow::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    let hash = config
+        .hasher()?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("failed to hash password: {}", e))?;
+
+    Ok(hash.to_string())
+}
+
+fn verify_with(config: &PasswordConfig, password: &str, stored: &str) -> Verification {
+    let Ok(hash) = PasswordHash::new(stored) else {
+        return if verify_legacy(password, stored) {
+            Verification::NeedsRehash
+        } else {
+            Verification::Invalid
+        };
+    };
+
+    // the hasher takes algorithm and parameters from the stored hash, not from the config
+    if Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_err()
+    {
+        return Verification::Invalid;
+    }
+
+    let current = hash.algorithm == Algorithm::Argon2id.ident()
+        && Params::try_from(&hash).is_ok_and(|params| {
+            params.m_cost() == config.memory_kib
+                && params.t_cost() == config.iterations
+                && params.p_cost() == config.parallelism
+        });
+
+    if current {
+        Verification::Valid
+    } else {
+        Verification::NeedsRehash
+    }
+}
+
+/// Accounts created before Argon2 store an unsalted, hex encoded SHA-256 of the password.
+fn verify_legacy(password: &str, stored: &str) -> bool {
+    if stored.len() != 64 {
+        return false;
+    }
+
+    let digest = format!("{:x}", Sha256::digest(password));
+
+    digest
+        .bytes()
+        .zip(stored.to_ascii_lowercase().bytes())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHEAP: PasswordConfig = PasswordConfig {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_with(&CHEAP, "hunter2").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert_ne!(hash, hash_with(&CHEAP, "hunter2").unwrap());
+        assert_eq!(verify_with(&CHEAP, "hunter2", &hash), Verification::Valid);
+        assert_eq!(verify_with(&CHEAP, "hunter3", &hash), Verification::Invalid);
+    }
+
+    #[test]
+    fn test_password_debug_is_redacted() {
+        let password = Password::new("hunter2");
+
+        assert_eq!(format!("{:?}", password), "Password(..)");
+        assert_eq!(password.expose(), "hunter2");
+        assert_eq!(
+            serde_json::from_str::<Password>("\"hunter2\"").unwrap(),
+            password
+        );
+    }
+
+    #[test]
+    fn test_dummy_hash_uses_current_cost() {
+        let hash = dummy_hash(&CHEAP).unwrap();
+
+        assert_eq!(verify_with(&CHEAP, "hunter2", &hash), Verification::Invalid);
+        assert_eq!(dummy_hash(&CHEAP).unwrap(), hash);
+
+        let stronger = PasswordConfig {
+            iterations: 2,
+            ..CHEAP
+        };
+        let params =
+            Params::try_from(&PasswordHash::new(&dummy_hash(&stronger).unwrap()).unwrap()).unwrap();
+        assert_eq!(params.t_cost(), 2);
+    }
+
+    #[test]
+    fn test_legacy_hashes_need_rehash() {
+        // sha256("test"), the password of the seeded account
+        let legacy = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        assert_eq!(
+            verify_with(&CHEAP, "test", legacy),
+            Verification::NeedsRehash
+        );
+        assert_eq!(verify_with(&CHEAP, "tset", legacy), Verification::Invalid);
+        assert_eq!(verify_with(&CHEAP, "test", ""), Verification::Invalid);
+    }
+
+    #[test]
+    fn test_changed_cost_needs_rehash() {
+        let hash = hash_with(&CHEAP, "hunter2").unwrap();
+        let stronger = PasswordConfig {
+            iterations: 2,
+            ..CHEAP
+        };
+
+        assert_eq!(
+            verify_with(&stronger, "hunter2", &hash),
+            Verification::NeedsRehash
+        );
+    }
+}
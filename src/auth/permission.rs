@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Roles are ordered, every role holds the permissions of the roles below it and can only
+/// manage accounts with a lower role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Player,
+    Moderator,
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// List the buildings, resources, blueprints and policies of the own inventory.
+    ViewInventory,
+    /// Build, queue and run lifecycle commands on the own buildings.
+    ManageBuildings,
+    ManagePolicies,
+    /// Suspend and reactivate accounts.
+    SuspendAccounts,
+    BanAccounts,
+    ManageRoles,
+    /// See which usernames and addresses are throttled after failed logins.
+    ViewLockouts,
+    /// See the scheduled maintenance jobs and how their last runs went.
+    ViewScheduler,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        use Permission::*;
+
+        match self {
+            Role::Player => &[ViewInventory, ManageBuildings, ManagePolicies],
+            Role::Moderator => &[
+                ViewInventory,
+                ManageBuildings,
+                ManagePolicies,
+                SuspendAccounts,
+                ViewLockouts,
+            ],
+            Role::Admin => &[
+                ViewInventory,
+                ManageBuildings,
+                ManagePolicies,
+                SuspendAccounts,
+                ViewLockouts,
+                BanAccounts,
+                ManageRoles,
+                ViewScheduler,
+            ],
+        }
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "player" => Ok(Role::Player),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            other => Err(anyhow::anyhow!("unknown role '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Permission {
+    pub const ALL: [Permission; 8] = [
+        Permission::ViewInventory,
+        Permission::ManageBuildings,
+        Permission::ManagePolicies,
+        Permission::SuspendAccounts,
+        Permission::BanAccounts,
+        Permission::ManageRoles,
+        Permission::ViewLockouts,
+        Permission::ViewScheduler,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ViewInventory => "view_inventory",
+            Permission::ManageBuildings => "manage_buildings",
+            Permission::ManagePolicies => "manage_policies",
+            Permission::SuspendAccounts => "suspend_accounts",
+            Permission::BanAccounts => "ban_accounts",
+            Permission::ManageRoles => "manage_roles",
+            Permission::ViewLockouts => "view_lockouts",
+            Permission::ViewScheduler => "view_scheduler",
+        }
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Permission {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permission::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown permission '{}'", s))
+    }
+}
+
+/// The permissions an API key is limited to, serialized as a list. Kept as a bit set so
+/// `TokenClaims` stays `Copy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<Permission>", into = "Vec<Permission>")]
+pub struct PermissionSet(u16);
+
+impl PermissionSet {
+    fn bit(permission: Permission) -> u16 {
+        1 << Permission::ALL
+            .iter()
+            .position(|p| *p == permission)
+            .unwrap_or_default()
+    }
+
+    pub fn contains(&self, permission: Permission) -> bool {
+        self.0 & Self::bit(permission) != 0
+    }
+
+    pub fn insert(&mut self, permission: Permission) {
+        self.0 |= Self::bit(permission);
+    }
+
+    pub fn is_subset(&self, other: &PermissionSet) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Permission> + '_ {
+        Permission::ALL
+            .into_iter()
+            .filter(|permission| self.contains(*permission))
+    }
+}
+
+impl FromIterator<Permission> for PermissionSet {
+    fn from_iter<T: IntoIterator<Item = Permission>>(iter: T) -> Self {
+        let mut set = PermissionSet::default();
+        for permission in iter {
+            set.insert(permission);
+        }
+        set
+    }
+}
+
+impl From<Vec<Permission>> for PermissionSet {
+    fn from(value: Vec<Permission>) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+impl From<PermissionSet> for Vec<Permission> {
+    fn from(value: PermissionSet) -> Self {
+        value.iter().collect()
+    }
+}
+
+impl From<Role> for PermissionSet {
+    fn from(value: Role) -> Self {
+        value.permissions().iter().copied().collect()
+    }
+}
+
+/// Stored in `accounts.state`, only active accounts can log in, refresh or connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountState {
+    Active,
+    Suspended,
+    Banned,
+}
+
+impl AccountState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountState::Active => "active",
+            AccountState::Suspended => "suspended",
+            AccountState::Banned => "banned",
+        }
+    }
+
+    /// What a caller needs to move an account into this state.
+    pub fn required_permission(&self) -> Permission {
+        match self {
+            AccountState::Active | AccountState::Suspended => Permission::SuspendAccounts,
+            AccountState::Banned => Permission::BanAccounts,
+        }
+    }
+
+    /// What a caller needs to move an account from this state to `to`. Only those who can ban
+    /// may lift or soften a ban.
+    pub fn transition_permission(&self, to: AccountState) -> Permission {
+        match (self, to) {
+            (AccountState::Banned, _) => Permission::BanAccounts,
+            (_, to) => to.required_permission(),
+        }
+    }
+}
+
+impl std::str::FromStr for AccountState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(AccountState::Active),
+            "suspended" => Ok(AccountState::Suspended),
+            "banned" => Ok(AccountState::Banned),
+            other => Err(anyhow::anyhow!("unknown account state '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for AccountState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The role and state of an account, as checked on login, refresh and connection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountStatus {
+    pub account_id: Uuid,
+    pub role: Role,
+    pub state: AccountState,
+    pub reason: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_inherit_permissions() {
+        for permission in Role::Player.permissions() {
+            assert!(Role::Moderator.can(*permission));
+        }
+        for permission in Role::Moderator.permissions() {
+            assert!(Role::Admin.can(*permission));
+        }
+
+        assert!(!Role::Player.can(Permission::SuspendAccounts));
+        assert!(Role::Moderator.can(Permission::SuspendAccounts));
+        assert!(!Role::Moderator.can(Permission::BanAccounts));
+        assert!(Role::Admin.can(Permission::ManageRoles));
+        assert!(!Role::Moderator.can(Permission::ViewScheduler));
+        assert!(Role::Admin > Role::Moderator && Role::Moderator > Role::Player);
+    }
+
+    #[test]
+    fn test_moderator_cannot_lift_a_ban() {
+        let moderator = PermissionSet::from(Role::Moderator);
+        let can = |from: AccountState, to: AccountState| {
+            moderator.contains(from.transition_permission(to))
+        };
+
+        assert!(can(AccountState::Active, AccountState::Suspended));
+        assert!(can(AccountState::Suspended, AccountState::Active));
+        assert!(!can(AccountState::Active, AccountState::Banned));
+        assert!(!can(AccountState::Banned, AccountState::Active));
+        assert!(!can(AccountState::Banned, AccountState::Suspended));
+        assert!(Role::Admin.can(AccountState::Banned.transition_permission(AccountState::Active)));
+    }
+
+    #[test]
+    fn test_role_and_state_round_trip() {
+        for role in [Role::Player, Role::Moderator, Role::Admin] {
+            assert_eq!(role.as_str().parse::<Role>().unwrap(), role);
+            assert_eq!(
+                serde_json::to_value(role).unwrap(),
+                serde_json::json!(role.as_str())
+            );
+        }
+        for state in [
+            AccountState::Active,
+            AccountState::Suspended,
+            AccountState::Banned,
+        ] {
+            assert_eq!(state.as_str().parse::<AccountState>().unwrap(), state);
+        }
+        assert!("root".parse::<Role>().is_err());
+    }
+
+    #[test]
+    fn test_permission_set() {
+        let scope: PermissionSet =
+            serde_json::from_value(serde_json::json!(["view_inventory", "manage_policies"]))
+                .unwrap();
+
+        assert!(scope.contains(Permission::ViewInventory));
+        assert!(!scope.contains(Permission::ManageBuildings));
+        assert!(scope.is_subset(&Role::Player.into()));
+        assert!(!PermissionSet::from(Role::Moderator).is_subset(&Role::Player.into()));
+        assert_eq!(
+            serde_json::to_value(scope).unwrap(),
+            serde_json::json!(["view_inventory", "manage_policies"])
+        );
+
+        for permission in Permission::ALL {
+            assert_eq!(
+                permission.as_str().parse::<Permission>().unwrap(),
+                permission
+            );
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How failed logins for one key, a username or an address, are slowed down.
+#[derive(Debug, Clone)]
+pub struct ThrottleConfig {
+    /// Failures allowed before any wait is imposed.
+    pub free_attempts: u32,
+    /// The first wait, doubled with every further failure up to `max_delay`.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Failures after which the key is locked for `lockout`, the count restarts afterwards.
+    pub lockout_threshold: u32,
+    pub lockout: Duration,
+    /// Failures are forgotten after this long without another one.
+    pub forget_after: Duration,
+}
+
+impl ThrottleConfig {
+    pub fn usernames() -> Self {
+        ThrottleConfig {
+            free_attempts: 3,
+            base_delay: Duration::seconds(1),
+            max_delay: Duration::minutes(5),
+            lockout_threshold: 10,
+            lockout: Duration::minutes(15),
+            forget_after: Duration::hours(1),
+        }
+    }
+
+    /// Looser than for usernames, players may share an address.
+    pub fn addresses() -> Self {
+        ThrottleConfig {
+            free_attempts: 10,
+            base_delay: Duration::seconds(1),
+            max_delay: Duration::minutes(5),
+            lockout_threshold: 50,
+            lockout: Duration::hours(1),
+            forget_after: Duration::hours(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Attempts {
+    failures: u32,
+    last_failure: DateTime<Utc>,
+    blocked_until: Option<DateTime<Utc>>,
+    locked: bool,
+}
+
+#[derive(Debug)]
+struct Throttle {
+    config: ThrottleConfig,
+    attempts: HashMap<String, Attempts>,
+}
+
+impl Throttle {
+    fn new(config: ThrottleConfig) -> Self {
+        Throttle {
+            config,
+            attempts: HashMap::new(),
+        }
+    }
+
+    fn refusal(&self, key: &str, now: DateTime<Utc>) -> Option<Refusal> {
+        let attempts = self.attempts.get(key)?;
+        let until = attempts.blocked_until.filter(|until| *until > now)?;
+
+        Some(Refusal {
+            retry_after: ((until - now).num_milliseconds().max(0) as u64).div_ceil(1000),
+            locked: attempts.locked,
+        })
+    }
+
+    fn record_failure(&mut self, key: &str, now: DateTime<Utc>) {
+        let config = &self.config;
+        self.attempts.retain(|_, attempts| {
+            now - attempts.last_failure < config.forget_after
+                || attempts.blocked_until.is_some_and(|until| until > now)
+        });
+
+        let attempts = self.attempts.entry(key.into()).or_insert(Attempts {
+            failures: 0,
+            last_failure: now,
+            blocked_until: None,
+            locked: false,
+        });
+        if attempts.locked && attempts.blocked_until.is_some_and(|until| until <= now) {
+            attempts.failures = 0;
+            attempts.locked = false;
+        }
+
+        attempts.failures += 1;
+        attempts.last_failure = now;
+
+        if attempts.failures >= config.lockout_threshold {
+            attempts.blocked_until = Some(now + config.lockout);
+            attempts.locked = true;
+        } else if attempts.failures > config.free_attempts {
+            let doublings = (attempts.failures - config.free_attempts - 1).min(30);
+            let delay = config.base_delay * 2i32.pow(doublings);
+            attempts.blocked_until = Some(now + delay.min(config.max_delay));
+        }
+    }
+
+    fn reset(&mut self, key: &str) {
+        self.attempts.remove(key);
+    }
+
+    fn lockouts(
+        &self,
+        kind: LockoutKind,
+        now: DateTime<Utc>,
+    ) -> impl Iterator<Item = Lockout> + '_ {
+        self.attempts.iter().filter_map(move |(key, attempts)| {
+            let until = attempts.blocked_until.filter(|until| *until > now)?;
+
+            Some(Lockout {
+                kind,
+                key: key.clone(),
+                failures: attempts.failures,
+                locked: attempts.locked,
+                until,
+            })
+        })
+    }
+}
+
+/// Why a login was refused before its password was checked.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Refusal {
+    pub retry_after: u64,
+    /// Whether the lockout threshold was reached rather than a backoff delay.
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockoutKind {
+    Username,
+    Address,
+}
+
+/// A username or address that currently has to wait, as listed to moderators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lockout {
+    pub kind: LockoutKind,
+    pub key: String,
+    pub failures: u32,
+    pub locked: bool,
+    pub until: DateTime<Utc>,
+}
+
+/// Tracks failed logins per username and per client address. Usernames are tracked whether
+/// or not an account has them, so refusals don't reveal which ones exist.
+#[derive(Debug)]
+pub struct LoginThrottle {
+    usernames: Throttle,
+    addresses: Throttle,
+}
+
+impl Default for LoginThrottle {
+    fn default() -> Self {
+        Self::new(ThrottleConfig::usernames(), ThrottleConfig::addresses())
+    }
+}
+
+impl LoginThrottle {
+    pub fn new(usernames: ThrottleConfig, addresses: ThrottleConfig) -> Self {
+        LoginThrottle {
+            usernames: Throttle::new(usernames),
+            addresses: Throttle::new(addresses),
+        }
+    }
+
+    /// The longer of the waits for the username and the address, if any.
+    pub fn check(
+        &self,
+        username: &str,
+        address: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Option<Refusal> {
+        let by_username = self.usernames.refusal(&username_key(username), now);
+        let by_address =
+            address.and_then(|address| self.addresses.refusal(&address.to_string(), now));
+
+        match (by_username, by_address) {
+            (Some(a), Some(b)) => Some(if a.retry_after >= b.retry_after { a } else { b }),
+            (refusal, None) | (None, refusal) => refusal,
+        }
+    }
+
+    pub fn failed(&mut self, username: &str, address: Option<IpAddr>, now: DateTime<Utc>) {
+        self.usernames.record_failure(&username_key(username), now);
+        if let Some(address) = address {
+            self.addresses.record_failure(&address.to_string(), now);
+        }
+    }
+
+    /// Clears the failures of the username. Those of the address are kept, a successful login
+    /// to an own account must not reset the count of guesses against others.
+    pub fn succeeded(&mut self, username: &str) {
+        self.usernames.reset(&username_key(username));
+    }
+
+    pub fn lockouts(&self, now: DateTime<Utc>) -> Vec<Lockout> {
+        let mut lockouts = self
+            .usernames
+            .lockouts(LockoutKind::Username, now)
+            .chain(self.addresses.lockouts(LockoutKind::Address, now))
+            .collect::<Vec<_>>();
+        lockouts.sort_by_key(|lockout| std::cmp::Reverse(lockout.until));
+
+        lockouts
+    }
+}
+
+fn username_key(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(seconds)
+    }
+
+    #[test]
+    fn test_backoff_doubles_after_free_attempts() {
+        let mut throttle = LoginThrottle::default();
+
+        for _ in 0..3 {
+            throttle.failed("Brvy", None, at(0));
+        }
+        assert_eq!(throttle.check("brvy", None, at(0)), None);
+
+        throttle.failed("brvy", None, at(0));
+        assert_eq!(
+            throttle.check("brvy", None, at(0)),
+            Some(Refusal {
+                retry_after: 1,
+                locked: false
+            })
+        );
+
+        throttle.failed("brvy", None, at(1));
+        throttle.failed("brvy", None, at(3));
+        assert_eq!(throttle.check("brvy", None, at(3)).unwrap().retry_after, 4);
+        assert_eq!(throttle.check("brvy", None, at(7)), None);
+    }
+
+    #[test]
+    fn test_lockout_after_threshold() {
+        let mut throttle = LoginThrottle::default();
+
+        for _ in 0..10 {
+            throttle.failed("brvy", None, at(0));
+        }
+        let refusal = throttle.check("brvy", None, at(0)).unwrap();
+        assert!(refusal.locked);
+        assert_eq!(refusal.retry_after, 15 * 60);
+
+        let lockouts = throttle.lockouts(at(60));
+        assert_eq!(lockouts.len(), 1);
+        assert_eq!(lockouts[0].kind, LockoutKind::Username);
+        assert_eq!(lockouts[0].failures, 10);
+
+        // the count restarts once the lockout is over
+        throttle.failed("brvy", None, at(15 * 60));
+        assert_eq!(throttle.check("brvy", None, at(15 * 60)), None);
+    }
+
+    #[test]
+    fn test_success_resets_username_but_not_address() {
+        let mut throttle = LoginThrottle::new(
+            ThrottleConfig::usernames(),
+            ThrottleConfig {
+                free_attempts: 1,
+                ..ThrottleConfig::addresses()
+            },
+        );
+        let address = Some("192.0.2.1".parse().unwrap());
+
+        for _ in 0..4 {
+            throttle.failed("brvy", address, at(0));
+        }
+        throttle.succeeded("brvy");
+
+        assert_eq!(throttle.check("brvy", None, at(0)), None);
+        assert!(throttle.check("someone", address, at(0)).is_some());
+    }
+
+    #[test]
+    fn test_failures_are_forgotten() {
+        let mut throttle = LoginThrottle::default();
+
+        for _ in 0..3 {
+            throttle.failed("brvy", None, at(0));
+        }
+        throttle.failed("brvy", None, at(2 * 60 * 60));
+
+        assert_eq!(throttle.check("brvy", None, at(2 * 60 * 60)), None);
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::auth::token::TokenClaims;
+
+/// Connection tickets are meant to be used right away, by a browser that can't send the access
+/// token in a header.
+pub const TICKET_LIFETIME_SECONDS: i64 = 30;
+
+#[derive(Debug)]
+struct Ticket {
+    claims: TokenClaims,
+    expires_at: DateTime<Utc>,
+}
+
+/// Single-use tickets for opening a WebSocket, keyed by their hash so the tickets themselves
+/// never reach the bus or stay in memory.
+#[derive(Debug, Default)]
+pub struct TicketStore {
+    tickets: HashMap<String, Ticket>,
+}
+
+impl TicketStore {
+    pub fn insert(&mut self, ticket_hash: String, claims: TokenClaims, now: DateTime<Utc>) {
+        self.tickets.retain(|_, ticket| ticket.expires_at > now);
+        self.tickets.insert(
+            ticket_hash,
+            Ticket {
+                claims,
+                expires_at: now + Duration::seconds(TICKET_LIFETIME_SECONDS),
+            },
+        );
+    }
+
+    /// Removes the ticket, it can't be redeemed a second time even if it had expired.
+    pub fn redeem(&mut self, ticket_hash: &str, now: DateTime<Utc>) -> Option<TokenClaims> {
+        self.tickets
+            .remove(ticket_hash)
+            .filter(|ticket| ticket.expires_at > now)
+            .map(|ticket| ticket.claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::auth::permission::Role;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(seconds)
+    }
+
+    fn claims() -> TokenClaims {
+        TokenClaims {
+            account_id: Uuid::new_v4(),
+            session_id: Uuid::new_v4(),
+            role: Role::Player,
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn test_ticket_is_single_use() {
+        let mut store = TicketStore::default();
+        let claims = claims();
+
+        store.insert("hash".into(), claims, at(0));
+
+        assert_eq!(store.redeem("other", at(1)), None);
+        assert_eq!(store.redeem("hash", at(1)), Some(claims));
+        assert_eq!(store.redeem("hash", at(1)), None);
+    }
+
+    #[test]
+    fn test_ticket_expires() {
+        let mut store = TicketStore::default();
+
+        store.insert("late".into(), claims(), at(0));
+        assert_eq!(store.redeem("late", at(TICKET_LIFETIME_SECONDS)), None);
+
+        store.insert("stale".into(), claims(), at(0));
+        store.insert("fresh".into(), claims(), at(TICKET_LIFETIME_SECONDS + 1));
+        assert_eq!(store.tickets.len(), 1);
+    }
+}
@@ -1,69 +1,352 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
 use rusty_paseto::{
-    core::{Key, Local, PasetoSymmetricKey, V4},
-    prelude::{ExpirationClaim, PasetoBuilder, PasetoParser, SubjectClaim},
+    core::{
+        Footer, Key, Local, PasetoAsymmetricPrivateKey, PasetoAsymmetricPublicKey,
+        PasetoSymmetricKey, Public, V4,
+    },
+    prelude::{
+        CustomClaim, ExpirationClaim, PasetoBuilder, PasetoParser, SubjectClaim,
+        TokenIdentifierClaim,
+    },
 };
 use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-static PASETO_KEY: &str = "your-secret-key";
+use crate::auth::api_key;
+use crate::auth::keys::{self, KeyRing, TokenMode};
+use crate::auth::permission::{Permission, PermissionSet, Role};
+use crate::error::AuthError;
+use crate::messaging::{
+    broker::MessageBroker,
+    model::{Message, MessageBody},
+};
+use crate::persistence::{Query, QueryResponse};
+
+/// How long an access token stays valid, clients use their refresh token to get a new one.
+pub const ACCESS_TOKEN_LIFETIME_MINUTES: i64 = 15;
+
+/// How long a session, and every refresh token issued for it, stays valid after login.
+pub const SESSION_LIFETIME_DAYS: i64 = 30;
+
+/// What a valid token says about its bearer.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TokenClaims {
+    pub account_id: Uuid,
+    /// The `account_sessions` row the token was issued for, carried in the `jti` claim, or the
+    /// API key.
+    pub session_id: Uuid,
+    /// The role of the account when the token was issued, role changes revoke its sessions.
+    pub role: Role,
+    /// What an API key is limited to, `None` for session tokens.
+    #[serde(default)]
+    pub scope: Option<PermissionSet>,
+}
 
-pub fn generate_token(user_id: &str) -> Result<String, anyhow::Error> {
-    let expiration = chrono::Utc::now() + chrono::Duration::days(2);
+impl TokenClaims {
+    /// Whether both the role and, for API keys, the key's scope grant the permission.
+    pub fn can(&self, permission: Permission) -> bool {
+        self.role.can(permission) && self.scope.is_none_or(|scope| scope.contains(permission))
+    }
+}
 
-    let pass_hash = Sha256::digest(PASETO_KEY.as_bytes());
+/// Protects the token with the current key of the installed key ring, naming it in the footer.
+pub fn generate_token(
+    claims: &TokenClaims,
+    expires_at: DateTime<Utc>,
+) -> Result<String, anyhow::Error> {
+    generate_with(&keys::keyring(), claims, expires_at)
+}
 
-    let expiration_claim: ExpirationClaim = expiration.to_rfc3339().try_into()?;
+fn generate_with(
+    ring: &KeyRing,
+    claims: &TokenClaims,
+    expires_at: DateTime<Utc>,
+) -> Result<String, anyhow::Error> {
+    let expiration_claim: ExpirationClaim = expires_at.to_rfc3339().try_into()?;
+    let account_id = claims.account_id.to_string();
+    let session_id = claims.session_id.to_string();
+    let role_claim = CustomClaim::try_from(("role", claims.role.as_str()))?;
+    let signing_key = ring.current();
+    let footer = key_footer(&signing_key.id);
 
-    let key = PasetoSymmetricKey::<V4, Local>::from(Key::from(pass_hash.as_slice()));
-    let token = PasetoBuilder::<V4, Local>::default()
-        .set_claim(SubjectClaim::from(user_id))
-        .set_claim(expiration_claim)
-        .build(&key)?;
+    let token = match ring.mode() {
+        TokenMode::Local => {
+            let key = PasetoSymmetricKey::<V4, Local>::from(Key::from(signing_key.material()));
+            PasetoBuilder::<V4, Local>::default()
+                .set_claim(SubjectClaim::from(account_id.as_str()))
+                .set_claim(TokenIdentifierClaim::from(session_id.as_str()))
+                .set_claim(expiration_claim)
+                .set_claim(role_claim)
+                .set_footer(Footer::from(footer.as_str()))
+                .build(&key)?
+        }
+        TokenMode::Public => {
+            let keypair = signing_key.keypair();
+            let key = PasetoAsymmetricPrivateKey::<V4, Public>::from(keypair.as_slice());
+            PasetoBuilder::<V4, Public>::default()
+                .set_claim(SubjectClaim::from(account_id.as_str()))
+                .set_claim(TokenIdentifierClaim::from(session_id.as_str()))
+                .set_claim(expiration_claim)
+                .set_claim(role_claim)
+                .set_footer(Footer::from(footer.as_str()))
+                .build(&key)?
+        }
+    };
     Ok(token)
 }
 
-pub fn validate_token(token: &str) -> Result<Uuid, anyhow::Error> {
-    let pass_hash = Sha256::digest(PASETO_KEY.as_bytes());
+fn key_footer(key_id: &str) -> String {
+    serde_json::json!({ "kid": key_id }).to_string()
+}
 
-    let key = PasetoSymmetricKey::<V4, Local>::from(Key::from(pass_hash.as_slice()));
+/// An opaque random refresh token, only its hash is stored.
+pub fn generate_refresh_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
 
-    let parsed_token = PasetoParser::<V4, Local>::default()
-        // you can check any claim even custom claims
-        .parse(token, &key)?;
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
 
-    Ok(Uuid::parse_str(parsed_token["sub"].as_str().ok_or_else(
-        || anyhow::anyhow!("Missing subject claim"),
-    )?)?)
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token))
 }
 
-pub fn validate_headers(headers: &axum::http::HeaderMap) -> Result<Uuid, anyhow::Error> {
+/// Checks the token's signature and expiry, not whether its session is still active.
+pub fn validate_token(token: &str) -> Result<TokenClaims, anyhow::Error> {
+    validate_with(&keys::keyring(), token)
+}
+
+/// Only tokens of the configured mode are accepted, a `v4.local` token is rejected in public
+/// mode and the other way around.
+fn validate_with(ring: &KeyRing, token: &str) -> Result<TokenClaims, anyhow::Error> {
+    let footer =
+        Footer::try_from_token(token)?.ok_or_else(|| anyhow::anyhow!("Token has no key id"))?;
+    let key_id = serde_json::from_str::<serde_json::Value>(&footer)?["kid"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("Token has no key id"))?;
+    let signing_key = ring
+        .get(&key_id)
+        .ok_or_else(|| anyhow::anyhow!("Token was signed with unknown key {}", key_id))?;
+
+    let parsed_token = match ring.mode() {
+        TokenMode::Local => {
+            let key = PasetoSymmetricKey::<V4, Local>::from(Key::from(signing_key.material()));
+            let parsed = PasetoParser::<V4, Local>::default()
+                .set_footer(Footer::from(footer.as_str()))
+                .parse(token, &key)?;
+            parsed
+        }
+        TokenMode::Public => {
+            let public_key = Key::from(signing_key.public_key());
+            let key = PasetoAsymmetricPublicKey::<V4, Public>::from(&public_key);
+            let parsed = PasetoParser::<V4, Public>::default()
+                .set_footer(Footer::from(footer.as_str()))
+                .parse(token, &key)?;
+            parsed
+        }
+    };
+
+    let claim = |name: &str| -> Result<Uuid, anyhow::Error> {
+        Ok(Uuid::parse_str(parsed_token[name].as_str().ok_or_else(
+            || anyhow::anyhow!("Missing {} claim", name),
+        )?)?)
+    };
+
+    let role = parsed_token["role"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Missing role claim"))?
+        .parse()?;
+
+    Ok(TokenClaims {
+        account_id: claim("sub")?,
+        session_id: claim("jti")?,
+        role,
+        scope: None,
+    })
+}
+
+pub fn bearer_token(headers: &axum::http::HeaderMap) -> Result<&str, anyhow::Error> {
     if let Some(auth_header) = headers.get("Authorization") {
         let auth_str = auth_header.to_str()?;
         if auth_str.starts_with("Bearer ") {
-            let token = auth_str.trim_start_matches("Bearer ").trim();
-            return validate_token(token);
+            return Ok(auth_str.trim_start_matches("Bearer ").trim());
         }
     }
     Err(anyhow::anyhow!("Missing or invalid Authorization header"))
 }
 
+/// Validates the bearer token and checks that its session has neither expired nor been revoked
+/// and that the account isn't blocked, in which case the error is an `AuthError`.
+pub async fn validate_headers(
+    headers: &axum::http::HeaderMap,
+    broker: &MessageBroker,
+) -> Result<TokenClaims, anyhow::Error> {
+    validate_bearer(bearer_token(headers)?, broker).await
+}
+
+/// Validates an access token together with its session, or an API key.
+pub async fn validate_bearer(
+    token: &str,
+    broker: &MessageBroker,
+) -> Result<TokenClaims, anyhow::Error> {
+    if api_key::is_api_key(token) {
+        return api_key::validate(token, broker).await;
+    }
+
+    validate_session(validate_token(token)?, broker).await
+}
+
+/// Checks that the session of already validated claims is still active, for tokens that didn't
+/// come through the `Authorization` header.
+pub async fn validate_session(
+    claims: TokenClaims,
+    broker: &MessageBroker,
+) -> Result<TokenClaims, anyhow::Error> {
+    let response = broker
+        .request(Message::new_request(
+            MessageBody::PersistenceQueryRequest(Query::GetSession {
+                session_id: claims.session_id,
+            }),
+            Some("persistence".into()),
+        ))
+        .await?;
+
+    match response.map(|msg| msg.body) {
+        Some(MessageBody::PersistenceQueryResponse(QueryResponse::Session(Some(session))))
+            if session.account_id == claims.account_id =>
+        {
+            Ok(claims)
+        }
+        Some(MessageBody::PersistenceQueryResponse(QueryResponse::Session(_))) => {
+            Err(anyhow::anyhow!("Session is no longer active"))
+        }
+        Some(MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))) => {
+            Err(AuthError::AccountBlocked {
+                state: status.state,
+                reason: status.reason,
+            }
+            .into())
+        }
+        Some(MessageBody::PersistenceQueryResponse(QueryResponse::SessionsFailed(e))) => {
+            Err(anyhow::anyhow!(e))
+        }
+        _ => Err(anyhow::anyhow!("Unexpected response to session query")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use uuid::Uuid;
 
     use super::*;
+    use crate::auth::keys::SecretKey;
+
+    fn player(account_id: Uuid) -> TokenClaims {
+        TokenClaims {
+            account_id,
+            session_id: Uuid::new_v4(),
+            role: Role::Player,
+            scope: None,
+        }
+    }
 
     #[tokio::test]
     async fn test_generate_token_success() {
         let id = Uuid::parse_str("123e4567-e89b-12d3-a456-426614174000").unwrap();
-        let token = generate_token(id.to_string().as_str());
+        let issued = TokenClaims {
+            role: Role::Moderator,
+            ..player(id)
+        };
+        let expires_at = Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES);
+        let token = generate_token(&issued, expires_at);
         assert!(token.is_ok());
         let token_str = token.unwrap();
         assert!(!token_str.is_empty());
 
-        let user_id = validate_token(&token_str);
-        assert!(user_id.is_ok());
-        let user_id = user_id.unwrap();
-        assert_eq!(id, user_id);
+        let claims = validate_token(&token_str);
+        assert!(claims.is_ok());
+        assert_eq!(claims.unwrap(), issued);
+    }
+
+    #[test]
+    fn test_refresh_tokens_are_random() {
+        let token = generate_refresh_token();
+
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate_refresh_token());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let expires_at = Utc::now() - chrono::Duration::minutes(1);
+        let token = generate_token(&player(Uuid::new_v4()), expires_at).unwrap();
+
+        assert!(validate_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_tokens_survive_key_rotation() {
+        let old = SecretKey::new("2025-06", [1; 32]).unwrap();
+        let new = SecretKey::new("2025-12", [2; 32]).unwrap();
+        let expires_at = Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES);
+
+        for mode in [TokenMode::Local, TokenMode::Public] {
+            let before = KeyRing::new(mode, vec![old.clone()], None).unwrap();
+            let token = generate_with(&before, &player(Uuid::new_v4()), expires_at).unwrap();
+
+            let rotated = KeyRing::new(mode, vec![old.clone(), new.clone()], None).unwrap();
+            assert_eq!(rotated.current().id, "2025-12");
+            assert!(validate_with(&rotated, &token).is_ok());
+
+            let retired = KeyRing::new(mode, vec![new.clone()], None).unwrap();
+            assert!(validate_with(&retired, &token).is_err());
+        }
+    }
+
+    #[test]
+    fn test_token_mode_must_match() {
+        let key = SecretKey::new("2025-12", [3; 32]).unwrap();
+        let local = KeyRing::new(TokenMode::Local, vec![key.clone()], None).unwrap();
+        let public = KeyRing::new(TokenMode::Public, vec![key], None).unwrap();
+        let expires_at = Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES);
+        let account_id = Uuid::new_v4();
+
+        let token = generate_with(&public, &player(account_id), expires_at).unwrap();
+        assert!(token.starts_with("v4.public."));
+        assert_eq!(
+            validate_with(&public, &token).unwrap().account_id,
+            account_id
+        );
+        assert!(validate_with(&local, &token).is_err());
+
+        let token = generate_with(&local, &player(account_id), expires_at).unwrap();
+        assert!(token.starts_with("v4.local."));
+        assert!(validate_with(&public, &token).is_err());
+    }
+
+    #[test]
+    fn test_api_key_scope_limits_role() {
+        let claims = TokenClaims {
+            role: Role::Moderator,
+            ..player(Uuid::new_v4())
+        };
+        assert!(claims.can(Permission::SuspendAccounts));
+
+        let key = TokenClaims {
+            scope: Some(
+                [Permission::ViewInventory, Permission::BanAccounts]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..claims
+        };
+        assert!(key.can(Permission::ViewInventory));
+        assert!(!key.can(Permission::SuspendAccounts));
+        // the scope never grants more than the role
+        assert!(!key.can(Permission::BanAccounts));
     }
 }
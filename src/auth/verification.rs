@@ -0,0 +1,148 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::mail::{Mail, Outbox};
+
+/// What a single-use token sent by mail is good for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+        }
+    }
+
+    /// Reset tokens grant the account, so they expire much sooner.
+    pub fn lifetime(&self) -> Duration {
+        match self {
+            TokenPurpose::VerifyEmail => Duration::hours(24),
+            TokenPurpose::ResetPassword => Duration::hours(1),
+        }
+    }
+}
+
+impl FromStr for TokenPurpose {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "verify_email" => Ok(TokenPurpose::VerifyEmail),
+            "reset_password" => Ok(TokenPurpose::ResetPassword),
+            other => Err(anyhow::anyhow!("unknown token purpose {}", other)),
+        }
+    }
+}
+
+impl Display for TokenPurpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Who a token is issued to, the signed in account or whoever owns an address.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Recipient {
+    Account(Uuid),
+    Email(String),
+}
+
+/// The account a token was issued for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MailRecipient {
+    pub account_id: Uuid,
+    pub username: String,
+    pub email: String,
+}
+
+/// The mail carrying a freshly issued token.
+pub fn compose(
+    outbox: &Outbox,
+    purpose: TokenPurpose,
+    recipient: &MailRecipient,
+    token: &str,
+) -> Mail {
+    let expires_in = match purpose.lifetime().num_hours() {
+        1 => "1 hour".to_string(),
+        hours => format!("{} hours", hours),
+    };
+    let (subject, body) = match purpose {
+        TokenPurpose::VerifyEmail => (
+            "Verify your email address",
+            format!(
+                "Hello {},\n\nopen {} to verify your email address.\n\nThe link expires in {}.",
+                recipient.username,
+                outbox.link(&format!("/auth/verify-email?token={}", token)),
+                expires_in
+            ),
+        ),
+        TokenPurpose::ResetPassword => (
+            "Reset your password",
+            format!(
+                "Hello {},\n\nsomeone asked to reset the password of your account. Send\n\n    {}\n\nalong with a new password to {} to choose one, all your sessions are signed out.\n\nThe token expires in {}. If it wasn't you, ignore this mail.",
+                recipient.username,
+                token,
+                outbox.link("/auth/password-reset"),
+                expires_in
+            ),
+        ),
+    };
+
+    Mail {
+        to: recipient.email.clone(),
+        subject: subject.to_string(),
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mail::StdoutMailer;
+
+    #[test]
+    fn test_purpose_round_trip() {
+        for purpose in [TokenPurpose::VerifyEmail, TokenPurpose::ResetPassword] {
+            assert_eq!(purpose.as_str().parse::<TokenPurpose>().unwrap(), purpose);
+        }
+        assert!("login".parse::<TokenPurpose>().is_err());
+        assert!(TokenPurpose::ResetPassword.lifetime() < TokenPurpose::VerifyEmail.lifetime());
+    }
+
+    #[test]
+    fn test_compose_carries_token() {
+        let outbox = Outbox::new(
+            Arc::new(StdoutMailer {
+                from: "noreply@example.com".into(),
+            }),
+            "https://example.com",
+        );
+        let recipient = MailRecipient {
+            account_id: Uuid::new_v4(),
+            username: "brvy".into(),
+            email: "brvy@example.com".into(),
+        };
+
+        let mail = compose(&outbox, TokenPurpose::VerifyEmail, &recipient, "abc");
+        assert_eq!(mail.to, "brvy@example.com");
+        assert!(mail
+            .body
+            .contains("https://example.com/auth/verify-email?token=abc"));
+
+        let mail = compose(&outbox, TokenPurpose::ResetPassword, &recipient, "def");
+        assert!(mail.body.contains("    def\n"));
+        assert!(!format!("{:?}", mail).contains("def"));
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Source of the current time for the simulation. Everything that stamps or compares game time
+/// reads it from here instead of the system clock, so a virtual clock makes runs reproducible.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Moves a virtual clock forward. The system clock follows wall time and ignores this.
+    fn advance(&self, _by: Duration) {}
+
+    /// Whether time only moves when [`Clock::advance`] is called.
+    fn is_virtual(&self) -> bool {
+        false
+    }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that stands still until it is advanced, used for deterministic simulation runs.
+#[derive(Debug)]
+pub struct VirtualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl VirtualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.lock() = now;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DateTime<Utc>> {
+        match self.now.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.lock()
+    }
+
+    fn advance(&self, by: Duration) {
+        let mut now = self.lock();
+        *now += by;
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CLOCK: RwLock<SharedClock> = RwLock::new(Arc::new(SystemClock));
+}
+
+/// Replaces the process-wide clock. Call it once at startup, before any actor is running.
+/// Deterministic runs sharing a process take the clock through [`crate::rng::DeterministicRun`].
+pub fn install(clock: SharedClock) {
+    match CLOCK.write() {
+        Ok(mut guard) => *guard = clock,
+        Err(poisoned) => *poisoned.into_inner() = clock,
+    }
+}
+
+/// The process-wide clock, the system clock unless another one was installed.
+pub fn shared() -> SharedClock {
+    match CLOCK.read() {
+        Ok(guard) => Arc::clone(&guard),
+        Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+    }
+}
+
+pub fn now() -> DateTime<Utc> {
+    shared().now()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock_only_moves_when_advanced() {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        let clock = VirtualClock::new(start);
+
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::milliseconds(1500));
+        assert_eq!(clock.now(), start + Duration::milliseconds(1500));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+        assert!(clock.is_virtual());
+        assert!(!SystemClock.is_virtual());
+    }
+}
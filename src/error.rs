@@ -1,6 +1,13 @@
 use std::fmt::Display;
+
+use diesel::result::DatabaseErrorKind;
+use serde::{Deserialize, Serialize};
 use tokio_tungstenite::tungstenite;
 
+use crate::auth::permission::AccountState;
+use crate::auth::throttle::Refusal;
+use crate::model::Requirement;
+
 #[derive(Debug)]
 pub enum ApiError {
     WebsocketError(tungstenite::Error),
@@ -19,3 +26,155 @@ impl Display for ApiError {
         }
     }
 }
+
+#[derive(Debug)]
+pub enum InventoryError {
+    NotFound,
+    InvalidTransition { command: String, status: String },
+    InsufficientResources(String),
+    MaxLevelReached(i32),
+    MissingRequirements(Vec<Requirement>),
+    InvalidPolicy(String),
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for InventoryError {
+    fn from(err: diesel::result::Error) -> Self {
+        match err {
+            diesel::result::Error::NotFound => InventoryError::NotFound,
+            err => InventoryError::Database(err),
+        }
+    }
+}
+
+impl Display for InventoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InventoryError::NotFound => write!(f, "building or blueprint not found"),
+            InventoryError::InvalidTransition { command, status } => {
+                write!(f, "cannot {} a building that is {}", command, status)
+            }
+            InventoryError::InsufficientResources(resource) => {
+                write!(f, "not enough {} in inventory", resource)
+            }
+            InventoryError::MaxLevelReached(level) => {
+                write!(f, "building is already at its maximum level {}", level)
+            }
+            InventoryError::MissingRequirements(missing) => {
+                let missing = missing
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "missing requirements: {}", missing)
+            }
+            InventoryError::InvalidPolicy(reason) => write!(f, "invalid policy: {}", reason),
+            InventoryError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+/// Errors of account management. Unlike the other errors it is sent over the bus as is, so the
+/// HTTP handlers can tell invalid input and conflicts from internal failures.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AccountError {
+    InvalidUsername(String),
+    InvalidEmail(String),
+    InvalidPassword(String),
+    UsernameTaken,
+    EmailTaken,
+    NotFound,
+    /// The mailed token is unknown, expired or already used.
+    InvalidToken,
+    EmailAlreadyVerified,
+    InvalidApiKey(String),
+    /// The caller lacks the permission or doesn't outrank the account.
+    Forbidden(String),
+    Internal(String),
+}
+
+impl AccountError {
+    /// Whether the error was caused by the request rather than by the server.
+    pub fn is_client_error(&self) -> bool {
+        !matches!(self, AccountError::Internal(_))
+    }
+}
+
+impl From<diesel::result::Error> for AccountError {
+    fn from(err: diesel::result::Error) -> Self {
+        match &err {
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                match info.constraint_name() {
+                    Some("accounts_username_key") => AccountError::UsernameTaken,
+                    Some("accounts_email_key") => AccountError::EmailTaken,
+                    _ => AccountError::Internal(err.to_string()),
+                }
+            }
+            _ => AccountError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl Display for AccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountError::InvalidUsername(reason) => write!(f, "invalid username: {}", reason),
+            AccountError::InvalidEmail(reason) => write!(f, "invalid email: {}", reason),
+            AccountError::InvalidPassword(reason) => write!(f, "invalid password: {}", reason),
+            AccountError::UsernameTaken => write!(f, "username is already taken"),
+            AccountError::EmailTaken => write!(f, "email is already registered"),
+            AccountError::NotFound => write!(f, "account not found"),
+            AccountError::InvalidToken => write!(f, "invalid or expired token"),
+            AccountError::EmailAlreadyVerified => write!(f, "email is already verified"),
+            AccountError::InvalidApiKey(reason) => write!(f, "invalid API key: {}", reason),
+            AccountError::Forbidden(reason) => write!(f, "forbidden: {}", reason),
+            AccountError::Internal(err) => write!(f, "internal error: {}", err),
+        }
+    }
+}
+
+/// Why a login, refresh or connection was refused. Sent over the bus like `AccountError`, the
+/// reason of a blocked account is only revealed once its password or token checked out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AuthError {
+    InvalidCredentials,
+    AccountBlocked {
+        state: AccountState,
+        reason: Option<String>,
+    },
+    /// Too many failed logins for the username or the address, the password wasn't checked.
+    Throttled(Refusal),
+    Internal(String),
+}
+
+impl std::error::Error for AuthError {}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::InvalidCredentials => write!(f, "invalid credentials"),
+            AuthError::AccountBlocked {
+                state,
+                reason: Some(reason),
+            } => write!(f, "account is {}: {}", state, reason),
+            AuthError::AccountBlocked {
+                state,
+                reason: None,
+            } => {
+                write!(f, "account is {}", state)
+            }
+            AuthError::Throttled(Refusal {
+                retry_after,
+                locked: true,
+            }) => write!(f, "locked, retry after {} seconds", retry_after),
+            AuthError::Throttled(Refusal { retry_after, .. }) => {
+                write!(
+                    f,
+                    "too many failed attempts, retry after {} seconds",
+                    retry_after
+                )
+            }
+            AuthError::Internal(err) => write!(f, "internal error: {}", err),
+        }
+    }
+}
@@ -1,9 +1,12 @@
 pub mod actor;
 pub mod auth;
+pub mod clock;
 pub mod error;
+pub mod mail;
 pub mod messaging;
 pub mod model;
 pub mod persistence;
+pub mod rng;
 pub mod schema;
 pub mod websocket;
 
@@ -1,14 +1,15 @@
 use an_daghdha::actor::auth::AuthActorHandler;
+use an_daghdha::actor::scheduler::SchedulerActorHandler;
 use an_daghdha::messaging::{
     broker::MessageBroker, model::Message as InternalMessage, model::MessageBody, model::Status,
 };
+use an_daghdha::model::{JobSchedule, MaintenanceTask};
 use an_daghdha::persistence::HandlerStatus;
 use an_daghdha::{auth, AppState};
-use axum::extract::ws::Message;
-use axum::extract::{ws::WebSocket, State, WebSocketUpgrade};
+use axum::extract::{ws::WebSocket, Query, State, WebSocketUpgrade};
 use axum::http::HeaderMap;
 use axum::response::Response;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post};
 use axum::{Json, Router};
 use diesel::r2d2::ConnectionManager;
 use diesel::PgConnection;
@@ -16,8 +17,8 @@ use r2d2::Pool;
 use serde_json::json;
 use tokio::signal;
 
-use an_daghdha::auth::token;
 use an_daghdha::websocket::api;
+use an_daghdha::websocket::handshake::{self, ConnectParams, Credentials};
 use tokio::task::JoinHandle;
 
 #[tokio::main]
@@ -28,6 +29,11 @@ async fn main() -> Result<(), anyhow::Error> {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    init_simulation()?;
+    auth::password::install(auth::password::PasswordConfig::from_env()?);
+    auth::keys::install(auth::keys::KeyRing::from_env()?);
+    an_daghdha::mail::install(an_daghdha::mail::Outbox::from_env()?);
+
     let (broker, mut handler) = MessageBroker::new();
 
     let task_handle = tokio::spawn(async move {
@@ -36,9 +42,6 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let bouncer = api::Bouncer::new(&broker);
 
-    let ticker = an_daghdha::actor::ticker::TickerActorHandler::new();
-    let ticker_handle = ticker.start(broker.clone()).await?;
-
     let database_url = std::env::var("DATABASE_URL")
         .map_err(|_| anyhow::anyhow!("DATABASE_URL environment variable not set"))?;
 
@@ -51,6 +54,22 @@ async fn main() -> Result<(), anyhow::Error> {
         auth_actor.listen(auth_broker).await.unwrap();
     });
 
+    // subscribe before the inventory actors start so their tick registrations reach the ticker
+    let mut ticker = an_daghdha::actor::ticker::TickerActorHandler::new();
+    ticker.subscribe(&broker).await?;
+
+    let calendar_actor = an_daghdha::actor::calendar::CalendarActorHandler::new();
+    let calendar_broker = broker.clone();
+    tokio::spawn(async move {
+        calendar_actor.listen(calendar_broker).await.unwrap();
+    });
+
+    let scheduler_actor = init_scheduler()?;
+    let scheduler_broker = broker.clone();
+    tokio::spawn(async move {
+        scheduler_actor.listen(scheduler_broker).await.unwrap();
+    });
+
     let inventory_ids = AuthActorHandler::get_inventory_ids(&broker).await;
 
     tracing::info!("Starting inventory actors for IDs: {:?}", inventory_ids);
@@ -65,11 +84,44 @@ async fn main() -> Result<(), anyhow::Error> {
         });
     }
 
+    // the ticker loads its seq from persistence and may replay missed ticks, so it starts
+    // once persistence and the inventory actors are up
+    let ticker_handle = ticker.start(broker.clone()).await?;
+
     let state = (bouncer, broker.clone()).into();
 
     let app = Router::new()
         .route("/health", get(|| async { Json(json!({ "status": "ok" })) }))
         .route("/auth/login", post(auth::handler::handle_login))
+        .route("/auth/register", post(auth::handler::handle_register))
+        .route("/auth/refresh", post(auth::handler::handle_refresh))
+        .route("/auth/logout", post(auth::handler::handle_logout))
+        .route("/auth/logout/all", post(auth::handler::handle_logout_all))
+        .route("/auth/sessions", get(auth::handler::handle_sessions))
+        .route("/auth/keys", get(auth::handler::handle_public_keys))
+        .route(
+            "/auth/ticket",
+            post(auth::handler::handle_connection_ticket),
+        )
+        .route(
+            "/auth/api-keys",
+            get(auth::handler::handle_api_keys).post(auth::handler::handle_create_api_key),
+        )
+        .route(
+            "/auth/api-keys/{id}",
+            delete(auth::handler::handle_revoke_api_key),
+        )
+        .route(
+            "/admin/accounts/{id}/state",
+            post(auth::handler::handle_account_state),
+        )
+        .route(
+            "/admin/accounts/{id}/role",
+            post(auth::handler::handle_account_role),
+        )
+        .merge(auth::handler::account_routes())
+        .route("/admin/lockouts", get(auth::handler::handle_lockouts))
+        .route("/admin/scheduler", get(auth::handler::handle_scheduler))
         .route("/rtc", get(ws_handler))
         .with_state(state);
 
@@ -80,7 +132,13 @@ async fn main() -> Result<(), anyhow::Error> {
         address = format!("{}", listener.local_addr().unwrap()),
         "http service listening"
     );
-    axum::serve(listener, app).await.unwrap();
+    // logins are throttled per client address
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
 
     // Wait for Ctrl+C signal
     match signal::ctrl_c().await {
@@ -128,31 +186,85 @@ async fn main() -> Result<(), anyhow::Error> {
 async fn ws_handler(
     ws: WebSocketUpgrade,
     headers: HeaderMap,
+    Query(params): Query<ConnectParams>,
     State(state): State<AppState>,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, headers, state))
+    let credentials = handshake::credentials(&headers, &params);
+    ws.protocols([handshake::PROTOCOL])
+        .on_upgrade(move |socket| handle_socket(socket, credentials, state))
 }
 
-async fn handle_socket(mut ws: WebSocket, headers: HeaderMap, state: AppState) {
-    tracing::info!(
-        headers = format!("{:?}", headers),
-        "new WebSocket connection"
-    );
-
-    let user_id = match token::validate_headers(&headers) {
-        Ok(uid) => uid,
+async fn handle_socket(mut ws: WebSocket, credentials: Credentials, state: AppState) {
+    let claims = match handshake::authenticate(&mut ws, credentials, &state.broker).await {
+        Ok(claims) => claims,
         Err(e) => {
-            tracing::error!("Failed to validate headers: {}", e);
-            ws.send(Message::Text("Unauthorized".into()))
-                .await
-                .unwrap_or_else(|e| {
-                    tracing::error!("Failed to send unauthorized message: {}", e);
-                });
+            tracing::error!("Failed to authenticate WebSocket connection: {}", e);
+            handshake::refuse(&mut ws, &e).await;
             return;
         }
     };
 
-    state.bouncer.handle_connection(user_id, ws).await;
+    state.bouncer.handle_connection(claims, ws).await;
+}
+
+/// Registers the recurring maintenance jobs.
+fn init_scheduler() -> Result<SchedulerActorHandler, anyhow::Error> {
+    let mut scheduler = SchedulerActorHandler::new();
+
+    let jobs = [
+        (
+            "purge_expired_sessions",
+            JobSchedule::Cron {
+                expression: "0 * * * *".into(),
+            },
+            MaintenanceTask::PurgeExpiredSessions,
+        ),
+        (
+            "compact_buildings",
+            JobSchedule::EveryTicks { ticks: 600 },
+            MaintenanceTask::CompactBuildings,
+        ),
+        (
+            "daily_stats",
+            JobSchedule::Cron {
+                expression: "0 0 * * *".into(),
+            },
+            MaintenanceTask::DailyStats,
+        ),
+    ];
+    for (name, schedule, task) in jobs {
+        scheduler
+            .register(name, schedule, task)
+            .map_err(|e| anyhow::anyhow!("failed to register job {}: {}", name, e))?;
+    }
+
+    Ok(scheduler)
+}
+
+/// Seeds the simulation RNG and switches to a virtual clock when configured in the environment.
+fn init_simulation() -> Result<(), anyhow::Error> {
+    if let Ok(seed) = std::env::var("SIMULATION_SEED") {
+        let seed = seed
+            .parse::<u64>()
+            .map_err(|e| anyhow::anyhow!("invalid SIMULATION_SEED: {}", e))?;
+        an_daghdha::rng::install(seed);
+    }
+
+    if std::env::var("SIMULATION_CLOCK").as_deref() == Ok("virtual") {
+        let start = match std::env::var("SIMULATION_START") {
+            Ok(start) => chrono::DateTime::parse_from_rfc3339(&start)
+                .map_err(|e| anyhow::anyhow!("invalid SIMULATION_START: {}", e))?
+                .with_timezone(&chrono::Utc),
+            Err(_) => chrono::DateTime::<chrono::Utc>::UNIX_EPOCH,
+        };
+
+        tracing::info!(start = start.to_rfc3339(), "running with a virtual clock");
+        an_daghdha::clock::install(std::sync::Arc::new(an_daghdha::clock::VirtualClock::new(
+            start,
+        )));
+    }
+
+    Ok(())
 }
 
 pub async fn init_persistence(
@@ -4,6 +4,17 @@ use serde_json::Value;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+use crate::auth::model::AuthTokens;
+use crate::auth::permission::{AccountState, AccountStatus, PermissionSet, Role};
+use crate::auth::throttle::Lockout;
+use crate::auth::token::TokenClaims;
+use crate::auth::verification::{Recipient, TokenPurpose};
+use crate::error::{AccountError, AuthError};
+use crate::model::{
+    AccountRequest, ApiKeyInfo, BlueprintAvailability, BuildingCommand, BuildingCommandOutcome,
+    CalendarEvent, InventoryBuilding, InventoryPolicy, JobRecord, PolicyOutcome, ProductionReport,
+    ResourceStock, SessionInfo, TickOverrun, TickPhase, TickerCommand, TickerState, UpkeepReport,
+};
 use crate::persistence::{Query, QueryResponse};
 
 #[derive(Clone, Debug, PartialEq)]
@@ -25,14 +36,160 @@ pub enum MessageBody {
     AuthenticationRequest {
         user: String,
         password: String,
+        /// The client's address, failed logins are throttled per username and per address.
+        address: Option<std::net::IpAddr>,
     },
-    AuthenticationResponse(Result<String, String>),
+    AuthenticationResponse(Result<AuthTokens, AuthError>),
+
+    /// Carries the SHA-256 of the refresh token rather than the token itself.
+    RefreshRequest {
+        token_hash: String,
+    },
+    RefreshResponse(Result<AuthTokens, AuthError>),
+
+    RegistrationRequest(AccountRequest),
+    /// The id of the new account.
+    RegistrationResponse(Result<Uuid, AccountError>),
+
+    SessionsRequest {
+        account_id: Uuid,
+    },
+    SessionsResponse(Result<Vec<SessionInfo>, String>),
+    /// Revokes one session of the account, or all of them when `session_id` is `None`.
+    LogoutRequest {
+        account_id: Uuid,
+        session_id: Option<Uuid>,
+    },
+    /// The ids of the revoked sessions.
+    LogoutResponse(Result<Vec<Uuid>, String>),
+    /// Published on `out:session:{id}` so connections using the session can close.
+    SessionRevoked {
+        session_id: Uuid,
+    },
+
+    /// Suspends, bans or reactivates an account on behalf of `caller`.
+    AccountStateRequest {
+        caller: TokenClaims,
+        account_id: Uuid,
+        state: AccountState,
+        reason: Option<String>,
+    },
+    AccountRoleRequest {
+        caller: TokenClaims,
+        account_id: Uuid,
+        role: Role,
+    },
+    AccountUpdateResponse(Result<AccountStatus, AccountError>),
+    LockoutsRequest,
+    LockoutsResponse(Vec<Lockout>),
+    /// Issues a verification or password reset token and mails it to the recipient.
+    AccountMailRequest {
+        recipient: Recipient,
+        purpose: TokenPurpose,
+    },
+    AccountMailResponse(Result<(), AccountError>),
+    VerifyEmailRequest {
+        token_hash: String,
+    },
+    /// Sets the password and signs every session of the account out.
+    ResetPasswordRequest {
+        token_hash: String,
+        password: crate::auth::password::Password,
+    },
+    /// The account whose token was redeemed.
+    AccountTokenResponse(Result<Uuid, AccountError>),
+    /// Creates an API key for `caller`, limited to `scopes` or to the caller's role when `None`.
+    /// Only the hash of the key is sent.
+    CreateApiKeyRequest {
+        caller: TokenClaims,
+        name: String,
+        scopes: Option<PermissionSet>,
+        expires_in_days: Option<i64>,
+        prefix: String,
+        key_hash: String,
+    },
+    ApiKeyResponse(Result<ApiKeyInfo, AccountError>),
+    ApiKeysRequest {
+        account_id: Uuid,
+    },
+    ApiKeysResponse(Result<Vec<ApiKeyInfo>, AccountError>),
+    /// Revokes the key and closes the connections using it.
+    RevokeApiKeyRequest {
+        caller: TokenClaims,
+        key_id: Uuid,
+    },
+    RevokeApiKeyResponse(Result<(), AccountError>),
+    /// Stores a WebSocket connection ticket for `claims`, only its hash is sent.
+    ConnectionTicketRequest {
+        claims: TokenClaims,
+        ticket_hash: String,
+    },
+    ConnectionTicketResponse(Result<i64, String>),
+    RedeemTicketRequest {
+        ticket_hash: String,
+    },
+    /// `None` when the ticket is unknown, expired or already used.
+    RedeemTicketResponse(Option<TokenClaims>),
 
     BuildRequest {
         inventory_id: Uuid,
         blueprint_slug: String,
     },
-    BuildResponse(Result<Uuid, String>),
+    BuildResponse(Result<InventoryBuilding, String>),
+
+    BuildQueueRequest {
+        inventory_id: Uuid,
+    },
+    ReorderBuildQueueRequest {
+        inventory_id: Uuid,
+        building_id: Uuid,
+        position: i32,
+    },
+    RemoveFromBuildQueueRequest {
+        inventory_id: Uuid,
+        building_id: Uuid,
+    },
+    BuildQueueResponse(Result<Vec<InventoryBuilding>, String>),
+
+    BuildingCommandRequest {
+        inventory_id: Uuid,
+        building_id: Uuid,
+        command: BuildingCommand,
+    },
+    BuildingCommandResponse(Result<BuildingCommandOutcome, String>),
+
+    BlueprintsRequest {
+        inventory_id: Uuid,
+    },
+    BlueprintsResponse(Result<Vec<BlueprintAvailability>, String>),
+
+    ResourcesRequest {
+        inventory_id: Uuid,
+    },
+    ResourcesResponse(Result<Vec<ResourceStock>, String>),
+
+    ProductionReport(ProductionReport),
+    UpkeepReport(UpkeepReport),
+
+    PoliciesRequest {
+        inventory_id: Uuid,
+    },
+    CreatePolicyRequest {
+        inventory_id: Uuid,
+        name: String,
+        rule: Value,
+        interval_ticks: i32,
+    },
+    DeletePolicyRequest {
+        inventory_id: Uuid,
+        policy_id: Uuid,
+    },
+    PoliciesResponse(Result<Vec<InventoryPolicy>, String>),
+    PolicyDryRunRequest {
+        inventory_id: Uuid,
+        policy_id: Option<Uuid>,
+    },
+    PolicyReport(Result<Vec<PolicyOutcome>, String>),
 
     DebugMessage(String),
 
@@ -42,7 +199,24 @@ pub enum MessageBody {
     Tick {
         seq: u64,
         timestamp: chrono::DateTime<chrono::Utc>,
+        phase: TickPhase,
     },
+    /// Registers a tick participant for the given phases, an empty list unregisters it.
+    TickRegistration {
+        participant: Uuid,
+        phases: Vec<TickPhase>,
+    },
+    TickAck {
+        participant: Uuid,
+        seq: u64,
+        phase: TickPhase,
+    },
+    TickOverrun(TickOverrun),
+    CalendarEvent(CalendarEvent),
+    TickerControl(TickerCommand),
+    TickerStatus(Result<TickerState, String>),
+    SchedulerStatusRequest,
+    SchedulerStatus(Result<Vec<JobRecord>, String>),
     Stop,
     Empty,
 }
@@ -73,6 +247,7 @@ impl MessageBody {
                 Ok(Self::AuthenticationRequest {
                     user: user.into(),
                     password: password.into(),
+                    address: None,
                 })
             }
             "build" => {
@@ -122,7 +297,7 @@ impl Message {
             body,
             topic,
             is_request,
-            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+            timestamp: crate::clock::now().timestamp_millis() as u64,
         }
     }
 
@@ -142,8 +317,85 @@ impl Message {
             MessageBody::AuthenticationResponse(_) => {
                 "MessageBody::AuthenticationResponse".to_string()
             }
+            MessageBody::RefreshRequest { .. } => "MessageBody::RefreshRequest".to_string(),
+            MessageBody::RefreshResponse(_) => "MessageBody::RefreshResponse".to_string(),
+            MessageBody::RegistrationRequest(_) => "MessageBody::RegistrationRequest".to_string(),
+            MessageBody::RegistrationResponse(_) => "MessageBody::RegistrationResponse".to_string(),
+            MessageBody::SessionsRequest { .. } => "MessageBody::SessionsRequest".to_string(),
+            MessageBody::SessionsResponse(_) => "MessageBody::SessionsResponse".to_string(),
+            MessageBody::LogoutRequest { .. } => "MessageBody::LogoutRequest".to_string(),
+            MessageBody::LogoutResponse(_) => "MessageBody::LogoutResponse".to_string(),
+            MessageBody::SessionRevoked { .. } => "MessageBody::SessionRevoked".to_string(),
+            MessageBody::AccountStateRequest { .. } => {
+                "MessageBody::AccountStateRequest".to_string()
+            }
+            MessageBody::AccountRoleRequest { .. } => "MessageBody::AccountRoleRequest".to_string(),
+            MessageBody::AccountUpdateResponse(_) => {
+                "MessageBody::AccountUpdateResponse".to_string()
+            }
+            MessageBody::LockoutsRequest => "MessageBody::LockoutsRequest".to_string(),
+            MessageBody::LockoutsResponse(_) => "MessageBody::LockoutsResponse".to_string(),
+            MessageBody::AccountMailRequest { .. } => "MessageBody::AccountMailRequest".to_string(),
+            MessageBody::AccountMailResponse(_) => "MessageBody::AccountMailResponse".to_string(),
+            MessageBody::VerifyEmailRequest { .. } => "MessageBody::VerifyEmailRequest".to_string(),
+            MessageBody::ResetPasswordRequest { .. } => {
+                "MessageBody::ResetPasswordRequest".to_string()
+            }
+            MessageBody::AccountTokenResponse(_) => "MessageBody::AccountTokenResponse".to_string(),
+            MessageBody::CreateApiKeyRequest { .. } => {
+                "MessageBody::CreateApiKeyRequest".to_string()
+            }
+            MessageBody::ApiKeyResponse(_) => "MessageBody::ApiKeyResponse".to_string(),
+            MessageBody::ApiKeysRequest { .. } => "MessageBody::ApiKeysRequest".to_string(),
+            MessageBody::ApiKeysResponse(_) => "MessageBody::ApiKeysResponse".to_string(),
+            MessageBody::RevokeApiKeyRequest { .. } => {
+                "MessageBody::RevokeApiKeyRequest".to_string()
+            }
+            MessageBody::RevokeApiKeyResponse(_) => "MessageBody::RevokeApiKeyResponse".to_string(),
+            MessageBody::ConnectionTicketRequest { .. } => {
+                "MessageBody::ConnectionTicketRequest".to_string()
+            }
+            MessageBody::ConnectionTicketResponse(_) => {
+                "MessageBody::ConnectionTicketResponse".to_string()
+            }
+            MessageBody::RedeemTicketRequest { .. } => {
+                "MessageBody::RedeemTicketRequest".to_string()
+            }
+            MessageBody::RedeemTicketResponse(_) => "MessageBody::RedeemTicketResponse".to_string(),
             MessageBody::BuildRequest { .. } => "MessageBody::BuildRequest".to_string(),
             MessageBody::BuildResponse(_) => "MessageBody::BuildResponse".to_string(),
+            MessageBody::BuildQueueRequest { .. } => "MessageBody::BuildQueueRequest".to_string(),
+            MessageBody::ReorderBuildQueueRequest { .. } => {
+                "MessageBody::ReorderBuildQueueRequest".to_string()
+            }
+            MessageBody::RemoveFromBuildQueueRequest { .. } => {
+                "MessageBody::RemoveFromBuildQueueRequest".to_string()
+            }
+            MessageBody::BuildQueueResponse(_) => "MessageBody::BuildQueueResponse".to_string(),
+            MessageBody::BuildingCommandRequest { .. } => {
+                "MessageBody::BuildingCommandRequest".to_string()
+            }
+            MessageBody::BuildingCommandResponse(_) => {
+                "MessageBody::BuildingCommandResponse".to_string()
+            }
+            MessageBody::BlueprintsRequest { .. } => "MessageBody::BlueprintsRequest".to_string(),
+            MessageBody::BlueprintsResponse(_) => "MessageBody::BlueprintsResponse".to_string(),
+            MessageBody::ResourcesRequest { .. } => "MessageBody::ResourcesRequest".to_string(),
+            MessageBody::ResourcesResponse(_) => "MessageBody::ResourcesResponse".to_string(),
+            MessageBody::ProductionReport(_) => "MessageBody::ProductionReport".to_string(),
+            MessageBody::UpkeepReport(_) => "MessageBody::UpkeepReport".to_string(),
+            MessageBody::PoliciesRequest { .. } => "MessageBody::PoliciesRequest".to_string(),
+            MessageBody::CreatePolicyRequest { .. } => {
+                "MessageBody::CreatePolicyRequest".to_string()
+            }
+            MessageBody::DeletePolicyRequest { .. } => {
+                "MessageBody::DeletePolicyRequest".to_string()
+            }
+            MessageBody::PoliciesResponse(_) => "MessageBody::PoliciesResponse".to_string(),
+            MessageBody::PolicyDryRunRequest { .. } => {
+                "MessageBody::PolicyDryRunRequest".to_string()
+            }
+            MessageBody::PolicyReport(_) => "MessageBody::PolicyReport".to_string(),
             MessageBody::DebugMessage(_) => "MessageBody::DebugMessage".to_string(),
             MessageBody::PersistenceQueryRequest(_) => {
                 "MessageBody::PersistenceQueryRequest".to_string()
@@ -152,6 +404,16 @@ impl Message {
                 "MessageBody::PersistenceQueryResponse".to_string()
             }
             MessageBody::Tick { .. } => "MessageBody::Tick".to_string(),
+            MessageBody::TickRegistration { .. } => "MessageBody::TickRegistration".to_string(),
+            MessageBody::TickAck { .. } => "MessageBody::TickAck".to_string(),
+            MessageBody::TickOverrun(_) => "MessageBody::TickOverrun".to_string(),
+            MessageBody::CalendarEvent(_) => "MessageBody::CalendarEvent".to_string(),
+            MessageBody::TickerControl(_) => "MessageBody::TickerControl".to_string(),
+            MessageBody::TickerStatus(_) => "MessageBody::TickerStatus".to_string(),
+            MessageBody::SchedulerStatusRequest => {
+                "MessageBody::SchedulerStatusRequest".to_string()
+            }
+            MessageBody::SchedulerStatus(_) => "MessageBody::SchedulerStatus".to_string(),
             MessageBody::Stop => "MessageBody::Stop".to_string(),
             MessageBody::Empty => "MessageBody::Empty".to_string(),
         }
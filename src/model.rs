@@ -1,6 +1,12 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
 use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::auth::permission::PermissionSet;
+use crate::error::AccountError;
+
 #[derive(Queryable, Selectable)]
 #[diesel(table_name = crate::schema::accounts)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -12,27 +18,131 @@ pub struct Account {
     pub state: String,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub role: String,
+    /// Why the account is suspended or banned, shown to its owner on login.
+    pub state_reason: Option<String>,
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct AccountRequest {
     pub username: String,
     pub password: String,
     pub email: String,
 }
 
+impl std::fmt::Debug for AccountRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountRequest")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AccountRequest {
+    /// Trims the username, lowercases the email and checks both against the column limits
+    /// along with the password length.
+    pub fn normalize(self) -> Result<Self, AccountError> {
+        let username = self.username.trim().to_string();
+        let email = self.email.trim().to_lowercase();
+
+        if !(3..=50).contains(&username.chars().count()) {
+            return Err(AccountError::InvalidUsername(
+                "must be between 3 and 50 characters".into(),
+            ));
+        }
+        if !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            return Err(AccountError::InvalidUsername(
+                "may only contain letters, digits, '_', '-' and '.'".into(),
+            ));
+        }
+
+        let valid_email = email.len() <= 100
+            && !email.contains(char::is_whitespace)
+            && email.split_once('@').is_some_and(|(local, domain)| {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.split('.').all(|label| !label.is_empty())
+                    && domain.contains('.')
+            });
+        if !valid_email {
+            return Err(AccountError::InvalidEmail(
+                "must be an address like name@example.com".into(),
+            ));
+        }
+
+        validate_password(&self.password)?;
+
+        Ok(AccountRequest {
+            username,
+            password: self.password,
+            email,
+        })
+    }
+}
+
+/// The password rules of registration, also applied to password resets.
+pub fn validate_password(password: &str) -> Result<(), AccountError> {
+    if !(8..=128).contains(&password.chars().count()) {
+        return Err(AccountError::InvalidPassword(
+            "must be between 8 and 128 characters".into(),
+        ));
+    }
+
+    Ok(())
+}
+
 #[derive(Queryable, Selectable)]
 #[diesel(table_name = crate::schema::account_sessions)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct AccountSession {
     pub id: Uuid,
     pub account_id: Uuid,
+    /// SHA-256 of the refresh token issued with this row.
     pub session_token: String,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
     pub expires_at: Option<chrono::NaiveDateTime>,
+    /// The login this row descends from, every refresh adds a row to the family.
+    pub family_id: Uuid,
+    /// Set once the refresh token was exchanged, presenting it again revokes the family.
+    pub rotated_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The current row of a session family without its token hash, as listed to the account owner.
+/// Its id is the family id, which access tokens carry in their `jti` claim.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::schema::account_sessions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SessionInfo {
+    #[diesel(column_name = family_id)]
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    /// When the session was last refreshed.
+    pub updated_at: Option<chrono::NaiveDateTime>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// An API key as listed to its owner, without its hash.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiKeyInfo {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub name: String,
+    /// The part of the key after `adk_`, enough to tell keys apart.
+    pub prefix: String,
+    pub scopes: PermissionSet,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
 }
 
-#[derive(Queryable, Selectable, Insertable)]
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = crate::schema::inventories_x_buildings)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct InventoryBuilding {
@@ -43,4 +153,882 @@ pub struct InventoryBuilding {
     pub progress: i32,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+    pub queue_position: Option<i32>,
+    pub level: i32,
+    /// Maintenance state from 0 to [`MAX_CONDITION`], scaling production output.
+    pub condition: i32,
+}
+
+pub const MAX_CONDITION: i32 = 100;
+
+impl InventoryBuilding {
+    /// The level a building currently produces at, if any. An upgrading building keeps producing
+    /// at its previous level unless its blueprint says otherwise.
+    pub fn producing_level(&self, properties: &BlueprintProperties) -> Option<i32> {
+        match self.status.as_str() {
+            "completed" => Some(self.level),
+            _ if self.level > 1 && properties.produces_while_upgrading => Some(self.level - 1),
+            _ => None,
+        }
+    }
+
+    /// The highest level this building has finished, regardless of what it is doing now.
+    pub fn built_level(&self) -> Option<i32> {
+        match self.status.as_str() {
+            "completed" => Some(self.level),
+            _ if self.level > 1 => Some(self.level - 1),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl InventoryBuilding {
+    /// A building in good condition for tests that don't touch the database.
+    pub fn fixture(blueprint_slug: &str, status: &str, level: i32) -> Self {
+        InventoryBuilding {
+            id: Uuid::new_v4(),
+            inventory_id: Uuid::new_v4(),
+            blueprint_slug: blueprint_slug.into(),
+            status: status.into(),
+            progress: 0,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            queue_position: None,
+            level,
+            condition: MAX_CONDITION,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::blueprints)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Blueprint {
+    pub slug: String,
+    pub name: String,
+    pub properties: serde_json::Value,
+}
+
+/// Typed view of the `blueprints.properties` column. Unknown keys are ignored so the JSON can
+/// carry data for features that don't read it yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlueprintProperties {
+    /// Buildings of blueprints without it never complete.
+    #[serde(default)]
+    pub ticks_required: Option<i32>,
+    /// Resources withdrawn from the inventory when construction is requested.
+    #[serde(default)]
+    pub cost: HashMap<String, i32>,
+    /// Share of the cost returned when a started building is cancelled.
+    #[serde(default = "default_refund_ratio")]
+    pub refund_ratio: f64,
+    /// Upgrade levels keyed by level number, level 1 being the base properties above.
+    #[serde(default)]
+    pub levels: BTreeMap<i32, LevelProperties>,
+    #[serde(default = "default_produces_while_upgrading")]
+    pub produces_while_upgrading: bool,
+    /// Requirements the inventory must meet before construction can be requested.
+    #[serde(default)]
+    pub requires: Vec<Requirement>,
+    /// Storage capacity added per resource once built, scaled by the level output multiplier.
+    #[serde(default)]
+    pub storage: HashMap<String, i32>,
+    /// Resources consumed every tick while producing.
+    #[serde(default)]
+    pub inputs: HashMap<String, i32>,
+    /// Resources produced every tick, scaled by the level output multiplier.
+    #[serde(default)]
+    pub outputs: HashMap<String, i32>,
+    #[serde(default)]
+    pub upkeep: Option<Upkeep>,
+    /// Cost of repairing a building from zero to full condition, charged proportionally.
+    #[serde(default)]
+    pub repair_cost: HashMap<String, i32>,
+    /// Output multipliers per season, seasons that aren't listed produce normally.
+    #[serde(default)]
+    pub seasonal: HashMap<Season, f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Upkeep {
+    #[serde(default)]
+    pub resources: HashMap<String, i32>,
+    /// Upkeep is due every `interval` ticks.
+    #[serde(default = "default_upkeep_interval")]
+    pub interval: u64,
+    /// Condition lost every time the upkeep can't be paid.
+    #[serde(default = "default_upkeep_decay")]
+    pub decay: i32,
+}
+
+fn default_upkeep_interval() -> u64 {
+    1
+}
+
+fn default_upkeep_decay() -> i32 {
+    10
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Requirement {
+    Building {
+        blueprint: String,
+        #[serde(default = "default_required_level")]
+        level: i32,
+    },
+    Tech {
+        slug: String,
+    },
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Requirement::Building { blueprint, level } => {
+                write!(f, "{} at level {}", blueprint, level)
+            }
+            Requirement::Tech { slug } => write!(f, "{} research", slug),
+        }
+    }
+}
+
+fn default_required_level() -> i32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelProperties {
+    #[serde(default)]
+    pub cost: HashMap<String, i32>,
+    /// Falls back to the base `ticks_required` when omitted.
+    pub ticks_required: Option<i32>,
+    #[serde(default = "default_output_multiplier")]
+    pub output_multiplier: f64,
+}
+
+fn default_refund_ratio() -> f64 {
+    0.5
+}
+
+fn default_produces_while_upgrading() -> bool {
+    true
+}
+
+fn default_output_multiplier() -> f64 {
+    1.0
+}
+
+impl Default for BlueprintProperties {
+    fn default() -> Self {
+        Self {
+            ticks_required: None,
+            cost: HashMap::new(),
+            refund_ratio: default_refund_ratio(),
+            levels: BTreeMap::new(),
+            produces_while_upgrading: default_produces_while_upgrading(),
+            requires: Vec::new(),
+            storage: HashMap::new(),
+            inputs: HashMap::new(),
+            outputs: HashMap::new(),
+            upkeep: None,
+            repair_cost: HashMap::new(),
+            seasonal: HashMap::new(),
+        }
+    }
+}
+
+impl BlueprintProperties {
+    pub fn has_level(&self, level: i32) -> bool {
+        level == 1 || self.levels.contains_key(&level)
+    }
+
+    pub fn max_level(&self) -> i32 {
+        self.levels.keys().copied().max().unwrap_or(1).max(1)
+    }
+
+    pub fn cost_for_level(&self, level: i32) -> HashMap<String, i32> {
+        match self.levels.get(&level) {
+            Some(props) if level > 1 => props.cost.clone(),
+            _ => self.cost.clone(),
+        }
+    }
+
+    pub fn ticks_for_level(&self, level: i32) -> Option<i32> {
+        self.levels
+            .get(&level)
+            .filter(|_| level > 1)
+            .and_then(|props| props.ticks_required)
+            .or(self.ticks_required)
+    }
+
+    /// The share of `repair_cost` needed to bring a building back to full condition.
+    pub fn repair_cost_for(&self, condition: i32) -> HashMap<String, i32> {
+        let missing = f64::from((MAX_CONDITION - condition).clamp(0, MAX_CONDITION));
+
+        self.repair_cost
+            .iter()
+            .map(|(resource, amount)| {
+                let amount = (f64::from(*amount) * missing / f64::from(MAX_CONDITION)).ceil();
+                (resource.clone(), amount as i32)
+            })
+            .filter(|(_, amount)| *amount > 0)
+            .collect()
+    }
+
+    pub fn seasonal_multiplier(&self, season: Season) -> f64 {
+        self.seasonal.get(&season).copied().unwrap_or(1.0)
+    }
+
+    pub fn output_multiplier(&self, level: i32) -> f64 {
+        self.levels
+            .get(&level)
+            .filter(|_| level > 1)
+            .map(|props| props.output_multiplier)
+            .unwrap_or(1.0)
+    }
+
+    /// Returns the requirements not yet met by an inventory's buildings and researched techs.
+    pub fn missing_requirements(
+        &self,
+        buildings: &[InventoryBuilding],
+        techs: &HashSet<String>,
+    ) -> Vec<Requirement> {
+        self.requires
+            .iter()
+            .filter(|requirement| match requirement {
+                Requirement::Building { blueprint, level } => !buildings.iter().any(|b| {
+                    b.blueprint_slug == *blueprint && b.built_level().is_some_and(|l| l >= *level)
+                }),
+                Requirement::Tech { slug } => !techs.contains(slug),
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlueprintStatus {
+    Available,
+    Locked,
+    Built,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlueprintAvailability {
+    pub slug: String,
+    pub name: String,
+    pub status: BlueprintStatus,
+    pub missing: Vec<Requirement>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::resources)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Resource {
+    pub slug: String,
+    pub name: String,
+    pub base_capacity: i32,
+    /// Resource that overflowing quantities are converted into, discarded when `None`.
+    pub overflow_resource: Option<String>,
+    pub overflow_rate: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResourceStock {
+    pub resource: String,
+    pub quantity: i32,
+    pub capacity: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum StallReason {
+    MissingInput { resource: String },
+    StorageFull { resource: String },
+    Broken,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StalledBuilding {
+    pub building_id: Uuid,
+    pub blueprint_slug: String,
+    #[serde(flatten)]
+    pub reason: StallReason,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProductionReport {
+    pub consumed: HashMap<String, i32>,
+    pub produced: HashMap<String, i32>,
+    pub stalled: Vec<StalledBuilding>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UpkeepReport {
+    pub paid: HashMap<String, i32>,
+    /// Buildings whose upkeep couldn't be paid, with their condition after decay.
+    pub degraded: HashMap<Uuid, i32>,
+}
+
+/// Tick interval used for policies created without one, matching the column default.
+pub const DEFAULT_POLICY_INTERVAL: i32 = 10;
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::inventory_policies)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InventoryPolicy {
+    pub id: Uuid,
+    pub inventory_id: Uuid,
+    pub name: String,
+    /// A serialized [`PolicyRule`].
+    pub rule: serde_json::Value,
+    /// The policy is evaluated every `interval_ticks` ticks.
+    pub interval_ticks: i32,
+    pub enabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// A declarative automation rule: when every condition holds the action is carried out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PolicyRule {
+    #[serde(default)]
+    pub when: Vec<PolicyCondition>,
+    pub then: PolicyAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PolicyCondition {
+    ResourceBelow { resource: String, amount: i32 },
+    ResourceAbove { resource: String, amount: i32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Queue a new building unless one of the same blueprint is already pending.
+    Build { blueprint: String },
+    /// Upgrade the lowest level finished building of the blueprint.
+    UpgradeLowest { blueprint: String },
+}
+
+/// What a policy evaluation decided to do, resolved against the current inventory state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlannedAction {
+    Build {
+        blueprint: String,
+    },
+    Upgrade {
+        building_id: Uuid,
+        blueprint: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PolicyOutcome {
+    pub policy_id: Uuid,
+    pub name: String,
+    pub action: Option<PlannedAction>,
+    pub executed: bool,
+    /// Why nothing was planned, or why carrying out the plan failed.
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildingCommand {
+    Cancel,
+    Pause,
+    Resume,
+    Demolish,
+    Upgrade,
+    Repair,
+}
+
+impl std::fmt::Display for BuildingCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let command = match self {
+            BuildingCommand::Cancel => "cancel",
+            BuildingCommand::Pause => "pause",
+            BuildingCommand::Resume => "resume",
+            BuildingCommand::Demolish => "demolish",
+            BuildingCommand::Upgrade => "upgrade",
+            BuildingCommand::Repair => "repair",
+        };
+        write!(f, "{}", command)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BuildingCommandOutcome {
+    pub building_id: Uuid,
+    pub command: BuildingCommand,
+    /// The building after the command was applied, `None` when it was removed.
+    pub building: Option<InventoryBuilding>,
+    pub refund: HashMap<String, i32>,
+}
+
+/// How the ticker treats the ticks missed while the server was down.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpMode {
+    /// Replay the missed ticks in batches before resuming normal pace.
+    CatchUp,
+    /// Resume from the persisted seq as if no time had passed.
+    Skip,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::world_settings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WorldSettings {
+    pub id: i32,
+    pub tick_seq: i64,
+    pub last_tick_at: Option<chrono::NaiveDateTime>,
+    pub catch_up_mode: String,
+    pub catch_up_batch: i32,
+    pub max_catch_up_ticks: i64,
+    pub ticks_per_day: i32,
+    pub days_per_season: i32,
+}
+
+impl WorldSettings {
+    pub fn mode(&self) -> CatchUpMode {
+        match self.catch_up_mode.as_str() {
+            "skip" => CatchUpMode::Skip,
+            _ => CatchUpMode::CatchUp,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [
+        Season::Spring,
+        Season::Summer,
+        Season::Autumn,
+        Season::Winter,
+    ];
+}
+
+/// A point in game time. Years and days are counted from 1.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GameDate {
+    pub year: u64,
+    pub season: Season,
+    /// Day of the season.
+    pub day: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CalendarEvent {
+    DayStarted(GameDate),
+    SeasonChanged {
+        from: Season,
+        to: Season,
+        date: GameDate,
+    },
+}
+
+/// Maps tick sequence numbers to game dates. Day one of spring, year one starts at seq 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calendar {
+    pub ticks_per_day: u64,
+    pub days_per_season: u64,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Self {
+            ticks_per_day: 60,
+            days_per_season: 10,
+        }
+    }
+}
+
+impl From<&WorldSettings> for Calendar {
+    fn from(settings: &WorldSettings) -> Self {
+        Self {
+            ticks_per_day: settings.ticks_per_day.max(1) as u64,
+            days_per_season: settings.days_per_season.max(1) as u64,
+        }
+    }
+}
+
+impl Calendar {
+    pub fn date(&self, seq: u64) -> GameDate {
+        let day = seq / self.ticks_per_day;
+        let season = day / self.days_per_season;
+        let seasons = Season::ALL.len() as u64;
+
+        GameDate {
+            year: season / seasons + 1,
+            season: Season::ALL[(season % seasons) as usize],
+            day: day % self.days_per_season + 1,
+        }
+    }
+
+    /// The events tick `seq` triggers, a day starts on its first tick.
+    pub fn events(&self, seq: u64) -> Vec<CalendarEvent> {
+        if seq == 0 || !seq.is_multiple_of(self.ticks_per_day) {
+            return Vec::new();
+        }
+
+        let date = self.date(seq);
+        let mut events = vec![CalendarEvent::DayStarted(date)];
+
+        if date.day == 1 {
+            let from = self.date(seq - 1).season;
+            events.push(CalendarEvent::SeasonChanged {
+                from,
+                to: date.season,
+                date,
+            });
+        }
+
+        events
+    }
+}
+
+/// The ordered stages of a single tick. The ticker waits for every participant registered for a
+/// phase to acknowledge it before moving on to the next one.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TickPhase {
+    PreTick,
+    Production,
+    Consumption,
+    Resolution,
+    PostTick,
+}
+
+impl TickPhase {
+    pub const ALL: [TickPhase; 5] = [
+        TickPhase::PreTick,
+        TickPhase::Production,
+        TickPhase::Consumption,
+        TickPhase::Resolution,
+        TickPhase::PostTick,
+    ];
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimedOutPhase {
+    pub participant: Uuid,
+    pub phase: TickPhase,
+}
+
+/// Published on `out:ticker` when a tick took longer than the interval or a phase timed out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TickOverrun {
+    pub seq: u64,
+    pub elapsed_ms: u64,
+    pub interval_ms: u64,
+    pub timed_out: Vec<TimedOutPhase>,
+}
+
+/// Control messages accepted by the ticker on its `in:ticker` topic.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum TickerCommand {
+    Pause,
+    Resume,
+    SetInterval {
+        millis: u64,
+    },
+    /// How long to wait for participants to acknowledge a phase before moving on.
+    SetPhaseTimeout {
+        millis: u64,
+    },
+    /// Sends `count` ticks right away, whether or not the ticker is paused.
+    Step {
+        count: u64,
+    },
+    /// Sends `count` ticks as fast as subscribers allow, then returns to the regular cadence.
+    FastForward {
+        count: u64,
+    },
+    Status,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TickerState {
+    pub seq: u64,
+    pub paused: bool,
+    pub interval_ms: u64,
+    pub phase_timeout_ms: u64,
+    pub fast_forward_remaining: u64,
+    pub participants: usize,
+    pub overruns: u64,
+}
+
+/// Maintenance work the scheduler can run through the persistence actor.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// Deletes account sessions whose `expires_at` has passed.
+    PurgeExpiredSessions,
+    /// Clears leftover queue positions from buildings that are no longer queued.
+    CompactBuildings,
+    DailyStats,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MaintenanceReport {
+    pub task: MaintenanceTask,
+    pub affected: usize,
+    pub summary: String,
+}
+
+/// When a scheduled job runs, either every `ticks` ticks or on the minutes matching a five
+/// field cron expression evaluated against the game clock.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobSchedule {
+    EveryTicks { ticks: u64 },
+    Cron { expression: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobResult {
+    pub success: bool,
+    pub message: String,
+    pub finished_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// State of a scheduled job as reported by the scheduler's status query.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobRecord {
+    pub name: String,
+    pub task: MaintenanceTask,
+    pub schedule: JobSchedule,
+    pub running: bool,
+    pub runs: u64,
+    /// Times the job was due while its previous run was still going.
+    pub skipped: u64,
+    pub last_started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_result: Option<JobResult>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(username: &str, email: &str, password: &str) -> AccountRequest {
+        AccountRequest {
+            username: username.into(),
+            password: password.into(),
+            email: email.into(),
+        }
+    }
+
+    #[test]
+    fn test_account_request_normalize() {
+        let normalized = account(" brvy ", "No@Email.com ", "correct horse")
+            .normalize()
+            .unwrap();
+        assert_eq!(normalized.username, "brvy");
+        assert_eq!(normalized.email, "no@email.com");
+
+        assert!(matches!(
+            account("ab", "a@b.c", "correct horse").normalize(),
+            Err(AccountError::InvalidUsername(_))
+        ));
+        assert!(matches!(
+            account("bob smith", "a@b.c", "correct horse").normalize(),
+            Err(AccountError::InvalidUsername(_))
+        ));
+        for email in ["bob", "@b.c", "a@b", "a@@b.c", "a@b..c", "a b@c.d"] {
+            assert!(matches!(
+                account("bob", email, "correct horse").normalize(),
+                Err(AccountError::InvalidEmail(_))
+            ));
+        }
+        assert!(matches!(
+            account("bob", "a@b.c", "short").normalize(),
+            Err(AccountError::InvalidPassword(_))
+        ));
+    }
+
+    #[test]
+    fn test_calendar_dates_and_events() {
+        let calendar = Calendar {
+            ticks_per_day: 10,
+            days_per_season: 3,
+        };
+
+        assert_eq!(
+            calendar.date(0),
+            GameDate {
+                year: 1,
+                season: Season::Spring,
+                day: 1
+            }
+        );
+        assert_eq!(
+            calendar.date(29),
+            GameDate {
+                year: 1,
+                season: Season::Spring,
+                day: 3
+            }
+        );
+        assert_eq!(
+            calendar.date(120),
+            GameDate {
+                year: 2,
+                season: Season::Spring,
+                day: 1
+            }
+        );
+
+        assert!(calendar.events(15).is_empty());
+        assert_eq!(calendar.events(10).len(), 1);
+
+        let events = calendar.events(60);
+        assert_eq!(
+            events[1],
+            CalendarEvent::SeasonChanged {
+                from: Season::Summer,
+                to: Season::Autumn,
+                date: calendar.date(60),
+            }
+        );
+    }
+
+    #[test]
+    fn test_seasonal_multiplier() {
+        let properties: BlueprintProperties = serde_json::from_value(serde_json::json!({
+            "outputs": { "grain": 4 },
+            "seasonal": { "winter": 0.25, "summer": 1.5 }
+        }))
+        .unwrap();
+
+        assert_eq!(properties.seasonal_multiplier(Season::Winter), 0.25);
+        assert_eq!(properties.seasonal_multiplier(Season::Spring), 1.0);
+    }
+
+    #[test]
+    fn test_blueprint_level_properties() {
+        let properties: BlueprintProperties = serde_json::from_value(serde_json::json!({
+            "ticks_required": 10,
+            "cost": { "wood": 50 },
+            "levels": {
+                "2": { "cost": { "wood": 100, "stone": 20 }, "ticks_required": 25, "output_multiplier": 1.5 },
+                "3": { "cost": { "wood": 200 } }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(properties.max_level(), 3);
+        assert!(properties.has_level(2));
+        assert!(!properties.has_level(4));
+
+        assert_eq!(properties.cost_for_level(1).get("wood"), Some(&50));
+        assert_eq!(properties.cost_for_level(2).get("stone"), Some(&20));
+
+        assert_eq!(properties.ticks_for_level(1), Some(10));
+        assert_eq!(properties.ticks_for_level(2), Some(25));
+        assert_eq!(properties.ticks_for_level(3), Some(10));
+
+        assert_eq!(properties.output_multiplier(1), 1.0);
+        assert_eq!(properties.output_multiplier(2), 1.5);
+        assert_eq!(properties.output_multiplier(3), 1.0);
+
+        // without a base ticks_required level 1 never completes
+        let properties: BlueprintProperties = serde_json::from_value(serde_json::json!({
+            "levels": { "2": { "ticks_required": 25 } }
+        }))
+        .unwrap();
+        assert_eq!(properties.ticks_for_level(1), None);
+        assert_eq!(properties.ticks_for_level(2), Some(25));
+    }
+
+    #[test]
+    fn test_missing_requirements() {
+        let properties: BlueprintProperties = serde_json::from_value(serde_json::json!({
+            "requires": [
+                { "kind": "building", "blueprint": "farm", "level": 2 },
+                { "kind": "building", "blueprint": "well" },
+                { "kind": "tech", "slug": "masonry" }
+            ]
+        }))
+        .unwrap();
+
+        let mut well = InventoryBuilding::fixture("farm", "completed", 1);
+        well.blueprint_slug = "well".into();
+        let buildings = vec![InventoryBuilding::fixture("farm", "completed", 1), well];
+        let techs = HashSet::from(["masonry".to_string()]);
+
+        assert_eq!(
+            properties.missing_requirements(&buildings, &techs),
+            vec![Requirement::Building {
+                blueprint: "farm".into(),
+                level: 2
+            }]
+        );
+
+        // An upgrade to level 3 in progress still counts as a finished level 2
+        let buildings = vec![InventoryBuilding::fixture("farm", "in_progress", 3)];
+        assert_eq!(
+            properties.missing_requirements(&buildings, &HashSet::new()),
+            vec![
+                Requirement::Building {
+                    blueprint: "well".into(),
+                    level: 1
+                },
+                Requirement::Tech {
+                    slug: "masonry".into()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_cost_scales_with_missing_condition() {
+        let properties: BlueprintProperties =
+            serde_json::from_value(serde_json::json!({ "repair_cost": { "stone": 50 } })).unwrap();
+
+        assert_eq!(properties.repair_cost_for(75).get("stone"), Some(&13));
+        assert_eq!(properties.repair_cost_for(0).get("stone"), Some(&50));
+        assert!(properties.repair_cost_for(MAX_CONDITION).is_empty());
+    }
+
+    #[test]
+    fn test_producing_level_while_upgrading() {
+        let mut properties = BlueprintProperties::default();
+
+        assert_eq!(
+            InventoryBuilding::fixture("farm", "completed", 2).producing_level(&properties),
+            Some(2)
+        );
+        assert_eq!(
+            InventoryBuilding::fixture("farm", "in_progress", 1).producing_level(&properties),
+            None
+        );
+        assert_eq!(
+            InventoryBuilding::fixture("farm", "in_progress", 3).producing_level(&properties),
+            Some(2)
+        );
+
+        properties.produces_while_upgrading = false;
+        assert_eq!(
+            InventoryBuilding::fixture("farm", "in_progress", 3).producing_level(&properties),
+            None
+        );
+    }
 }
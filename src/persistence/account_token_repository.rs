@@ -0,0 +1,152 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use crate::auth::password::hash_password;
+use crate::auth::verification::{MailRecipient, Recipient, TokenPurpose};
+use crate::error::AccountError;
+
+/// Stores a token for the recipient's account, replacing its unused tokens of the same purpose
+/// so only the latest mail works. Verification tokens aren't issued for verified addresses.
+pub async fn issue_token(
+    conn: &mut PgConnection,
+    recipient: &Recipient,
+    purpose: TokenPurpose,
+    hash: &str,
+) -> Result<MailRecipient, AccountError> {
+    use crate::schema::{account_tokens, accounts};
+
+    let query = accounts::table
+        .select((
+            accounts::id,
+            accounts::username,
+            accounts::email,
+            accounts::email_verified_at,
+        ))
+        .into_boxed();
+    let query = match recipient {
+        Recipient::Account(account) => query.filter(accounts::id.eq(*account)),
+        Recipient::Email(address) => {
+            query.filter(accounts::email.eq(address.trim().to_lowercase()))
+        }
+    };
+    let (account, username, email, verified_at) = query
+        .first::<(uuid::Uuid, String, String, Option<chrono::NaiveDateTime>)>(conn)
+        .optional()?
+        .ok_or(AccountError::NotFound)?;
+
+    if purpose == TokenPurpose::VerifyEmail && verified_at.is_some() {
+        return Err(AccountError::EmailAlreadyVerified);
+    }
+
+    // tokens expire in real time like sessions
+    let now = chrono::Utc::now().naive_utc();
+    conn.transaction(|conn| {
+        diesel::delete(
+            account_tokens::table
+                .filter(account_tokens::account_id.eq(account))
+                .filter(account_tokens::purpose.eq(purpose.as_str()))
+                .filter(account_tokens::used_at.is_null()),
+        )
+        .execute(conn)?;
+
+        diesel::insert_into(account_tokens::table)
+            .values((
+                account_tokens::account_id.eq(account),
+                account_tokens::purpose.eq(purpose.as_str()),
+                account_tokens::token_hash.eq(hash),
+                account_tokens::created_at.eq(now),
+                account_tokens::expires_at.eq(now + purpose.lifetime()),
+            ))
+            .execute(conn)
+    })?;
+
+    Ok(MailRecipient {
+        account_id: account,
+        username,
+        email,
+    })
+}
+
+/// Marks the address of the token's account as verified.
+pub async fn verify_email(conn: &mut PgConnection, hash: &str) -> Result<uuid::Uuid, AccountError> {
+    use crate::schema::accounts;
+
+    conn.transaction(|conn| {
+        let account = consume_token(conn, TokenPurpose::VerifyEmail, hash)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        diesel::update(accounts::table.find(account))
+            .set((
+                accounts::email_verified_at.eq(Some(now)),
+                accounts::updated_at.eq(Some(now)),
+            ))
+            .execute(conn)?;
+
+        Ok(account)
+    })
+}
+
+/// Sets the password of the token's account. Receiving the token proves the address too, so it
+/// is marked verified. Revoking the sessions is up to the caller.
+pub async fn reset_password(
+    conn: &mut PgConnection,
+    hash: &str,
+    password: &str,
+) -> Result<uuid::Uuid, AccountError> {
+    use crate::schema::{account_tokens, accounts};
+
+    let password = hash_password(password).map_err(|e| AccountError::Internal(e.to_string()))?;
+
+    conn.transaction(|conn| {
+        let account = consume_token(conn, TokenPurpose::ResetPassword, hash)?;
+        let now = chrono::Utc::now().naive_utc();
+
+        diesel::update(accounts::table.find(account))
+            .set((
+                accounts::password_hash.eq(&password),
+                accounts::updated_at.eq(Some(now)),
+            ))
+            .execute(conn)?;
+        diesel::update(
+            accounts::table
+                .find(account)
+                .filter(accounts::email_verified_at.is_null()),
+        )
+        .set(accounts::email_verified_at.eq(Some(now)))
+        .execute(conn)?;
+
+        diesel::delete(
+            account_tokens::table
+                .filter(account_tokens::account_id.eq(account))
+                .filter(account_tokens::purpose.eq(TokenPurpose::ResetPassword.as_str()))
+                .filter(account_tokens::used_at.is_null()),
+        )
+        .execute(conn)?;
+
+        Ok(account)
+    })
+}
+
+/// Uses the token up in a single statement, so concurrent requests can't both redeem it.
+fn consume_token(
+    conn: &mut PgConnection,
+    purpose: TokenPurpose,
+    hash: &str,
+) -> Result<uuid::Uuid, AccountError> {
+    use crate::schema::account_tokens::dsl;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    diesel::update(
+        dsl::account_tokens
+            .filter(dsl::token_hash.eq(hash))
+            .filter(dsl::purpose.eq(purpose.as_str()))
+            .filter(dsl::used_at.is_null())
+            .filter(dsl::expires_at.gt(now)),
+    )
+    .set(dsl::used_at.eq(Some(now)))
+    .returning(dsl::account_id)
+    .get_result::<uuid::Uuid>(conn)
+    .optional()?
+    .ok_or(AccountError::InvalidToken)
+}
@@ -0,0 +1,171 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use crate::auth::api_key::MAX_KEYS_PER_ACCOUNT;
+use crate::auth::permission::{Permission, PermissionSet};
+use crate::error::AccountError;
+use crate::model::ApiKeyInfo;
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::api_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+struct ApiKeyRow {
+    id: uuid::Uuid,
+    account_id: uuid::Uuid,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    created_at: chrono::NaiveDateTime,
+    expires_at: Option<chrono::NaiveDateTime>,
+    last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Writes of `last_used_at` are skipped when the key was used more recently than this.
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+/// Stores a new key, refusing it when the account already holds `MAX_KEYS_PER_ACCOUNT`
+/// usable ones.
+pub async fn create_key(
+    conn: &mut PgConnection,
+    account: uuid::Uuid,
+    key_name: &str,
+    key_scopes: PermissionSet,
+    (key_prefix, hash): (&str, &str),
+    expires: Option<chrono::NaiveDateTime>,
+) -> Result<ApiKeyInfo, AccountError> {
+    use crate::schema::api_keys::dsl::*;
+
+    // keys expire in real time like sessions
+    let now = chrono::Utc::now().naive_utc();
+
+    conn.transaction(|conn| {
+        let active = api_keys
+            .filter(account_id.eq(account))
+            .filter(revoked_at.is_null())
+            .filter(expires_at.is_null().or(expires_at.gt(now)))
+            .count()
+            .get_result::<i64>(conn)?;
+        if active >= MAX_KEYS_PER_ACCOUNT {
+            return Err(AccountError::Forbidden(format!(
+                "an account can hold at most {} API keys",
+                MAX_KEYS_PER_ACCOUNT
+            )));
+        }
+
+        let row = diesel::insert_into(api_keys)
+            .values((
+                account_id.eq(account),
+                name.eq(key_name),
+                prefix.eq(key_prefix),
+                key_hash.eq(hash),
+                scopes.eq(scope_names(key_scopes)),
+                created_at.eq(now),
+                expires_at.eq(expires),
+            ))
+            .returning(ApiKeyRow::as_returning())
+            .get_result(conn)?;
+
+        key_info(row).map_err(AccountError::from)
+    })
+}
+
+/// The account's keys that weren't revoked, expired ones included so their owner sees why a
+/// bot stopped working.
+pub async fn get_keys(
+    conn: &mut PgConnection,
+    account: uuid::Uuid,
+) -> Result<Vec<ApiKeyInfo>, diesel::result::Error> {
+    use crate::schema::api_keys::dsl::*;
+
+    api_keys
+        .filter(account_id.eq(account))
+        .filter(revoked_at.is_null())
+        .order(created_at.asc())
+        .select(ApiKeyRow::as_select())
+        .load(conn)?
+        .into_iter()
+        .map(key_info)
+        .collect()
+}
+
+pub async fn revoke_key(
+    conn: &mut PgConnection,
+    account: uuid::Uuid,
+    key: uuid::Uuid,
+) -> Result<(), AccountError> {
+    use crate::schema::api_keys::dsl::*;
+
+    let revoked = diesel::update(
+        api_keys
+            .find(key)
+            .filter(account_id.eq(account))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(Some(chrono::Utc::now().naive_utc())))
+    .execute(conn)?;
+
+    match revoked {
+        0 => Err(AccountError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Looks up a usable key by its hash and records its use.
+pub async fn authenticate(
+    conn: &mut PgConnection,
+    hash: &str,
+) -> Result<Option<ApiKeyInfo>, diesel::result::Error> {
+    use crate::schema::api_keys::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let Some(row) = api_keys
+        .filter(key_hash.eq(hash))
+        .filter(revoked_at.is_null())
+        .filter(expires_at.is_null().or(expires_at.gt(now)))
+        .select(ApiKeyRow::as_select())
+        .first(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let key = key_info(row)?;
+
+    let stale = now - chrono::Duration::seconds(LAST_USED_RESOLUTION_SECONDS);
+    diesel::update(
+        api_keys
+            .find(key.id)
+            .filter(last_used_at.is_null().or(last_used_at.lt(stale))),
+    )
+    .set(last_used_at.eq(Some(now)))
+    .execute(conn)?;
+
+    Ok(Some(key))
+}
+
+fn scope_names(scope: PermissionSet) -> Vec<String> {
+    scope
+        .iter()
+        .map(|permission| permission.as_str().to_string())
+        .collect()
+}
+
+fn key_info(row: ApiKeyRow) -> Result<ApiKeyInfo, diesel::result::Error> {
+    let scopes = row
+        .scopes
+        .iter()
+        .map(|scope| scope.parse::<Permission>())
+        .collect::<Result<PermissionSet, _>>()
+        .map_err(|e| diesel::result::Error::DeserializationError(e.into()))?;
+
+    Ok(ApiKeyInfo {
+        id: row.id,
+        account_id: row.account_id,
+        name: row.name,
+        prefix: row.prefix,
+        scopes,
+        created_at: row.created_at,
+        expires_at: row.expires_at,
+        last_used_at: row.last_used_at,
+    })
+}
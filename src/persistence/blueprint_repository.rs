@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet};
+
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use crate::model::{
+    Blueprint, BlueprintAvailability, BlueprintProperties, BlueprintStatus, InventoryBuilding,
+};
+
+pub fn get_blueprint(conn: &mut PgConnection, slug: &str) -> QueryResult<Blueprint> {
+    use crate::schema::blueprints;
+
+    blueprints::table
+        .find(slug)
+        .select(Blueprint::as_select())
+        .first(conn)
+}
+
+pub fn get_properties(conn: &mut PgConnection, slug: &str) -> QueryResult<BlueprintProperties> {
+    let blueprint = get_blueprint(conn, slug)?;
+
+    parse_properties(&blueprint)
+}
+
+/// Loads the properties of every blueprint keyed by slug.
+pub fn get_all_properties(
+    conn: &mut PgConnection,
+) -> QueryResult<HashMap<String, BlueprintProperties>> {
+    use crate::schema::blueprints;
+
+    let all_blueprints = blueprints::table
+        .select(Blueprint::as_select())
+        .load(conn)?;
+
+    all_blueprints
+        .into_iter()
+        .map(|blueprint| Ok((blueprint.slug.clone(), parse_properties(&blueprint)?)))
+        .collect()
+}
+
+pub fn parse_properties(blueprint: &Blueprint) -> QueryResult<BlueprintProperties> {
+    serde_json::from_value(blueprint.properties.clone())
+        .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))
+}
+
+pub fn get_researched_techs(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+) -> QueryResult<HashSet<String>> {
+    use crate::schema::inventories_x_techs::dsl::*;
+
+    let techs = inventories_x_techs
+        .filter(inventory_id.eq(inventory))
+        .select(tech_slug)
+        .load::<String>(conn)?;
+
+    Ok(techs.into_iter().collect())
+}
+
+pub fn get_inventory_buildings(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+) -> QueryResult<Vec<InventoryBuilding>> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    inventories_x_buildings
+        .filter(inventory_id.eq(inventory))
+        .select(InventoryBuilding::as_select())
+        .load(conn)
+}
+
+/// Lists every blueprint with whether the inventory has already built it, can build it now or
+/// is still missing some of its requirements.
+pub async fn get_availability(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+) -> Result<Vec<BlueprintAvailability>, diesel::result::Error> {
+    use crate::schema::blueprints;
+
+    let all_blueprints = blueprints::table
+        .order(blueprints::slug.asc())
+        .select(Blueprint::as_select())
+        .load(conn)?;
+    let buildings = get_inventory_buildings(conn, inventory)?;
+    let techs = get_researched_techs(conn, inventory)?;
+
+    all_blueprints
+        .into_iter()
+        .map(|blueprint| {
+            let missing = parse_properties(&blueprint)?.missing_requirements(&buildings, &techs);
+
+            let built = buildings
+                .iter()
+                .any(|b| b.blueprint_slug == blueprint.slug && b.built_level().is_some());
+
+            let status = if built {
+                BlueprintStatus::Built
+            } else if missing.is_empty() {
+                BlueprintStatus::Available
+            } else {
+                BlueprintStatus::Locked
+            };
+
+            Ok(BlueprintAvailability {
+                slug: blueprint.slug,
+                name: blueprint.name,
+                status,
+                missing,
+            })
+        })
+        .collect()
+}
@@ -1,12 +1,19 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
 use diesel::prelude::*;
 use diesel::PgConnection;
 use uuid::Uuid;
 
-use crate::model::InventoryBuilding;
+use super::{blueprint_repository, resource_repository};
+use crate::error::InventoryError;
+use crate::model::{
+    BlueprintProperties, BuildingCommand, BuildingCommandOutcome, InventoryBuilding, MAX_CONDITION,
+};
 
+#[derive(Debug, Clone, PartialEq)]
 enum Status {
+    Queued,
     InProgress,
     Completed,
     Stopped,
@@ -15,6 +22,7 @@ enum Status {
 impl From<String> for Status {
     fn from(s: String) -> Self {
         match s.as_str() {
+            "queued" => Status::Queued,
             "in_progress" => Status::InProgress,
             "completed" => Status::Completed,
             "stopped" => Status::Stopped,
@@ -26,6 +34,7 @@ impl From<String> for Status {
 impl Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let status_str = match self {
+            Status::Queued => "queued",
             Status::InProgress => "in_progress",
             Status::Completed => "completed",
             Status::Stopped => "stopped",
@@ -34,29 +43,296 @@ impl Display for Status {
     }
 }
 
+/// What a lifecycle command does to a building in a given status.
+#[derive(Debug, PartialEq)]
+enum Transition {
+    Remove,
+    MoveTo(Status),
+    Repair,
+}
+
+fn transition(command: BuildingCommand, current: &Status) -> Option<Transition> {
+    match (command, current) {
+        (BuildingCommand::Cancel, Status::Queued | Status::InProgress | Status::Stopped) => {
+            Some(Transition::Remove)
+        }
+        (BuildingCommand::Pause, Status::InProgress) => Some(Transition::MoveTo(Status::Stopped)),
+        (BuildingCommand::Resume, Status::Stopped) => Some(Transition::MoveTo(Status::InProgress)),
+        (BuildingCommand::Demolish, Status::Completed) => Some(Transition::Remove),
+        (BuildingCommand::Upgrade, Status::Completed) => {
+            Some(Transition::MoveTo(Status::InProgress))
+        }
+        (BuildingCommand::Repair, Status::Completed | Status::Stopped) => Some(Transition::Repair),
+        _ => None,
+    }
+}
+
+/// Scales a cost down to the share that is returned on cancellation.
+fn refund_for(cost: &HashMap<String, i32>, ratio: f64) -> HashMap<String, i32> {
+    let ratio = ratio.clamp(0.0, 1.0);
+
+    cost.iter()
+        .map(|(resource, amount)| {
+            (
+                resource.clone(),
+                (f64::from(*amount) * ratio).floor() as i32,
+            )
+        })
+        .filter(|(_, amount)| *amount > 0)
+        .collect()
+}
+
 pub async fn create_building(
     conn: &mut PgConnection,
     inventory_id: Uuid,
     blueprint_slug: String,
-) -> Result<Uuid, diesel::result::Error> {
+) -> Result<InventoryBuilding, InventoryError> {
     use crate::schema::inventories_x_buildings;
 
-    let new_building = InventoryBuilding {
-        id: Uuid::new_v4(),
-        inventory_id,
-        blueprint_slug,
-        status: Status::InProgress.to_string(),
-        progress: 0,
-        created_at: chrono::Utc::now().naive_utc(),
-        updated_at: chrono::Utc::now().naive_utc(),
-    };
+    conn.transaction(|conn| {
+        let slots = lock_construction_slots(conn, inventory_id)?;
+
+        let properties = blueprint_repository::get_properties(conn, &blueprint_slug)?;
+
+        let missing = properties.missing_requirements(
+            &blueprint_repository::get_inventory_buildings(conn, inventory_id)?,
+            &blueprint_repository::get_researched_techs(conn, inventory_id)?,
+        );
+        if !missing.is_empty() {
+            return Err(InventoryError::MissingRequirements(missing));
+        }
+
+        resource_repository::withdraw(conn, inventory_id, &properties.cost)?;
 
-    let id = diesel::insert_into(inventories_x_buildings::table)
-        .values(&new_building)
-        .returning(inventories_x_buildings::id)
-        .get_result(conn)?;
+        let active = count_in_progress(conn, inventory_id)?;
 
-    Ok(id)
+        let queue_position = if active < i64::from(slots) {
+            None
+        } else {
+            Some(next_queue_position(conn, inventory_id)?)
+        };
+
+        let new_building = new_building(inventory_id, blueprint_slug, queue_position);
+
+        let building = diesel::insert_into(inventories_x_buildings::table)
+            .values(&new_building)
+            .returning(InventoryBuilding::as_returning())
+            .get_result(conn)?;
+
+        Ok(building)
+    })
+}
+
+/// Returns the buildings currently under construction followed by the queued ones in queue order.
+pub async fn get_build_queue(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+) -> Result<Vec<InventoryBuilding>, diesel::result::Error> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    inventories_x_buildings
+        .filter(inventory_id.eq(inventory))
+        .filter(status.eq_any([Status::InProgress.to_string(), Status::Queued.to_string()]))
+        .order((queue_position.asc().nulls_first(), created_at.asc()))
+        .select(InventoryBuilding::as_select())
+        .load(conn)
+}
+
+/// Moves a queued building to the given 1-based position, clamped to the queue bounds.
+pub async fn reorder_build_queue(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    building: Uuid,
+    position: i32,
+) -> Result<Vec<InventoryBuilding>, diesel::result::Error> {
+    conn.transaction(|conn| {
+        let mut queued = load_queued_ids(conn, inventory)?;
+
+        let current = queued
+            .iter()
+            .position(|b| *b == building)
+            .ok_or(diesel::result::Error::NotFound)?;
+        let moved = queued.remove(current);
+
+        let target = usize::try_from(position.max(1) - 1)
+            .unwrap_or_default()
+            .min(queued.len());
+        queued.insert(target, moved);
+
+        write_queue_positions(conn, &queued)
+    })?;
+
+    get_build_queue(conn, inventory).await
+}
+
+pub async fn remove_from_build_queue(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    building: Uuid,
+) -> Result<Vec<InventoryBuilding>, diesel::result::Error> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    conn.transaction(|conn| {
+        let queued = inventories_x_buildings
+            .filter(id.eq(building))
+            .filter(inventory_id.eq(inventory))
+            .filter(status.eq(Status::Queued.to_string()))
+            .select(InventoryBuilding::as_select())
+            .first(conn)?;
+
+        // Construction never started, so the whole cost goes back
+        let properties = blueprint_repository::get_properties(conn, &queued.blueprint_slug)?;
+        resource_repository::deposit(conn, inventory, &properties.cost_for_level(queued.level))?;
+        abandon_construction(conn, &queued)?;
+
+        let queued = load_queued_ids(conn, inventory)?;
+        write_queue_positions(conn, &queued)
+    })?;
+
+    get_build_queue(conn, inventory).await
+}
+
+/// Applies a lifecycle command to a building owned by the inventory, refunding part of the cost
+/// on cancellation and handing freed construction slots to the queue.
+pub async fn apply_building_command(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    building: Uuid,
+    command: BuildingCommand,
+) -> Result<BuildingCommandOutcome, InventoryError> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    conn.transaction(|conn| {
+        let slots = lock_construction_slots(conn, inventory)?;
+
+        let current = inventories_x_buildings
+            .filter(id.eq(building))
+            .filter(inventory_id.eq(inventory))
+            .select(InventoryBuilding::as_select())
+            .first(conn)?;
+        let current_status = Status::from(current.status.clone());
+
+        let next = transition(command, &current_status).ok_or_else(|| {
+            InventoryError::InvalidTransition {
+                command: command.to_string(),
+                status: current_status.to_string(),
+            }
+        })?;
+
+        let mut refund = HashMap::new();
+
+        match next {
+            Transition::Remove if command == BuildingCommand::Cancel => {
+                let properties =
+                    blueprint_repository::get_properties(conn, &current.blueprint_slug)?;
+                let cost = properties.cost_for_level(current.level);
+
+                refund = match current.progress {
+                    0 => cost,
+                    _ => refund_for(&cost, properties.refund_ratio),
+                };
+                resource_repository::deposit(conn, inventory, &refund)?;
+
+                abandon_construction(conn, &current)?;
+            }
+            Transition::Remove => {
+                diesel::delete(inventories_x_buildings.find(building)).execute(conn)?;
+            }
+            Transition::MoveTo(Status::InProgress) => {
+                if command == BuildingCommand::Upgrade {
+                    let properties =
+                        blueprint_repository::get_properties(conn, &current.blueprint_slug)?;
+                    let next_level = current.level + 1;
+
+                    if !properties.has_level(next_level) {
+                        return Err(InventoryError::MaxLevelReached(current.level));
+                    }
+
+                    resource_repository::withdraw(
+                        conn,
+                        inventory,
+                        &properties.cost_for_level(next_level),
+                    )?;
+
+                    diesel::update(inventories_x_buildings.find(building))
+                        .set((level.eq(next_level), progress.eq(0)))
+                        .execute(conn)?;
+                }
+
+                if count_in_progress(conn, inventory)? >= i64::from(slots) {
+                    // No free slot to start in, wait at the back of the queue instead
+                    let position = next_queue_position(conn, inventory)?;
+                    diesel::update(inventories_x_buildings.find(building))
+                        .set((
+                            status.eq(Status::Queued.to_string()),
+                            queue_position.eq(Some(position)),
+                            updated_at.eq(crate::clock::now().naive_utc()),
+                        ))
+                        .execute(conn)?;
+                } else {
+                    diesel::update(inventories_x_buildings.find(building))
+                        .set((
+                            status.eq(Status::InProgress.to_string()),
+                            updated_at.eq(crate::clock::now().naive_utc()),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+            Transition::Repair => {
+                if current.condition >= MAX_CONDITION {
+                    return Err(InventoryError::InvalidTransition {
+                        command: command.to_string(),
+                        status: "in full condition".into(),
+                    });
+                }
+
+                let properties =
+                    blueprint_repository::get_properties(conn, &current.blueprint_slug)?;
+                resource_repository::withdraw(
+                    conn,
+                    inventory,
+                    &properties.repair_cost_for(current.condition),
+                )?;
+
+                diesel::update(inventories_x_buildings.find(building))
+                    .set((
+                        condition.eq(MAX_CONDITION),
+                        updated_at.eq(crate::clock::now().naive_utc()),
+                    ))
+                    .execute(conn)?;
+            }
+            Transition::MoveTo(next_status) => {
+                diesel::update(inventories_x_buildings.find(building))
+                    .set((
+                        status.eq(next_status.to_string()),
+                        updated_at.eq(crate::clock::now().naive_utc()),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+
+        match current_status {
+            Status::Queued => {
+                let queued = load_queued_ids(conn, inventory)?;
+                write_queue_positions(conn, &queued)?
+            }
+            Status::InProgress => start_queued_buildings(conn, inventory)?,
+            _ => {}
+        }
+
+        let updated = inventories_x_buildings
+            .find(building)
+            .select(InventoryBuilding::as_select())
+            .first(conn)
+            .optional()?;
+
+        Ok(BuildingCommandOutcome {
+            building_id: building,
+            command,
+            building: updated,
+            refund,
+        })
+    })
 }
 
 pub async fn process_building_ticks(
@@ -72,25 +348,250 @@ pub async fn process_building_ticks(
             .filter(status.eq("in_progress"))
             .set((
                 progress.eq(progress + 1),
-                updated_at.eq(chrono::Utc::now().naive_utc()),
+                updated_at.eq(crate::clock::now().naive_utc()),
             ))
             .execute(conn)?;
 
-        // Then mark buildings as completed if they have reached required ticks
-        diesel::sql_query(
-            r"UPDATE inventories_x_buildings AS ixb
-            SET status = 'completed'
-            FROM blueprints AS b
-            WHERE ixb.inventory_id = $1
-                AND b.slug = ixb.blueprint_slug
-                AND (b.properties->>'ticks_required') ~ '^\d+$'
-                AND ixb.progress >= COALESCE(NULLIF(b.properties->>'ticks_required','')::int, 0);",
-        )
-        .bind::<diesel::sql_types::Uuid, _>(inventory)
-        .execute(conn)?;
+        // Then mark buildings as completed if they have reached the ticks required for their level
+        let in_progress = inventories_x_buildings
+            .filter(inventory_id.eq(inventory))
+            .filter(status.eq(Status::InProgress.to_string()))
+            .select(InventoryBuilding::as_select())
+            .load(conn)?;
+
+        let mut blueprints = HashMap::new();
+        for building in in_progress {
+            if !blueprints.contains_key(&building.blueprint_slug) {
+                let properties = completion_properties(conn, &building.blueprint_slug)?;
+                blueprints.insert(building.blueprint_slug.clone(), properties);
+            }
+
+            let Some(ticks_required) = blueprints[&building.blueprint_slug]
+                .as_ref()
+                .and_then(|properties| properties.ticks_for_level(building.level))
+            else {
+                continue;
+            };
+
+            if building.progress >= ticks_required {
+                diesel::update(inventories_x_buildings.find(building.id))
+                    .set(status.eq(Status::Completed.to_string()))
+                    .execute(conn)?;
+            }
+        }
+
+        // Finally start queued buildings in the slots freed up by completed ones
+        start_queued_buildings(conn, inventory)?;
 
         diesel::result::QueryResult::Ok(())
     })?;
 
     Ok(())
 }
+
+/// The properties deciding when construction completes, `None` when they can't be parsed so a
+/// broken blueprint only holds up its own buildings instead of failing every tick.
+/// The row for a building placed in an inventory, in progress unless it got a queue position.
+pub(crate) fn new_building(
+    inventory_id: Uuid,
+    blueprint_slug: String,
+    queue_position: Option<i32>,
+) -> InventoryBuilding {
+    let status = match queue_position {
+        Some(_) => Status::Queued,
+        None => Status::InProgress,
+    };
+    InventoryBuilding {
+        id: crate::rng::new_id(&format!("building:{}", inventory_id)),
+        inventory_id,
+        blueprint_slug,
+        status: status.to_string(),
+        progress: 0,
+        created_at: crate::clock::now().naive_utc(),
+        updated_at: crate::clock::now().naive_utc(),
+        queue_position,
+        level: 1,
+        condition: MAX_CONDITION,
+    }
+}
+
+fn completion_properties(
+    conn: &mut PgConnection,
+    slug: &str,
+) -> QueryResult<Option<BlueprintProperties>> {
+    match blueprint_repository::get_properties(conn, slug) {
+        Ok(properties) => Ok(Some(properties)),
+        Err(diesel::result::Error::DeserializationError(e)) => {
+            tracing::warn!(
+                blueprint = slug,
+                "not completing buildings of a blueprint with invalid properties: {}",
+                e
+            );
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Drops a building whose construction is abandoned. Upgrades fall back to the completed
+/// previous level instead of removing the building.
+fn abandon_construction(conn: &mut PgConnection, building: &InventoryBuilding) -> QueryResult<()> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    if building.level > 1 {
+        diesel::update(inventories_x_buildings.find(building.id))
+            .set((
+                status.eq(Status::Completed.to_string()),
+                level.eq(building.level - 1),
+                progress.eq(0),
+                queue_position.eq(None::<i32>),
+                updated_at.eq(crate::clock::now().naive_utc()),
+            ))
+            .execute(conn)?;
+    } else {
+        diesel::delete(inventories_x_buildings.find(building.id)).execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Locks the inventory row so concurrent requests can't both claim the last free slot.
+fn lock_construction_slots(conn: &mut PgConnection, inventory: Uuid) -> QueryResult<i32> {
+    use crate::schema::inventories;
+
+    inventories::table
+        .find(inventory)
+        .select(inventories::construction_slots)
+        .for_update()
+        .first(conn)
+}
+
+fn next_queue_position(conn: &mut PgConnection, inventory: Uuid) -> QueryResult<i32> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    let last_position = inventories_x_buildings
+        .filter(inventory_id.eq(inventory))
+        .filter(status.eq(Status::Queued.to_string()))
+        .select(diesel::dsl::max(queue_position))
+        .first::<Option<i32>>(conn)?;
+
+    Ok(last_position.unwrap_or(0) + 1)
+}
+
+fn count_in_progress(conn: &mut PgConnection, inventory: Uuid) -> QueryResult<i64> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    inventories_x_buildings
+        .filter(inventory_id.eq(inventory))
+        .filter(status.eq(Status::InProgress.to_string()))
+        .count()
+        .get_result(conn)
+}
+
+fn load_queued_ids(conn: &mut PgConnection, inventory: Uuid) -> QueryResult<Vec<Uuid>> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    inventories_x_buildings
+        .filter(inventory_id.eq(inventory))
+        .filter(status.eq(Status::Queued.to_string()))
+        .order((queue_position.asc(), created_at.asc()))
+        .select(id)
+        .load(conn)
+}
+
+fn write_queue_positions(conn: &mut PgConnection, queued: &[Uuid]) -> QueryResult<()> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    for (index, building) in queued.iter().enumerate() {
+        diesel::update(inventories_x_buildings.find(building))
+            .set(queue_position.eq(index as i32 + 1))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+fn start_queued_buildings(conn: &mut PgConnection, inventory: Uuid) -> QueryResult<()> {
+    use crate::schema::inventories;
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    let slots = inventories::table
+        .find(inventory)
+        .select(inventories::construction_slots)
+        .first::<i32>(conn)?;
+
+    let free_slots =
+        usize::try_from(i64::from(slots) - count_in_progress(conn, inventory)?).unwrap_or_default();
+
+    let queued = load_queued_ids(conn, inventory)?;
+    let (starting, waiting) = queued.split_at(free_slots.min(queued.len()));
+
+    if !starting.is_empty() {
+        diesel::update(inventories_x_buildings.filter(id.eq_any(starting)))
+            .set((
+                status.eq(Status::InProgress.to_string()),
+                queue_position.eq(None::<i32>),
+                updated_at.eq(crate::clock::now().naive_utc()),
+            ))
+            .execute(conn)?;
+
+        write_queue_positions(conn, waiting)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transition_validation() {
+        assert_eq!(
+            transition(BuildingCommand::Pause, &Status::InProgress),
+            Some(Transition::MoveTo(Status::Stopped))
+        );
+        assert_eq!(
+            transition(BuildingCommand::Resume, &Status::Stopped),
+            Some(Transition::MoveTo(Status::InProgress))
+        );
+        assert_eq!(
+            transition(BuildingCommand::Cancel, &Status::Queued),
+            Some(Transition::Remove)
+        );
+        assert_eq!(
+            transition(BuildingCommand::Demolish, &Status::Completed),
+            Some(Transition::Remove)
+        );
+        assert_eq!(
+            transition(BuildingCommand::Upgrade, &Status::Completed),
+            Some(Transition::MoveTo(Status::InProgress))
+        );
+
+        assert_eq!(transition(BuildingCommand::Pause, &Status::Queued), None);
+        assert_eq!(
+            transition(BuildingCommand::Resume, &Status::InProgress),
+            None
+        );
+        assert_eq!(
+            transition(BuildingCommand::Cancel, &Status::Completed),
+            None
+        );
+        assert_eq!(
+            transition(BuildingCommand::Demolish, &Status::InProgress),
+            None
+        );
+    }
+
+    #[test]
+    fn test_refund_for_rounds_down_and_drops_empty() {
+        let cost = HashMap::from([("wood".to_string(), 15), ("stone".to_string(), 1)]);
+
+        let refund = refund_for(&cost, 0.5);
+        assert_eq!(refund.get("wood"), Some(&7));
+        assert_eq!(refund.get("stone"), None);
+
+        let refund = refund_for(&cost, 2.0);
+        assert_eq!(refund, cost);
+    }
+}
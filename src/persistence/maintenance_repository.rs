@@ -0,0 +1,100 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use crate::model::{MaintenanceReport, MaintenanceTask};
+
+pub async fn run_task(
+    conn: &mut PgConnection,
+    task: MaintenanceTask,
+) -> QueryResult<MaintenanceReport> {
+    let (affected, summary) = match task {
+        MaintenanceTask::PurgeExpiredSessions => {
+            let purged = purge_expired_sessions(conn)?;
+            let tokens = purge_expired_account_tokens(conn)?;
+            (
+                purged + tokens,
+                format!(
+                    "purged {} expired sessions and {} account tokens",
+                    purged, tokens
+                ),
+            )
+        }
+        MaintenanceTask::CompactBuildings => {
+            let compacted = compact_buildings(conn)?;
+            (
+                compacted,
+                format!("cleared queue positions of {} buildings", compacted),
+            )
+        }
+        MaintenanceTask::DailyStats => daily_stats(conn)?,
+    };
+
+    Ok(MaintenanceReport {
+        task,
+        affected,
+        summary,
+    })
+}
+
+fn purge_expired_sessions(conn: &mut PgConnection) -> QueryResult<usize> {
+    use crate::schema::account_sessions::dsl::*;
+
+    // sessions expire in real time, see session_repository
+    let now = chrono::Utc::now().naive_utc();
+
+    diesel::delete(account_sessions.filter(expires_at.lt(now))).execute(conn)
+}
+
+/// Used tokens go too, once they would have expired anyway.
+fn purge_expired_account_tokens(conn: &mut PgConnection) -> QueryResult<usize> {
+    use crate::schema::account_tokens::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    diesel::delete(account_tokens.filter(expires_at.lt(now))).execute(conn)
+}
+
+fn compact_buildings(conn: &mut PgConnection) -> QueryResult<usize> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    diesel::update(
+        inventories_x_buildings
+            .filter(status.ne("queued"))
+            .filter(queue_position.is_not_null()),
+    )
+    .set((
+        queue_position.eq(None::<i32>),
+        updated_at.eq(crate::clock::now().naive_utc()),
+    ))
+    .execute(conn)
+}
+
+fn daily_stats(conn: &mut PgConnection) -> QueryResult<(usize, String)> {
+    use crate::schema::{account_sessions, accounts, inventories, inventories_x_buildings};
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let account_count: i64 = accounts::table.count().get_result(conn)?;
+    let session_count: i64 = account_sessions::table
+        .filter(
+            account_sessions::expires_at
+                .is_null()
+                .or(account_sessions::expires_at.ge(now)),
+        )
+        .count()
+        .get_result(conn)?;
+    let inventory_count: i64 = inventories::table.count().get_result(conn)?;
+    let building_count: i64 = inventories_x_buildings::table.count().get_result(conn)?;
+    let completed_count: i64 = inventories_x_buildings::table
+        .filter(inventories_x_buildings::status.eq("completed"))
+        .count()
+        .get_result(conn)?;
+
+    Ok((
+        0,
+        format!(
+            "{} accounts, {} active sessions, {} inventories, {} buildings ({} completed)",
+            account_count, session_count, inventory_count, building_count, completed_count
+        ),
+    ))
+}
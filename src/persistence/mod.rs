@@ -6,13 +6,35 @@ use serde::{Deserialize, Serialize};
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 
-mod inventory_repository;
+mod account_token_repository;
+mod api_key_repository;
+mod blueprint_repository;
+pub(crate) mod inventory_repository;
+mod maintenance_repository;
+mod policy_repository;
+mod production_repository;
+mod resource_repository;
+mod session_repository;
+mod upkeep_repository;
 mod user_repository;
+mod world_repository;
 
+use crate::auth::model::AuthTokens;
+use crate::auth::password::Password;
+use crate::auth::permission::{AccountState, AccountStatus, PermissionSet, Role};
+use crate::auth::token::TokenClaims;
+use crate::auth::verification::{MailRecipient, Recipient, TokenPurpose};
+use crate::error::{AccountError, AuthError, InventoryError};
 use crate::messaging::{
     broker::MessageBroker,
     model::{Message, MessageBody},
 };
+use crate::model::{
+    AccountRequest, ApiKeyInfo, BlueprintAvailability, BuildingCommand, BuildingCommandOutcome,
+    InventoryBuilding, InventoryPolicy, MaintenanceReport, MaintenanceTask, PolicyOutcome,
+    ProductionReport, ResourceStock, Season, SessionInfo, UpkeepReport, WorldSettings,
+};
+use session_repository::RefreshOutcome;
 
 const TOPIC: &str = "persistence";
 
@@ -35,6 +57,67 @@ pub enum Query {
         username: String,
         password: String,
     },
+    CreateAccount(AccountRequest),
+    /// Carries the SHA-256 of the refresh token rather than the token itself.
+    RefreshSession {
+        token_hash: String,
+    },
+    GetSession {
+        session_id: Uuid,
+    },
+    GetSessions {
+        account_id: Uuid,
+    },
+    /// Revokes one session of the account, or all of them when `session_id` is `None`.
+    RevokeSessions {
+        account_id: Uuid,
+        session_id: Option<Uuid>,
+    },
+    /// `caller` has to outrank the account and hold the permission for leaving its current
+    /// state as well as entering the new one.
+    SetAccountState {
+        caller: TokenClaims,
+        account_id: Uuid,
+        state: AccountState,
+        reason: Option<String>,
+    },
+    SetAccountRole {
+        caller_role: Role,
+        account_id: Uuid,
+        role: Role,
+    },
+    /// Stores the SHA-256 of a token to be mailed to the recipient.
+    IssueAccountToken {
+        recipient: Recipient,
+        purpose: TokenPurpose,
+        token_hash: String,
+    },
+    VerifyEmail {
+        token_hash: String,
+    },
+    ResetPassword {
+        token_hash: String,
+        password: Password,
+    },
+    /// Only the SHA-256 of the key is stored, the prefix identifies it in listings.
+    CreateApiKey {
+        account_id: Uuid,
+        name: String,
+        scopes: PermissionSet,
+        prefix: String,
+        key_hash: String,
+        expires_at: Option<chrono::NaiveDateTime>,
+    },
+    GetApiKeys {
+        account_id: Uuid,
+    },
+    RevokeApiKey {
+        account_id: Uuid,
+        key_id: Uuid,
+    },
+    AuthenticateApiKey {
+        key_hash: String,
+    },
     GetInventoryIds,
     GetInventoryForUser {
         user_id: Uuid,
@@ -46,13 +129,105 @@ pub enum Query {
     ProgressBuildings {
         inventory_id: Uuid,
     },
+    GetBuildQueue {
+        inventory_id: Uuid,
+    },
+    ReorderBuildQueue {
+        inventory_id: Uuid,
+        building_id: Uuid,
+        position: i32,
+    },
+    RemoveFromBuildQueue {
+        inventory_id: Uuid,
+        building_id: Uuid,
+    },
+    ApplyBuildingCommand {
+        inventory_id: Uuid,
+        building_id: Uuid,
+        command: BuildingCommand,
+    },
+    GetBlueprintAvailability {
+        inventory_id: Uuid,
+    },
+    GetResources {
+        inventory_id: Uuid,
+    },
+    RunProduction {
+        inventory_id: Uuid,
+        season: Season,
+    },
+    ApplyUpkeep {
+        inventory_id: Uuid,
+        seq: u64,
+    },
+    GetPolicies {
+        inventory_id: Uuid,
+    },
+    CreatePolicy {
+        inventory_id: Uuid,
+        name: String,
+        rule: serde_json::Value,
+        interval_ticks: i32,
+    },
+    DeletePolicy {
+        inventory_id: Uuid,
+        policy_id: Uuid,
+    },
+    EvaluatePolicies {
+        inventory_id: Uuid,
+        seq: u64,
+    },
+    DryRunPolicies {
+        inventory_id: Uuid,
+        policy_id: Option<Uuid>,
+    },
+    GetWorldSettings,
+    SaveTick {
+        seq: u64,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    },
+    RunMaintenance {
+        task: MaintenanceTask,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum QueryResponse {
     // Placeholder for actual response types
-    AuthSuccess(String),
-    AuthFailed(String),
+    AuthSuccess(AuthTokens),
+    AuthFailed(AuthError),
+    /// Answers `Auth`, `RefreshSession` and `GetSession` for suspended and banned accounts.
+    AccountBlocked(AccountStatus),
+
+    AccountCreated {
+        account_id: Uuid,
+        inventory_id: Uuid,
+    },
+    AccountCreationFailed(AccountError),
+    AccountUpdated(AccountStatus),
+    AccountUpdateFailed(AccountError),
+    AccountTokenIssued(MailRecipient),
+    /// Answers `VerifyEmail` and `ResetPassword` with the account the token belonged to.
+    AccountTokenRedeemed(Uuid),
+    AccountTokenFailed(AccountError),
+
+    ApiKey(ApiKeyInfo),
+    ApiKeys(Vec<ApiKeyInfo>),
+    ApiKeyRevoked(Uuid),
+    ApiKeyFailed(AccountError),
+    /// Answers `AuthenticateApiKey`, unusable keys get `AuthFailed` and blocked accounts
+    /// `AccountBlocked`.
+    ApiKeyAuthenticated(TokenClaims),
+
+    Session(Option<SessionInfo>),
+    Sessions(Vec<SessionInfo>),
+    SessionsRevoked(Vec<Uuid>),
+    SessionsFailed(String),
+
+    SessionRefreshed(AuthTokens),
+    /// A rotated refresh token was reused and the session with this id was revoked.
+    RefreshTokenReused(Uuid),
+    RefreshFailed(String),
 
     GetInventoryIds(Vec<Uuid>),
     GetInventoryIdsFailed(String),
@@ -60,8 +235,44 @@ pub enum QueryResponse {
     GetInventoryIdForUser(Uuid),
     GetInventoryIdForUserFailed(String),
 
-    CreateBuilding(Uuid),
+    CreateBuilding(InventoryBuilding),
     CreateBuildingFailed(String),
+
+    ProgressBuildings(Uuid),
+    ProgressBuildingsFailed(String),
+
+    BuildQueue(Vec<InventoryBuilding>),
+    BuildQueueFailed(String),
+
+    BuildingCommand(BuildingCommandOutcome),
+    BuildingCommandFailed(String),
+
+    BlueprintAvailability(Vec<BlueprintAvailability>),
+    BlueprintAvailabilityFailed(String),
+
+    Resources(Vec<ResourceStock>),
+    ResourcesFailed(String),
+
+    Production(ProductionReport),
+    ProductionFailed(String),
+
+    Upkeep(UpkeepReport),
+    UpkeepFailed(String),
+
+    Policies(Vec<InventoryPolicy>),
+    PoliciesFailed(String),
+
+    PolicyOutcomes(Vec<PolicyOutcome>),
+    PolicyOutcomesFailed(String),
+
+    WorldSettings(WorldSettings),
+    WorldSettingsFailed(String),
+
+    TickSaved(u64),
+    TickSaveFailed(String),
+
+    Maintenance(MaintenanceReport),
+    MaintenanceFailed(String),
 }
 
 impl Default for PersistenceHandler {
@@ -116,7 +327,7 @@ impl PersistenceHandler {
             }
 
             while let Some(msg) = rx.recv().await {
-                tracing::info!("Persistence handler received message: {:?}", msg);
+                tracing::trace!(?msg, "persistence handler received message");
                 let reply_topic = msg.reply_topic();
                 let conn = &mut pool.get().expect("Failed to get DB connection from pool");
                 match msg.body {
@@ -146,6 +357,168 @@ impl PersistenceHandler {
                                 )
                                 .await;
                             }
+                            Query::CreateAccount(account) => {
+                                PersistenceHandler::create_account(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    account,
+                                )
+                                .await;
+                            }
+                            Query::RefreshSession { token_hash } => {
+                                PersistenceHandler::refresh_session(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    token_hash,
+                                )
+                                .await;
+                            }
+                            Query::GetSession { session_id } => {
+                                PersistenceHandler::get_session(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    session_id,
+                                )
+                                .await;
+                            }
+                            Query::GetSessions { account_id } => {
+                                PersistenceHandler::get_sessions(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    account_id,
+                                )
+                                .await;
+                            }
+                            Query::RevokeSessions {
+                                account_id,
+                                session_id,
+                            } => {
+                                PersistenceHandler::revoke_sessions(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    account_id,
+                                    session_id,
+                                )
+                                .await;
+                            }
+                            Query::SetAccountState {
+                                caller,
+                                account_id,
+                                state,
+                                reason,
+                            } => {
+                                PersistenceHandler::set_account_state(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    caller,
+                                    account_id,
+                                    (state, reason),
+                                )
+                                .await;
+                            }
+                            Query::SetAccountRole {
+                                caller_role,
+                                account_id,
+                                role,
+                            } => {
+                                PersistenceHandler::set_account_role(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    caller_role,
+                                    account_id,
+                                    role,
+                                )
+                                .await;
+                            }
+                            Query::IssueAccountToken {
+                                recipient,
+                                purpose,
+                                token_hash,
+                            } => {
+                                PersistenceHandler::issue_account_token(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    (recipient, purpose),
+                                    token_hash,
+                                )
+                                .await;
+                            }
+                            Query::VerifyEmail { token_hash } => {
+                                PersistenceHandler::verify_email(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    token_hash,
+                                )
+                                .await;
+                            }
+                            Query::ResetPassword {
+                                token_hash,
+                                password,
+                            } => {
+                                PersistenceHandler::reset_password(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    token_hash,
+                                    password,
+                                )
+                                .await;
+                            }
+                            Query::CreateApiKey {
+                                account_id,
+                                name,
+                                scopes,
+                                prefix,
+                                key_hash,
+                                expires_at,
+                            } => {
+                                PersistenceHandler::create_api_key(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    (account_id, name, scopes),
+                                    (prefix, key_hash),
+                                    expires_at,
+                                )
+                                .await;
+                            }
+                            Query::GetApiKeys { account_id } => {
+                                PersistenceHandler::get_api_keys(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    account_id,
+                                )
+                                .await;
+                            }
+                            Query::RevokeApiKey { account_id, key_id } => {
+                                PersistenceHandler::revoke_api_key(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    account_id,
+                                    key_id,
+                                )
+                                .await;
+                            }
+                            Query::AuthenticateApiKey { key_hash } => {
+                                PersistenceHandler::authenticate_api_key(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    key_hash,
+                                )
+                                .await;
+                            }
                             Query::CreateBuilding {
                                 inventory_id,
                                 blueprint_slug,
@@ -168,137 +541,1012 @@ impl PersistenceHandler {
                                 )
                                 .await;
                             }
-                        }
-                    }
-                    _ => {
-                        tracing::warn!(
-                            "Persistence handler received unexpected message body: {:?}",
-                            msg.body
-                        );
-                    }
-                }
-            }
-        });
-
-        Ok(handle)
-    }
-
-    pub async fn get_inventory_ids(
-        conn: &mut PgConnection,
-        broker: &MessageBroker,
-        reply_topic: String,
-    ) {
-        tracing::debug!("received GetInventoryIds query");
-        let reply_body = match user_repository::get_inventory_ids(conn).await {
-            Ok(ids) => MessageBody::PersistenceQueryResponse(QueryResponse::GetInventoryIds(ids)),
-            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::GetInventoryIdsFailed(
-                e.to_string(),
-            )),
-        };
-
-        let message = Message {
-            id: Uuid::new_v4(),
-            body: reply_body,
-            topic: Some(reply_topic),
-            is_request: false,
-            timestamp: chrono::Utc::now().timestamp_millis() as u64,
-        };
-
-        if let Err(e) = broker.send(message).await {
-            tracing::error!(
-                error = e.to_string(),
-                "Failed to send persistence query response"
-            );
-        }
-    }
-
-    pub async fn get_inventory_id_for_user(
-        conn: &mut PgConnection,
-        broker: &MessageBroker,
-        reply_topic: String,
-        user_id: Uuid,
-    ) {
-        tracing::debug!("received GetInventoryIdForUser query");
-        let reply_body = match user_repository::get_inventory_id_for_user(conn, user_id).await {
-            Ok(ids) => {
-                MessageBody::PersistenceQueryResponse(QueryResponse::GetInventoryIdForUser(ids))
-            }
-            Err(e) => MessageBody::PersistenceQueryResponse(
-                QueryResponse::GetInventoryIdForUserFailed(e.to_string()),
-            ),
-        };
-
-        let message = Message {
-            id: Uuid::new_v4(),
-            body: reply_body,
-            topic: Some(reply_topic),
-            is_request: false,
-            timestamp: chrono::Utc::now().timestamp_millis() as u64,
-        };
-
-        if let Err(e) = broker.send(message).await {
-            tracing::error!(
-                error = e.to_string(),
-                "Failed to send persistence query response"
-            );
-        }
-    }
-
-    pub async fn auth_user(
-        conn: &mut PgConnection,
-        broker: &MessageBroker,
-        reply_topic: String,
-        user_data: (&String, &String),
-    ) {
-        let id = user_repository::authenticate(conn, user_data.0, user_data.1)
-            .await
-            .unwrap_or(None);
-
-        let reply: MessageBody = match id {
-            None => MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(
-                "Authentication failed".into(),
-            )),
-            Some(user_id) => {
-                tracing::info!("User authenticated with ID: {}", user_id);
-
-                match crate::auth::token::generate_token(&user_id.to_string()) {
-                    Ok(token) => {
-                        MessageBody::PersistenceQueryResponse(QueryResponse::AuthSuccess(token))
-                    }
-                    Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(
-                        format!("Token generation failed: {}", e),
-                    )),
-                }
-            }
-        };
-
-        let reply = Message::new(reply, Some(reply_topic), false);
-        if let Err(e) = broker.send(reply).await {
-            tracing::error!(
-                error = e.to_string(),
-                "Failed to send persistence query response"
-            );
-        }
-    }
-
-    pub async fn create_building(
-        conn: &mut PgConnection,
-        broker: &MessageBroker,
-        reply_topic: String,
-        inventory_id: Uuid,
-        blueprint_slug: String,
+                            Query::GetBuildQueue { inventory_id } => {
+                                PersistenceHandler::get_build_queue(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                )
+                                .await;
+                            }
+                            Query::ReorderBuildQueue {
+                                inventory_id,
+                                building_id,
+                                position,
+                            } => {
+                                PersistenceHandler::reorder_build_queue(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    building_id,
+                                    position,
+                                )
+                                .await;
+                            }
+                            Query::RemoveFromBuildQueue {
+                                inventory_id,
+                                building_id,
+                            } => {
+                                PersistenceHandler::remove_from_build_queue(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    building_id,
+                                )
+                                .await;
+                            }
+                            Query::ApplyBuildingCommand {
+                                inventory_id,
+                                building_id,
+                                command,
+                            } => {
+                                PersistenceHandler::apply_building_command(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    building_id,
+                                    command,
+                                )
+                                .await;
+                            }
+                            Query::GetBlueprintAvailability { inventory_id } => {
+                                PersistenceHandler::get_blueprint_availability(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                )
+                                .await;
+                            }
+                            Query::GetResources { inventory_id } => {
+                                PersistenceHandler::get_resources(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                )
+                                .await;
+                            }
+                            Query::RunProduction {
+                                inventory_id,
+                                season,
+                            } => {
+                                PersistenceHandler::run_production(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    season,
+                                )
+                                .await;
+                            }
+                            Query::ApplyUpkeep { inventory_id, seq } => {
+                                PersistenceHandler::apply_upkeep(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    seq,
+                                )
+                                .await;
+                            }
+                            Query::GetPolicies { inventory_id } => {
+                                PersistenceHandler::get_policies(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                )
+                                .await;
+                            }
+                            Query::CreatePolicy {
+                                inventory_id,
+                                name,
+                                rule,
+                                interval_ticks,
+                            } => {
+                                PersistenceHandler::create_policy(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    name,
+                                    rule,
+                                    interval_ticks,
+                                )
+                                .await;
+                            }
+                            Query::DeletePolicy {
+                                inventory_id,
+                                policy_id,
+                            } => {
+                                PersistenceHandler::delete_policy(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    policy_id,
+                                )
+                                .await;
+                            }
+                            Query::EvaluatePolicies { inventory_id, seq } => {
+                                PersistenceHandler::evaluate_policies(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    seq,
+                                )
+                                .await;
+                            }
+                            Query::DryRunPolicies {
+                                inventory_id,
+                                policy_id,
+                            } => {
+                                PersistenceHandler::dry_run_policies(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    inventory_id,
+                                    policy_id,
+                                )
+                                .await;
+                            }
+                            Query::GetWorldSettings => {
+                                PersistenceHandler::get_world_settings(conn, &broker, reply_topic)
+                                    .await;
+                            }
+                            Query::SaveTick { seq, timestamp } => {
+                                PersistenceHandler::save_tick(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    seq,
+                                    timestamp,
+                                )
+                                .await;
+                            }
+                            Query::RunMaintenance { task } => {
+                                PersistenceHandler::run_maintenance(
+                                    conn,
+                                    &broker,
+                                    reply_topic,
+                                    task,
+                                )
+                                .await;
+                            }
+                        }
+                    }
+                    _ => {
+                        tracing::warn!(
+                            "Persistence handler received unexpected message body: {:?}",
+                            msg.body
+                        );
+                    }
+                }
+            }
+        });
+
+        Ok(handle)
+    }
+
+    pub async fn get_inventory_ids(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+    ) {
+        tracing::debug!("received GetInventoryIds query");
+        let reply_body = match user_repository::get_inventory_ids(conn).await {
+            Ok(ids) => MessageBody::PersistenceQueryResponse(QueryResponse::GetInventoryIds(ids)),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::GetInventoryIdsFailed(
+                e.to_string(),
+            )),
+        };
+
+        let message = Message {
+            id: Uuid::new_v4(),
+            body: reply_body,
+            topic: Some(reply_topic),
+            is_request: false,
+            timestamp: crate::clock::now().timestamp_millis() as u64,
+        };
+
+        if let Err(e) = broker.send(message).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_inventory_id_for_user(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        user_id: Uuid,
+    ) {
+        tracing::debug!("received GetInventoryIdForUser query");
+        let reply_body = match user_repository::get_inventory_id_for_user(conn, user_id).await {
+            Ok(ids) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::GetInventoryIdForUser(ids))
+            }
+            Err(e) => MessageBody::PersistenceQueryResponse(
+                QueryResponse::GetInventoryIdForUserFailed(e.to_string()),
+            ),
+        };
+
+        let message = Message {
+            id: Uuid::new_v4(),
+            body: reply_body,
+            topic: Some(reply_topic),
+            is_request: false,
+            timestamp: crate::clock::now().timestamp_millis() as u64,
+        };
+
+        if let Err(e) = broker.send(message).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn auth_user(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        user_data: (&String, &String),
+    ) {
+        let status = user_repository::authenticate(conn, user_data.0, user_data.1).await;
+
+        let reply: MessageBody = match status {
+            Ok(None) => MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(
+                AuthError::InvalidCredentials,
+            )),
+            Ok(Some(status)) if status.state != AccountState::Active => {
+                tracing::info!(
+                    user_id = status.account_id.to_string(),
+                    state = status.state.as_str(),
+                    "refused login of blocked account"
+                );
+                MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))
+            }
+            Ok(Some(status)) => {
+                tracing::info!("User authenticated with ID: {}", status.account_id);
+
+                match session_repository::open_session(conn, status.account_id, status.role).await {
+                    Ok(tokens) => {
+                        MessageBody::PersistenceQueryResponse(QueryResponse::AuthSuccess(tokens))
+                    }
+                    Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(
+                        AuthError::Internal(format!("Session creation failed: {}", e)),
+                    )),
+                }
+            }
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(
+                AuthError::Internal(e.to_string()),
+            )),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn create_account(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        account: AccountRequest,
+    ) {
+        tracing::debug!(username = account.username, "received CreateAccount query");
+
+        let reply = match user_repository::create_account(conn, &account).await {
+            Ok((account_id, inventory_id)) => {
+                tracing::info!(
+                    account_id = account_id.to_string(),
+                    inventory_id = inventory_id.to_string(),
+                    "created account"
+                );
+                MessageBody::PersistenceQueryResponse(QueryResponse::AccountCreated {
+                    account_id,
+                    inventory_id,
+                })
+            }
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::AccountCreationFailed(e))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn refresh_session(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        token_hash: String,
+    ) {
+        let reply = match session_repository::refresh_session(conn, &token_hash).await {
+            Ok(RefreshOutcome::Refreshed(tokens)) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::SessionRefreshed(tokens))
+            }
+            Ok(RefreshOutcome::Reused(session_id)) => {
+                tracing::warn!(
+                    session_id = session_id.to_string(),
+                    "refresh token reused, revoked its session"
+                );
+                MessageBody::PersistenceQueryResponse(QueryResponse::RefreshTokenReused(session_id))
+            }
+            Ok(RefreshOutcome::Blocked(status)) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))
+            }
+            Ok(RefreshOutcome::Invalid) => MessageBody::PersistenceQueryResponse(
+                QueryResponse::RefreshFailed("invalid or expired refresh token".into()),
+            ),
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::RefreshFailed(e.to_string()))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_session(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        session_id: Uuid,
+    ) {
+        let reply = match session_repository::get_active_session(conn, session_id).await {
+            Ok(Some(session)) => {
+                match user_repository::get_account_status(conn, session.account_id).await {
+                    Ok(Some(status)) if status.state != AccountState::Active => {
+                        MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))
+                    }
+                    Ok(_) => {
+                        MessageBody::PersistenceQueryResponse(QueryResponse::Session(Some(session)))
+                    }
+                    Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::SessionsFailed(
+                        e.to_string(),
+                    )),
+                }
+            }
+            Ok(None) => MessageBody::PersistenceQueryResponse(QueryResponse::Session(None)),
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::SessionsFailed(e.to_string()))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_sessions(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        account_id: Uuid,
+    ) {
+        let reply = match session_repository::get_sessions(conn, account_id).await {
+            Ok(sessions) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::Sessions(sessions))
+            }
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::SessionsFailed(e.to_string()))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn set_account_state(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        caller: TokenClaims,
+        account_id: Uuid,
+        (state, reason): (AccountState, Option<String>),
+    ) {
+        tracing::debug!(
+            account_id = account_id.to_string(),
+            state = state.as_str(),
+            "received SetAccountState query"
+        );
+
+        let reply =
+            match user_repository::set_account_state(conn, caller, account_id, state, reason).await
+            {
+                Ok(status) => {
+                    MessageBody::PersistenceQueryResponse(QueryResponse::AccountUpdated(status))
+                }
+                Err(e) => {
+                    MessageBody::PersistenceQueryResponse(QueryResponse::AccountUpdateFailed(e))
+                }
+            };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn set_account_role(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        caller_role: Role,
+        account_id: Uuid,
+        role: Role,
+    ) {
+        tracing::debug!(
+            account_id = account_id.to_string(),
+            role = role.as_str(),
+            "received SetAccountRole query"
+        );
+
+        let reply = match user_repository::set_account_role(conn, caller_role, account_id, role)
+            .await
+        {
+            Ok(status) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::AccountUpdated(status))
+            }
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::AccountUpdateFailed(e)),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn revoke_sessions(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        account_id: Uuid,
+        session_id: Option<Uuid>,
+    ) {
+        tracing::debug!(
+            account_id = account_id.to_string(),
+            ?session_id,
+            "received RevokeSessions query"
+        );
+
+        let reply = match session_repository::revoke_sessions(conn, account_id, session_id).await {
+            Ok(revoked) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::SessionsRevoked(revoked))
+            }
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::SessionsFailed(e.to_string()))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn issue_account_token(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        (recipient, purpose): (Recipient, TokenPurpose),
+        token_hash: String,
+    ) {
+        tracing::debug!(
+            purpose = purpose.as_str(),
+            "received IssueAccountToken query"
+        );
+
+        let reply =
+            match account_token_repository::issue_token(conn, &recipient, purpose, &token_hash)
+                .await
+            {
+                Ok(recipient) => MessageBody::PersistenceQueryResponse(
+                    QueryResponse::AccountTokenIssued(recipient),
+                ),
+                Err(e) => {
+                    MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenFailed(e))
+                }
+            };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn verify_email(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        token_hash: String,
+    ) {
+        let reply = match account_token_repository::verify_email(conn, &token_hash).await {
+            Ok(account_id) => {
+                tracing::info!(account_id = account_id.to_string(), "verified email");
+                MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenRedeemed(
+                    account_id,
+                ))
+            }
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenFailed(e)),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn reset_password(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        token_hash: String,
+        password: Password,
+    ) {
+        let reply =
+            match account_token_repository::reset_password(conn, &token_hash, password.expose())
+                .await
+            {
+                Ok(account_id) => {
+                    tracing::info!(account_id = account_id.to_string(), "reset password");
+                    MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenRedeemed(
+                        account_id,
+                    ))
+                }
+                Err(e) => {
+                    MessageBody::PersistenceQueryResponse(QueryResponse::AccountTokenFailed(e))
+                }
+            };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn create_api_key(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        (account_id, name, scopes): (Uuid, String, PermissionSet),
+        (prefix, key_hash): (String, String),
+        expires_at: Option<chrono::NaiveDateTime>,
+    ) {
+        tracing::debug!(
+            account_id = account_id.to_string(),
+            prefix,
+            "received CreateApiKey query"
+        );
+
+        let reply = match api_key_repository::create_key(
+            conn,
+            account_id,
+            &name,
+            scopes,
+            (&prefix, &key_hash),
+            expires_at,
+        )
+        .await
+        {
+            Ok(key) => MessageBody::PersistenceQueryResponse(QueryResponse::ApiKey(key)),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyFailed(e)),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_api_keys(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        account_id: Uuid,
+    ) {
+        let reply = match api_key_repository::get_keys(conn, account_id).await {
+            Ok(keys) => MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeys(keys)),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyFailed(
+                AccountError::Internal(e.to_string()),
+            )),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn revoke_api_key(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        account_id: Uuid,
+        key_id: Uuid,
+    ) {
+        let reply = match api_key_repository::revoke_key(conn, account_id, key_id).await {
+            Ok(()) => MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyRevoked(key_id)),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::ApiKeyFailed(e)),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    /// Like `get_session` for API keys, the claims carry the account's current role.
+    pub async fn authenticate_api_key(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        key_hash: String,
+    ) {
+        let key = match api_key_repository::authenticate(conn, &key_hash).await {
+            Ok(Some(key)) => user_repository::get_account_status(conn, key.account_id)
+                .await
+                .map(|status| status.map(|status| (key, status))),
+            Ok(None) => Ok(None),
+            Err(e) => Err(e),
+        };
+
+        let reply = match key {
+            Ok(Some((_, status))) if status.state != AccountState::Active => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::AccountBlocked(status))
+            }
+            Ok(Some((key, status))) => MessageBody::PersistenceQueryResponse(
+                QueryResponse::ApiKeyAuthenticated(TokenClaims {
+                    account_id: key.account_id,
+                    session_id: key.id,
+                    role: status.role,
+                    scope: Some(key.scopes),
+                }),
+            ),
+            Ok(None) => MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(
+                AuthError::InvalidCredentials,
+            )),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::AuthFailed(
+                AuthError::Internal(e.to_string()),
+            )),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn create_building(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        blueprint_slug: String,
+    ) {
+        tracing::debug!("received CreateBuilding query");
+
+        let reply: MessageBody =
+            match inventory_repository::create_building(conn, inventory_id, blueprint_slug.clone())
+                .await
+            {
+                Ok(building) => {
+                    MessageBody::PersistenceQueryResponse(QueryResponse::CreateBuilding(building))
+                }
+                Err(e) => MessageBody::PersistenceQueryResponse(
+                    QueryResponse::CreateBuildingFailed(e.to_string()),
+                ),
+            };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn progress_buildings(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+    ) {
+        let reply: MessageBody =
+            match inventory_repository::process_building_ticks(conn, inventory_id).await {
+                Ok(_) => MessageBody::PersistenceQueryResponse(QueryResponse::ProgressBuildings(
+                    inventory_id,
+                )),
+                Err(e) => MessageBody::PersistenceQueryResponse(
+                    QueryResponse::ProgressBuildingsFailed(e.to_string()),
+                ),
+            };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_build_queue(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+    ) {
+        tracing::debug!("received GetBuildQueue query");
+
+        let reply = Self::build_queue_reply(
+            inventory_repository::get_build_queue(conn, inventory_id).await,
+        );
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn reorder_build_queue(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        building_id: Uuid,
+        position: i32,
+    ) {
+        tracing::debug!("received ReorderBuildQueue query");
+
+        let reply = Self::build_queue_reply(
+            inventory_repository::reorder_build_queue(conn, inventory_id, building_id, position)
+                .await,
+        );
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn remove_from_build_queue(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        building_id: Uuid,
     ) {
-        tracing::debug!("received CreateBuilding query");
+        tracing::debug!("received RemoveFromBuildQueue query");
 
-        let reply: MessageBody =
-            match inventory_repository::create_building(conn, inventory_id, blueprint_slug.clone())
+        let reply = Self::build_queue_reply(
+            inventory_repository::remove_from_build_queue(conn, inventory_id, building_id).await,
+        );
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn apply_building_command(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        building_id: Uuid,
+        command: BuildingCommand,
+    ) {
+        tracing::debug!(%command, "received ApplyBuildingCommand query");
+
+        let reply = match inventory_repository::apply_building_command(
+            conn,
+            inventory_id,
+            building_id,
+            command,
+        )
+        .await
+        {
+            Ok(outcome) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::BuildingCommand(outcome))
+            }
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::BuildingCommandFailed(
+                e.to_string(),
+            )),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_blueprint_availability(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+    ) {
+        tracing::debug!("received GetBlueprintAvailability query");
+
+        let reply = match blueprint_repository::get_availability(conn, inventory_id).await {
+            Ok(blueprints) => MessageBody::PersistenceQueryResponse(
+                QueryResponse::BlueprintAvailability(blueprints),
+            ),
+            Err(e) => MessageBody::PersistenceQueryResponse(
+                QueryResponse::BlueprintAvailabilityFailed(e.to_string()),
+            ),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_resources(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+    ) {
+        tracing::debug!("received GetResources query");
+
+        let reply = match resource_repository::get_stock(conn, inventory_id).await {
+            Ok(stock) => MessageBody::PersistenceQueryResponse(QueryResponse::Resources(stock)),
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::ResourcesFailed(e.to_string()))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn run_production(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        season: Season,
+    ) {
+        let reply = match production_repository::run_production(conn, inventory_id, season).await {
+            Ok(report) => MessageBody::PersistenceQueryResponse(QueryResponse::Production(report)),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::ProductionFailed(
+                e.to_string(),
+            )),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn apply_upkeep(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        seq: u64,
+    ) {
+        let reply = match upkeep_repository::apply_upkeep(conn, inventory_id, seq).await {
+            Ok(report) => MessageBody::PersistenceQueryResponse(QueryResponse::Upkeep(report)),
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::UpkeepFailed(e.to_string()))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_policies(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+    ) {
+        tracing::debug!("received GetPolicies query");
+
+        let reply = Self::policies_reply(
+            policy_repository::get_policies(conn, inventory_id)
                 .await
-            {
-                Ok(id) => MessageBody::PersistenceQueryResponse(QueryResponse::CreateBuilding(id)),
-                Err(e) => MessageBody::PersistenceQueryResponse(
-                    QueryResponse::CreateBuildingFailed(e.to_string()),
-                ),
-            };
+                .map_err(Into::into),
+        );
 
         let reply = Message::new(reply, Some(reply_topic), false);
         if let Err(e) = broker.send(reply).await {
@@ -309,24 +1557,158 @@ impl PersistenceHandler {
         }
     }
 
-    pub async fn progress_buildings(
+    pub async fn create_policy(
         conn: &mut PgConnection,
         broker: &MessageBroker,
         reply_topic: String,
         inventory_id: Uuid,
+        name: String,
+        rule: serde_json::Value,
+        interval_ticks: i32,
     ) {
-        let reply: MessageBody =
-            match inventory_repository::process_building_ticks(conn, inventory_id).await {
-                Ok(_) => {
-                    // No specific response needed for progress operation
-                    MessageBody::PersistenceQueryResponse(QueryResponse::CreateBuilding(
-                        inventory_id,
-                    ))
-                }
-                Err(e) => MessageBody::PersistenceQueryResponse(
-                    QueryResponse::CreateBuildingFailed(e.to_string()),
-                ),
-            };
+        tracing::debug!("received CreatePolicy query");
+
+        let reply = Self::policies_reply(
+            policy_repository::create_policy(conn, inventory_id, name, rule, interval_ticks).await,
+        );
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn delete_policy(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        policy_id: Uuid,
+    ) {
+        tracing::debug!("received DeletePolicy query");
+
+        let reply = Self::policies_reply(
+            policy_repository::delete_policy(conn, inventory_id, policy_id)
+                .await
+                .map_err(Into::into),
+        );
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn evaluate_policies(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        seq: u64,
+    ) {
+        let reply = Self::policy_outcomes_reply(
+            policy_repository::evaluate_policies(conn, inventory_id, seq).await,
+        );
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn dry_run_policies(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        inventory_id: Uuid,
+        policy_id: Option<Uuid>,
+    ) {
+        tracing::debug!("received DryRunPolicies query");
+
+        let reply = Self::policy_outcomes_reply(
+            policy_repository::dry_run_policies(conn, inventory_id, policy_id).await,
+        );
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn get_world_settings(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+    ) {
+        tracing::debug!("received GetWorldSettings query");
+
+        let reply = match world_repository::get_world_settings(conn).await {
+            Ok(settings) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::WorldSettings(settings))
+            }
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::WorldSettingsFailed(
+                e.to_string(),
+            )),
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn save_tick(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        seq: u64,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) {
+        let reply = match world_repository::save_tick(conn, seq, timestamp).await {
+            Ok(_) => MessageBody::PersistenceQueryResponse(QueryResponse::TickSaved(seq)),
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::TickSaveFailed(e.to_string()))
+            }
+        };
+
+        let reply = Message::new(reply, Some(reply_topic), false);
+        if let Err(e) = broker.send(reply).await {
+            tracing::error!(
+                error = e.to_string(),
+                "Failed to send persistence query response"
+            );
+        }
+    }
+
+    pub async fn run_maintenance(
+        conn: &mut PgConnection,
+        broker: &MessageBroker,
+        reply_topic: String,
+        task: MaintenanceTask,
+    ) {
+        tracing::debug!(?task, "received RunMaintenance query");
+
+        let reply = match maintenance_repository::run_task(conn, task).await {
+            Ok(report) => MessageBody::PersistenceQueryResponse(QueryResponse::Maintenance(report)),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::MaintenanceFailed(
+                e.to_string(),
+            )),
+        };
 
         let reply = Message::new(reply, Some(reply_topic), false);
         if let Err(e) = broker.send(reply).await {
@@ -336,4 +1718,40 @@ impl PersistenceHandler {
             );
         }
     }
+
+    fn policies_reply(result: Result<Vec<InventoryPolicy>, InventoryError>) -> MessageBody {
+        match result {
+            Ok(policies) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::Policies(policies))
+            }
+            Err(e) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::PoliciesFailed(e.to_string()))
+            }
+        }
+    }
+
+    fn policy_outcomes_reply(result: Result<Vec<PolicyOutcome>, InventoryError>) -> MessageBody {
+        match result {
+            Ok(outcomes) => {
+                MessageBody::PersistenceQueryResponse(QueryResponse::PolicyOutcomes(outcomes))
+            }
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::PolicyOutcomesFailed(
+                e.to_string(),
+            )),
+        }
+    }
+
+    fn build_queue_reply(
+        result: Result<Vec<InventoryBuilding>, diesel::result::Error>,
+    ) -> MessageBody {
+        match result {
+            Ok(queue) => MessageBody::PersistenceQueryResponse(QueryResponse::BuildQueue(queue)),
+            Err(diesel::result::Error::NotFound) => MessageBody::PersistenceQueryResponse(
+                QueryResponse::BuildQueueFailed("building is not in the build queue".into()),
+            ),
+            Err(e) => MessageBody::PersistenceQueryResponse(QueryResponse::BuildQueueFailed(
+                e.to_string(),
+            )),
+        }
+    }
 }
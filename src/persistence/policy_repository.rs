@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use super::{blueprint_repository, inventory_repository, resource_repository};
+use crate::error::InventoryError;
+use crate::model::{
+    BlueprintProperties, BuildingCommand, InventoryBuilding, InventoryPolicy, PlannedAction,
+    PolicyAction, PolicyCondition, PolicyOutcome, PolicyRule,
+};
+
+pub async fn get_policies(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+) -> Result<Vec<InventoryPolicy>, diesel::result::Error> {
+    use crate::schema::inventory_policies::dsl::*;
+
+    inventory_policies
+        .filter(inventory_id.eq(inventory))
+        .order(created_at.asc())
+        .select(InventoryPolicy::as_select())
+        .load(conn)
+}
+
+pub async fn create_policy(
+    conn: &mut PgConnection,
+    inventory_id: Uuid,
+    name: String,
+    rule: serde_json::Value,
+    interval_ticks: i32,
+) -> Result<Vec<InventoryPolicy>, InventoryError> {
+    use crate::schema::inventory_policies;
+
+    parse_rule(&rule)?;
+    if interval_ticks < 1 {
+        return Err(InventoryError::InvalidPolicy(
+            "interval must be at least one tick".into(),
+        ));
+    }
+
+    let policy = InventoryPolicy {
+        id: crate::rng::new_id(&format!("policy:{}", inventory_id)),
+        inventory_id,
+        name,
+        rule,
+        interval_ticks,
+        enabled: true,
+        created_at: crate::clock::now().naive_utc(),
+        updated_at: crate::clock::now().naive_utc(),
+    };
+
+    diesel::insert_into(inventory_policies::table)
+        .values(&policy)
+        .execute(conn)?;
+
+    Ok(get_policies(conn, inventory_id).await?)
+}
+
+pub async fn delete_policy(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    policy: Uuid,
+) -> Result<Vec<InventoryPolicy>, diesel::result::Error> {
+    use crate::schema::inventory_policies::dsl::*;
+
+    let deleted = diesel::delete(
+        inventory_policies
+            .filter(id.eq(policy))
+            .filter(inventory_id.eq(inventory)),
+    )
+    .execute(conn)?;
+
+    if deleted == 0 {
+        return Err(diesel::result::Error::NotFound);
+    }
+
+    get_policies(conn, inventory).await
+}
+
+/// Evaluates the enabled policies due at tick `seq` and carries out whatever they decide.
+pub async fn evaluate_policies(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    seq: u64,
+) -> Result<Vec<PolicyOutcome>, InventoryError> {
+    let due = get_policies(conn, inventory)
+        .await?
+        .into_iter()
+        .filter(|p| {
+            p.enabled && p.interval_ticks > 0 && seq.is_multiple_of(p.interval_ticks as u64)
+        })
+        .collect::<Vec<_>>();
+
+    let mut outcomes = Vec::with_capacity(due.len());
+    for policy in due {
+        let mut outcome = plan(conn, inventory, &policy)?;
+
+        if let Some(action) = &outcome.action {
+            let result = match action {
+                PlannedAction::Build { blueprint } => {
+                    inventory_repository::create_building(conn, inventory, blueprint.clone())
+                        .await
+                        .map(|_| ())
+                }
+                PlannedAction::Upgrade { building_id, .. } => {
+                    inventory_repository::apply_building_command(
+                        conn,
+                        inventory,
+                        *building_id,
+                        BuildingCommand::Upgrade,
+                    )
+                    .await
+                    .map(|_| ())
+                }
+            };
+
+            match result {
+                Ok(()) => outcome.executed = true,
+                Err(e) => outcome.detail = Some(e.to_string()),
+            }
+        }
+
+        outcomes.push(outcome);
+    }
+
+    Ok(outcomes)
+}
+
+/// Reports what the inventory's policies, or a single one, would do right now without acting.
+pub async fn dry_run_policies(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    policy: Option<Uuid>,
+) -> Result<Vec<PolicyOutcome>, InventoryError> {
+    let policies = get_policies(conn, inventory)
+        .await?
+        .into_iter()
+        .filter(|p| policy.is_none() || policy == Some(p.id))
+        .collect::<Vec<_>>();
+
+    if policy.is_some() && policies.is_empty() {
+        return Err(InventoryError::NotFound);
+    }
+
+    policies
+        .iter()
+        .map(|policy| plan(conn, inventory, policy))
+        .collect()
+}
+
+fn plan(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    policy: &InventoryPolicy,
+) -> Result<PolicyOutcome, InventoryError> {
+    let rule = parse_rule(&policy.rule)?;
+    let buildings = blueprint_repository::get_inventory_buildings(conn, inventory)?;
+    let quantities = resource_repository::load_quantities(conn, inventory)?;
+    let blueprints = blueprint_repository::get_all_properties(conn)?;
+
+    let (action, detail) = match plan_policy(&rule, &quantities, &buildings, &blueprints) {
+        Ok(action) => (Some(action), None),
+        Err(reason) => (None, Some(reason)),
+    };
+
+    Ok(PolicyOutcome {
+        policy_id: policy.id,
+        name: policy.name.clone(),
+        action,
+        executed: false,
+        detail,
+    })
+}
+
+fn parse_rule(rule: &serde_json::Value) -> Result<PolicyRule, InventoryError> {
+    serde_json::from_value(rule.clone()).map_err(|e| InventoryError::InvalidPolicy(e.to_string()))
+}
+
+/// Resolves a rule against the inventory state, returning why it stays idle when it does.
+fn plan_policy(
+    rule: &PolicyRule,
+    quantities: &HashMap<String, i32>,
+    buildings: &[InventoryBuilding],
+    blueprints: &HashMap<String, BlueprintProperties>,
+) -> Result<PlannedAction, String> {
+    let quantity = |slug: &str| quantities.get(slug).copied().unwrap_or_default();
+
+    for condition in &rule.when {
+        let (resource, holds) = match condition {
+            PolicyCondition::ResourceBelow { resource, amount } => {
+                (resource, quantity(resource) < *amount)
+            }
+            PolicyCondition::ResourceAbove { resource, amount } => {
+                (resource, quantity(resource) > *amount)
+            }
+        };
+
+        if !holds {
+            return Err(format!("condition on {} is not met", resource));
+        }
+    }
+
+    match &rule.then {
+        PolicyAction::Build { blueprint } => {
+            let pending = buildings.iter().any(|b| {
+                b.blueprint_slug == *blueprint
+                    && matches!(b.status.as_str(), "queued" | "in_progress")
+            });
+
+            if pending {
+                return Err(format!("a {} is already pending", blueprint));
+            }
+
+            Ok(PlannedAction::Build {
+                blueprint: blueprint.clone(),
+            })
+        }
+        PolicyAction::UpgradeLowest { blueprint } => {
+            let max_level = blueprints
+                .get(blueprint)
+                .map(BlueprintProperties::max_level)
+                .unwrap_or(1);
+
+            buildings
+                .iter()
+                .filter(|b| {
+                    b.blueprint_slug == *blueprint && b.status == "completed" && b.level < max_level
+                })
+                .min_by_key(|b| (b.level, b.created_at))
+                .map(|b| PlannedAction::Upgrade {
+                    building_id: b.id,
+                    blueprint: blueprint.clone(),
+                })
+                .ok_or_else(|| format!("no {} can be upgraded", blueprint))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(value: serde_json::Value) -> PolicyRule {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_plan_policy_build_when_below() {
+        let rule = rule(serde_json::json!({
+            "when": [{ "kind": "resource_below", "resource": "wood", "amount": 500 }],
+            "then": { "kind": "build", "blueprint": "farm" }
+        }));
+
+        let low = HashMap::from([("wood".to_string(), 100)]);
+        let high = HashMap::from([("wood".to_string(), 800)]);
+
+        assert_eq!(
+            plan_policy(&rule, &low, &[], &HashMap::new()),
+            Ok(PlannedAction::Build {
+                blueprint: "farm".into()
+            })
+        );
+        assert!(plan_policy(&rule, &high, &[], &HashMap::new()).is_err());
+        assert!(plan_policy(
+            &rule,
+            &low,
+            &[InventoryBuilding::fixture("farm", "queued", 1)],
+            &HashMap::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_plan_policy_upgrades_lowest_level() {
+        let rule = rule(serde_json::json!({
+            "when": [{ "kind": "resource_above", "resource": "stone", "amount": 200 }],
+            "then": { "kind": "upgrade_lowest", "blueprint": "farm" }
+        }));
+        let blueprints = HashMap::from([(
+            "farm".to_string(),
+            serde_json::from_value::<BlueprintProperties>(serde_json::json!({
+                "levels": { "2": {}, "3": {} }
+            }))
+            .unwrap(),
+        )]);
+        let quantities = HashMap::from([("stone".to_string(), 250)]);
+        let buildings = vec![
+            InventoryBuilding::fixture("farm", "completed", 3),
+            InventoryBuilding::fixture("farm", "completed", 2),
+            InventoryBuilding::fixture("farm", "in_progress", 1),
+        ];
+
+        assert_eq!(
+            plan_policy(&rule, &quantities, &buildings, &blueprints),
+            Ok(PlannedAction::Upgrade {
+                building_id: buildings[1].id,
+                blueprint: "farm".into()
+            })
+        );
+        assert!(plan_policy(&rule, &quantities, &buildings[..1], &blueprints).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use super::{blueprint_repository, resource_repository};
+use crate::error::InventoryError;
+use crate::model::{
+    BlueprintProperties, InventoryBuilding, ProductionReport, Season, StallReason, StalledBuilding,
+    MAX_CONDITION,
+};
+
+/// Runs one tick of every producing building's recipe in `season`, oldest building first.
+pub async fn run_production(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    season: Season,
+) -> Result<ProductionReport, InventoryError> {
+    conn.transaction(|conn| {
+        let mut buildings = blueprint_repository::get_inventory_buildings(conn, inventory)?;
+        buildings.sort_by_key(|b| b.created_at);
+
+        let blueprints = blueprint_repository::get_all_properties(conn)?;
+        let quantities = resource_repository::load_quantities(conn, inventory)?;
+        let rules = resource_repository::load_resources(conn)?;
+        let capacities = resource_repository::get_capacities(conn, inventory, &rules)?;
+
+        let report = plan_production(&buildings, &blueprints, &quantities, &capacities, season);
+
+        resource_repository::withdraw(conn, inventory, &report.consumed)?;
+        resource_repository::deposit(conn, inventory, &report.produced)?;
+
+        Ok(report)
+    })
+}
+
+/// Works out what every producing building consumes and produces this tick. Buildings that lack
+/// an input or whose outputs have no room left are reported as stalled and skipped.
+fn plan_production(
+    buildings: &[InventoryBuilding],
+    blueprints: &HashMap<String, BlueprintProperties>,
+    quantities: &HashMap<String, i32>,
+    capacities: &HashMap<String, i32>,
+    season: Season,
+) -> ProductionReport {
+    let mut available = quantities.clone();
+    let mut report = ProductionReport::default();
+
+    for building in buildings {
+        let Some(properties) = blueprints.get(&building.blueprint_slug) else {
+            continue;
+        };
+        let Some(level) = building.producing_level(properties) else {
+            continue;
+        };
+        if properties.inputs.is_empty() && properties.outputs.is_empty() {
+            continue;
+        }
+
+        let stock = |slug: &str, available: &HashMap<String, i32>| {
+            available.get(slug).copied().unwrap_or_default()
+        };
+
+        let missing_input = properties
+            .inputs
+            .iter()
+            .find(|(slug, amount)| stock(slug, &available) < **amount);
+        let full_output = properties.outputs.keys().find(|slug| {
+            capacities
+                .get(*slug)
+                .is_some_and(|capacity| stock(slug, &available) >= *capacity)
+        });
+
+        let reason = match (missing_input, full_output) {
+            _ if building.condition <= 0 => Some(StallReason::Broken),
+            (Some((slug, _)), _) => Some(StallReason::MissingInput {
+                resource: slug.clone(),
+            }),
+            (None, Some(slug)) => Some(StallReason::StorageFull {
+                resource: slug.clone(),
+            }),
+            (None, None) => None,
+        };
+
+        if let Some(reason) = reason {
+            report.stalled.push(StalledBuilding {
+                building_id: building.id,
+                blueprint_slug: building.blueprint_slug.clone(),
+                reason,
+            });
+            continue;
+        }
+
+        for (slug, amount) in &properties.inputs {
+            *available.entry(slug.clone()).or_default() -= amount;
+            *report.consumed.entry(slug.clone()).or_default() += amount;
+        }
+
+        // Worn down buildings produce proportionally less
+        let multiplier = properties.output_multiplier(level)
+            * properties.seasonal_multiplier(season)
+            * f64::from(building.condition)
+            / f64::from(MAX_CONDITION);
+        for (slug, amount) in &properties.outputs {
+            let amount = (f64::from(*amount) * multiplier).floor() as i32;
+            *available.entry(slug.clone()).or_default() += amount;
+            *report.produced.entry(slug.clone()).or_default() += amount;
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blueprints() -> HashMap<String, BlueprintProperties> {
+        HashMap::from([
+            (
+                "sawmill".to_string(),
+                serde_json::from_value(serde_json::json!({
+                    "inputs": { "wood": 2 },
+                    "outputs": { "plank": 1 }
+                }))
+                .unwrap(),
+            ),
+            (
+                "lumber_camp".to_string(),
+                serde_json::from_value(serde_json::json!({ "outputs": { "wood": 3 } })).unwrap(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_plan_production_consumes_inputs() {
+        let buildings = vec![
+            InventoryBuilding::fixture("sawmill", "completed", 1),
+            InventoryBuilding::fixture("sawmill", "completed", 1),
+        ];
+        let quantities = HashMap::from([("wood".to_string(), 3)]);
+
+        let report = plan_production(
+            &buildings,
+            &blueprints(),
+            &quantities,
+            &HashMap::new(),
+            Season::Summer,
+        );
+
+        assert_eq!(report.consumed.get("wood"), Some(&2));
+        assert_eq!(report.produced.get("plank"), Some(&1));
+        assert_eq!(report.stalled.len(), 1);
+        assert_eq!(
+            report.stalled[0].reason,
+            StallReason::MissingInput {
+                resource: "wood".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_plan_production_scales_with_condition() {
+        let mut worn = InventoryBuilding::fixture("lumber_camp", "completed", 1);
+        worn.condition = 50;
+        let mut broken = InventoryBuilding::fixture("lumber_camp", "completed", 1);
+        broken.condition = 0;
+
+        let report = plan_production(
+            &[worn, broken],
+            &blueprints(),
+            &HashMap::new(),
+            &HashMap::new(),
+            Season::Summer,
+        );
+
+        assert_eq!(report.produced.get("wood"), Some(&1));
+        assert_eq!(report.stalled[0].reason, StallReason::Broken);
+    }
+
+    #[test]
+    fn test_plan_production_stalls_on_full_storage() {
+        let buildings = vec![
+            InventoryBuilding::fixture("lumber_camp", "completed", 1),
+            InventoryBuilding::fixture("sawmill", "completed", 1),
+        ];
+        let quantities = HashMap::from([("wood".to_string(), 100)]);
+        let capacities = HashMap::from([("wood".to_string(), 100), ("plank".to_string(), 50)]);
+
+        let report = plan_production(
+            &buildings,
+            &blueprints(),
+            &quantities,
+            &capacities,
+            Season::Summer,
+        );
+
+        assert_eq!(report.produced.get("wood"), None);
+        assert_eq!(report.produced.get("plank"), Some(&1));
+        assert_eq!(
+            report.stalled[0].reason,
+            StallReason::StorageFull {
+                resource: "wood".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_plan_production_applies_season() {
+        let blueprints = HashMap::from([(
+            "farm".to_string(),
+            serde_json::from_value(serde_json::json!({
+                "outputs": { "grain": 8 },
+                "seasonal": { "winter": 0.25 }
+            }))
+            .unwrap(),
+        )]);
+        let buildings = vec![InventoryBuilding::fixture("farm", "completed", 1)];
+
+        let summer = plan_production(
+            &buildings,
+            &blueprints,
+            &HashMap::new(),
+            &HashMap::new(),
+            Season::Summer,
+        );
+        let winter = plan_production(
+            &buildings,
+            &blueprints,
+            &HashMap::new(),
+            &HashMap::new(),
+            Season::Winter,
+        );
+
+        assert_eq!(summer.produced.get("grain"), Some(&8));
+        assert_eq!(winter.produced.get("grain"), Some(&2));
+    }
+}
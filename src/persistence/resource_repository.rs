@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use super::blueprint_repository;
+use crate::error::InventoryError;
+use crate::model::{BlueprintProperties, InventoryBuilding, Resource, ResourceStock};
+
+/// Removes the given amounts from an inventory, failing without side effects if any resource
+/// falls short. Meant to run inside the caller's transaction.
+pub fn withdraw(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    amounts: &HashMap<String, i32>,
+) -> Result<(), InventoryError> {
+    use crate::schema::inventories_x_resources::dsl::*;
+
+    for (slug, amount) in amounts.iter().filter(|(_, amount)| **amount > 0) {
+        let updated = diesel::update(
+            inventories_x_resources
+                .filter(inventory_id.eq(inventory))
+                .filter(resource.eq(slug))
+                .filter(quantity.ge(*amount)),
+        )
+        .set(quantity.eq(quantity - *amount))
+        .execute(conn)?;
+
+        if updated == 0 {
+            return Err(InventoryError::InsufficientResources(slug.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds the given amounts to an inventory, creating missing resource rows. Anything above the
+/// storage capacity is converted or discarded according to the resource's overflow rule.
+pub fn deposit(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    amounts: &HashMap<String, i32>,
+) -> QueryResult<()> {
+    use crate::schema::inventories_x_resources::dsl::*;
+
+    if amounts.values().all(|amount| *amount <= 0) {
+        return Ok(());
+    }
+
+    let rules = load_resources(conn)?;
+    let capacities = get_capacities(conn, inventory, &rules)?;
+    let current = load_quantities(conn, inventory)?;
+
+    for (slug, total) in plan_deposit(&current, amounts, &capacities, &rules) {
+        diesel::insert_into(inventories_x_resources)
+            .values((
+                inventory_id.eq(inventory),
+                resource.eq(&slug),
+                quantity.eq(total),
+            ))
+            .on_conflict((inventory_id, resource))
+            .do_update()
+            .set(quantity.eq(total))
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Returns every known resource with the inventory's quantity and storage capacity.
+pub async fn get_stock(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+) -> Result<Vec<ResourceStock>, diesel::result::Error> {
+    let rules = load_resources(conn)?;
+    let capacities = get_capacities(conn, inventory, &rules)?;
+    let current = load_quantities(conn, inventory)?;
+
+    let mut stock = rules
+        .keys()
+        .map(|slug| ResourceStock {
+            resource: slug.clone(),
+            quantity: current.get(slug).copied().unwrap_or_default(),
+            capacity: capacities.get(slug).copied().unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+    stock.sort_by(|a, b| a.resource.cmp(&b.resource));
+
+    Ok(stock)
+}
+
+pub fn get_capacities(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    rules: &HashMap<String, Resource>,
+) -> QueryResult<HashMap<String, i32>> {
+    let buildings = blueprint_repository::get_inventory_buildings(conn, inventory)?;
+    let blueprints = blueprint_repository::get_all_properties(conn)?;
+
+    Ok(capacities(rules, &buildings, &blueprints))
+}
+
+pub fn load_quantities(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+) -> QueryResult<HashMap<String, i32>> {
+    use crate::schema::inventories_x_resources::dsl::*;
+
+    let quantities = inventories_x_resources
+        .filter(inventory_id.eq(inventory))
+        .select((resource, quantity))
+        .load::<(String, i32)>(conn)?;
+
+    Ok(quantities.into_iter().collect())
+}
+
+pub fn load_resources(conn: &mut PgConnection) -> QueryResult<HashMap<String, Resource>> {
+    use crate::schema::resources;
+
+    let rules = resources::table.select(Resource::as_select()).load(conn)?;
+
+    Ok(rules.into_iter().map(|r| (r.slug.clone(), r)).collect())
+}
+
+/// Base capacity of every resource plus the storage provided by finished buildings.
+fn capacities(
+    rules: &HashMap<String, Resource>,
+    buildings: &[InventoryBuilding],
+    blueprints: &HashMap<String, BlueprintProperties>,
+) -> HashMap<String, i32> {
+    let mut capacities = rules
+        .values()
+        .map(|r| (r.slug.clone(), r.base_capacity))
+        .collect::<HashMap<_, _>>();
+
+    for building in buildings {
+        let (Some(level), Some(properties)) = (
+            building.built_level(),
+            blueprints.get(&building.blueprint_slug),
+        ) else {
+            continue;
+        };
+
+        let multiplier = properties.output_multiplier(level);
+        for (slug, amount) in &properties.storage {
+            let extra = (f64::from(*amount) * multiplier).floor() as i32;
+            *capacities.entry(slug.clone()).or_default() += extra;
+        }
+    }
+
+    capacities
+}
+
+/// Works out the resulting quantity of every resource touched by a deposit. Overflow is
+/// converted at most once so conversion rules can't chain or loop. Only the deposited amounts
+/// are capped, stock already above capacity is kept.
+fn plan_deposit(
+    current: &HashMap<String, i32>,
+    amounts: &HashMap<String, i32>,
+    capacities: &HashMap<String, i32>,
+    rules: &HashMap<String, Resource>,
+) -> HashMap<String, i32> {
+    let capacity =
+        |slug: &str, stock: i32| capacities.get(slug).copied().unwrap_or(i32::MAX).max(stock);
+
+    let mut totals = HashMap::new();
+    let mut converted: HashMap<String, i32> = HashMap::new();
+
+    for (slug, amount) in amounts.iter().filter(|(_, amount)| **amount > 0) {
+        let stock = current.get(slug).copied().unwrap_or_default();
+        let total = stock.saturating_add(*amount);
+        let kept = total.min(capacity(slug, stock));
+        totals.insert(slug.clone(), kept);
+
+        let excess = total - kept;
+        let rule = rules.get(slug);
+        if let Some((target, rate)) = rule
+            .and_then(|r| r.overflow_resource.as_ref().map(|t| (t, r.overflow_rate)))
+            .filter(|(target, _)| *target != slug && excess > 0)
+        {
+            let amount = (excess as f32 * rate.max(0.0)).floor() as i32;
+            *converted.entry(target.clone()).or_default() += amount;
+        }
+    }
+
+    for (slug, amount) in converted.into_iter().filter(|(_, amount)| *amount > 0) {
+        let base = totals
+            .get(&slug)
+            .or_else(|| current.get(&slug))
+            .copied()
+            .unwrap_or_default();
+        totals.insert(
+            slug.clone(),
+            base.saturating_add(amount).min(capacity(&slug, base)),
+        );
+    }
+
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(slug: &str, base_capacity: i32, overflow: Option<&str>) -> Resource {
+        Resource {
+            slug: slug.into(),
+            name: slug.into(),
+            base_capacity,
+            overflow_resource: overflow.map(Into::into),
+            overflow_rate: 0.5,
+        }
+    }
+
+    fn rules() -> HashMap<String, Resource> {
+        [
+            resource("wood", 100, Some("charcoal")),
+            resource("stone", 100, None),
+            resource("charcoal", 100, Some("wood")),
+        ]
+        .into_iter()
+        .map(|r| (r.slug.clone(), r))
+        .collect()
+    }
+
+    #[test]
+    fn test_capacities_include_finished_storage() {
+        let blueprints = HashMap::from([(
+            "warehouse".to_string(),
+            serde_json::from_value::<BlueprintProperties>(serde_json::json!({
+                "storage": { "wood": 200 },
+                "levels": { "2": { "output_multiplier": 2.0 } }
+            }))
+            .unwrap(),
+        )]);
+
+        let buildings = vec![
+            InventoryBuilding::fixture("warehouse", "completed", 2),
+            InventoryBuilding::fixture("warehouse", "in_progress", 1),
+        ];
+        let capacities = capacities(&rules(), &buildings, &blueprints);
+
+        assert_eq!(capacities.get("wood"), Some(&500));
+        assert_eq!(capacities.get("stone"), Some(&100));
+    }
+
+    #[test]
+    fn test_plan_deposit_converts_or_discards_overflow() {
+        let rules = rules();
+        let capacities = rules
+            .values()
+            .map(|r| (r.slug.clone(), r.base_capacity))
+            .collect::<HashMap<_, _>>();
+        let current = HashMap::from([("wood".to_string(), 90), ("charcoal".to_string(), 10)]);
+        let amounts = HashMap::from([("wood".to_string(), 30), ("stone".to_string(), 150)]);
+
+        let totals = plan_deposit(&current, &amounts, &capacities, &rules);
+
+        assert_eq!(totals.get("wood"), Some(&100));
+        assert_eq!(totals.get("charcoal"), Some(&20));
+        assert_eq!(totals.get("stone"), Some(&100));
+    }
+
+    #[test]
+    fn test_plan_deposit_keeps_stock_above_capacity() {
+        let rules = rules();
+        let capacities = rules
+            .values()
+            .map(|r| (r.slug.clone(), r.base_capacity))
+            .collect::<HashMap<_, _>>();
+        let current = HashMap::from([("wood".to_string(), 150), ("charcoal".to_string(), 120)]);
+        let amounts = HashMap::from([("wood".to_string(), 10)]);
+
+        let totals = plan_deposit(&current, &amounts, &capacities, &rules);
+
+        assert_eq!(totals.get("wood"), Some(&150));
+        assert_eq!(totals.get("charcoal"), Some(&120));
+    }
+}
@@ -0,0 +1,202 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use crate::auth::model::AuthTokens;
+use crate::auth::permission::{AccountState, AccountStatus, Role};
+use crate::auth::token::{self, TokenClaims, ACCESS_TOKEN_LIFETIME_MINUTES, SESSION_LIFETIME_DAYS};
+use crate::model::{AccountSession, SessionInfo};
+
+use super::user_repository;
+
+// Sessions expire in real time like the tokens bound to them, not on the game clock.
+
+pub enum RefreshOutcome {
+    Refreshed(AuthTokens),
+    /// An already rotated refresh token was presented again, the whole family was revoked.
+    Reused(Uuid),
+    /// The account was suspended or banned, the token is left as is.
+    Blocked(AccountStatus),
+    Invalid,
+}
+
+/// Starts a session family for the account and returns its first pair of tokens. Only a hash
+/// of the refresh token is stored.
+pub async fn open_session(
+    conn: &mut PgConnection,
+    account: Uuid,
+    role: Role,
+) -> Result<AuthTokens, anyhow::Error> {
+    use crate::schema::account_sessions::dsl::*;
+
+    let session = Uuid::new_v4();
+    let now = chrono::Utc::now();
+    let expires = now + chrono::Duration::days(SESSION_LIFETIME_DAYS);
+    let refresh_token = token::generate_refresh_token();
+
+    diesel::insert_into(account_sessions)
+        .values((
+            id.eq(session),
+            family_id.eq(session),
+            account_id.eq(account),
+            session_token.eq(token::hash_token(&refresh_token)),
+            created_at.eq(Some(now.naive_utc())),
+            updated_at.eq(Some(now.naive_utc())),
+            expires_at.eq(Some(expires.naive_utc())),
+        ))
+        .execute(conn)?;
+
+    issue_tokens(
+        TokenClaims {
+            account_id: account,
+            session_id: session,
+            role,
+            scope: None,
+        },
+        refresh_token,
+    )
+}
+
+/// Exchanges the refresh token with the given hash for a new pair. The presented token is
+/// marked as rotated, so a second use of it, by a thief or by the client it was stolen from,
+/// revokes the family.
+pub async fn refresh_session(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<RefreshOutcome, anyhow::Error> {
+    use crate::schema::account_sessions::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let Some(current) = account_sessions
+        .filter(session_token.eq(token_hash))
+        .select(AccountSession::as_select())
+        .first(conn)
+        .optional()?
+    else {
+        return Ok(RefreshOutcome::Invalid);
+    };
+
+    if current.expires_at.is_some_and(|expires| expires <= now) {
+        return Ok(RefreshOutcome::Invalid);
+    }
+
+    // refreshed tokens carry the current role, and blocked accounts get none
+    let Some(status) = user_repository::get_account_status(conn, current.account_id).await? else {
+        return Ok(RefreshOutcome::Invalid);
+    };
+    if status.state != AccountState::Active {
+        return Ok(RefreshOutcome::Blocked(status));
+    }
+
+    let next_token = token::generate_refresh_token();
+    let rotated = conn.transaction(|conn| {
+        // only one of two concurrent refreshes with the same token can rotate it
+        let rotated = diesel::update(
+            account_sessions
+                .find(current.id)
+                .filter(rotated_at.is_null()),
+        )
+        .set(rotated_at.eq(Some(now)))
+        .execute(conn)?;
+
+        if rotated == 1 {
+            diesel::insert_into(account_sessions)
+                .values((
+                    id.eq(Uuid::new_v4()),
+                    family_id.eq(current.family_id),
+                    account_id.eq(current.account_id),
+                    session_token.eq(token::hash_token(&next_token)),
+                    created_at.eq(current.created_at),
+                    updated_at.eq(Some(now)),
+                    expires_at.eq(current.expires_at),
+                ))
+                .execute(conn)?;
+        }
+
+        QueryResult::Ok(rotated == 1)
+    })?;
+
+    if !rotated {
+        diesel::delete(account_sessions.filter(family_id.eq(current.family_id))).execute(conn)?;
+        return Ok(RefreshOutcome::Reused(current.family_id));
+    }
+
+    Ok(RefreshOutcome::Refreshed(issue_tokens(
+        TokenClaims {
+            account_id: current.account_id,
+            session_id: current.family_id,
+            role: status.role,
+            scope: None,
+        },
+        next_token,
+    )?))
+}
+
+fn issue_tokens(claims: TokenClaims, refresh_token: String) -> Result<AuthTokens, anyhow::Error> {
+    let expires = chrono::Utc::now() + chrono::Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES);
+
+    Ok(AuthTokens {
+        access_token: token::generate_token(&claims, expires)?,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_LIFETIME_MINUTES * 60,
+    })
+}
+
+pub async fn get_active_session(
+    conn: &mut PgConnection,
+    session: Uuid,
+) -> QueryResult<Option<SessionInfo>> {
+    use crate::schema::account_sessions::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    account_sessions
+        .filter(family_id.eq(session))
+        .filter(rotated_at.is_null())
+        .filter(expires_at.is_null().or(expires_at.gt(now)))
+        .select(SessionInfo::as_select())
+        .first(conn)
+        .optional()
+}
+
+pub async fn get_sessions(conn: &mut PgConnection, account: Uuid) -> QueryResult<Vec<SessionInfo>> {
+    use crate::schema::account_sessions::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    account_sessions
+        .filter(account_id.eq(account))
+        .filter(rotated_at.is_null())
+        .filter(expires_at.is_null().or(expires_at.gt(now)))
+        .order(created_at.desc())
+        .select(SessionInfo::as_select())
+        .load(conn)
+}
+
+/// Deletes one session family of the account, or all of them when `session` is `None`,
+/// returning the ids of the deleted families.
+pub async fn revoke_sessions(
+    conn: &mut PgConnection,
+    account: Uuid,
+    session: Option<Uuid>,
+) -> QueryResult<Vec<Uuid>> {
+    use crate::schema::account_sessions::dsl::*;
+
+    let mut revoked: Vec<Uuid> = match session {
+        Some(session) => diesel::delete(
+            account_sessions
+                .filter(account_id.eq(account))
+                .filter(family_id.eq(session)),
+        )
+        .returning(family_id)
+        .get_results(conn)?,
+        None => diesel::delete(account_sessions.filter(account_id.eq(account)))
+            .returning(family_id)
+            .get_results(conn)?,
+    };
+    revoked.sort();
+    revoked.dedup();
+
+    Ok(revoked)
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use super::{blueprint_repository, resource_repository};
+use crate::error::InventoryError;
+use crate::model::{BlueprintProperties, InventoryBuilding, UpkeepReport};
+
+/// Charges the upkeep due at tick `seq` and wears down buildings that can't be paid for.
+pub async fn apply_upkeep(
+    conn: &mut PgConnection,
+    inventory: Uuid,
+    seq: u64,
+) -> Result<UpkeepReport, InventoryError> {
+    use crate::schema::inventories_x_buildings::dsl::*;
+
+    conn.transaction(|conn| {
+        let mut buildings = blueprint_repository::get_inventory_buildings(conn, inventory)?;
+        buildings.sort_by_key(|b| b.created_at);
+
+        let blueprints = blueprint_repository::get_all_properties(conn)?;
+        let quantities = resource_repository::load_quantities(conn, inventory)?;
+
+        let report = plan_upkeep(&buildings, &blueprints, &quantities, seq);
+
+        resource_repository::withdraw(conn, inventory, &report.paid)?;
+
+        for (building, degraded) in &report.degraded {
+            diesel::update(inventories_x_buildings.find(building))
+                .set((
+                    condition.eq(degraded),
+                    updated_at.eq(crate::clock::now().naive_utc()),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(report)
+    })
+}
+
+/// Works out which finished buildings owe upkeep this tick and whether the inventory can pay it,
+/// oldest building first.
+fn plan_upkeep(
+    buildings: &[InventoryBuilding],
+    blueprints: &HashMap<String, BlueprintProperties>,
+    quantities: &HashMap<String, i32>,
+    seq: u64,
+) -> UpkeepReport {
+    let mut available = quantities.clone();
+    let mut report = UpkeepReport::default();
+
+    for building in buildings.iter().filter(|b| b.built_level().is_some()) {
+        let Some(upkeep) = blueprints
+            .get(&building.blueprint_slug)
+            .and_then(|properties| properties.upkeep.as_ref())
+        else {
+            continue;
+        };
+
+        if upkeep.interval == 0 || !seq.is_multiple_of(upkeep.interval) {
+            continue;
+        }
+
+        let affordable = upkeep
+            .resources
+            .iter()
+            .all(|(slug, amount)| available.get(slug).copied().unwrap_or_default() >= *amount);
+
+        if affordable {
+            for (slug, amount) in upkeep.resources.iter().filter(|(_, amount)| **amount > 0) {
+                *available.entry(slug.clone()).or_default() -= amount;
+                *report.paid.entry(slug.clone()).or_default() += amount;
+            }
+        } else {
+            let degraded = (building.condition - upkeep.decay).max(0);
+            if degraded != building.condition {
+                report.degraded.insert(building.id, degraded);
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_upkeep_pays_then_degrades() {
+        let blueprints = HashMap::from([(
+            "farm".to_string(),
+            serde_json::from_value::<BlueprintProperties>(serde_json::json!({
+                "upkeep": { "resources": { "gold": 5 }, "interval": 10, "decay": 25 }
+            }))
+            .unwrap(),
+        )]);
+        let buildings = vec![
+            InventoryBuilding::fixture("farm", "completed", 1),
+            InventoryBuilding::fixture("farm", "completed", 1),
+            InventoryBuilding::fixture("farm", "in_progress", 1),
+        ];
+        let quantities = HashMap::from([("gold".to_string(), 7)]);
+
+        let report = plan_upkeep(&buildings, &blueprints, &quantities, 20);
+        assert_eq!(report.paid.get("gold"), Some(&5));
+        assert_eq!(report.degraded.get(&buildings[1].id), Some(&75));
+        assert_eq!(report.degraded.len(), 1);
+
+        let report = plan_upkeep(&buildings, &blueprints, &quantities, 21);
+        assert!(report.paid.is_empty());
+        assert!(report.degraded.is_empty());
+    }
+
+    #[test]
+    fn test_plan_upkeep_skips_ruined_buildings() {
+        let blueprints = HashMap::from([(
+            "farm".to_string(),
+            serde_json::from_value::<BlueprintProperties>(serde_json::json!({
+                "upkeep": { "resources": { "gold": 5 }, "interval": 10, "decay": 25 }
+            }))
+            .unwrap(),
+        )]);
+        let mut ruined = InventoryBuilding::fixture("farm", "completed", 1);
+        ruined.condition = 0;
+        let buildings = vec![ruined];
+
+        let report = plan_upkeep(&buildings, &blueprints, &HashMap::new(), 20);
+        assert!(report.paid.is_empty());
+        assert!(report.degraded.is_empty());
+    }
+}
@@ -1,60 +1,213 @@
 use diesel::prelude::*;
 use diesel::PgConnection;
-use sha2::Digest;
-use sha2::Sha256;
 
-pub fn hash_str(input: &str) -> String {
-    format!("{:x}", Sha256::digest(input))
-}
+use crate::auth::password::{hash_password, verify_password, verify_unknown, Verification};
+use crate::auth::permission::{AccountState, AccountStatus, Role};
+use crate::auth::token::TokenClaims;
+use crate::error::AccountError;
+use crate::model::AccountRequest;
 
-pub async fn _get_id_by_auth(
+/// Creates the account together with its starter inventory, returning both of their ids.
+pub async fn create_account(
     conn: &mut PgConnection,
-    auth: &str,
-) -> Result<Option<uuid::Uuid>, diesel::result::Error> {
-    use crate::schema::account_sessions::dsl::*;
+    account: &AccountRequest,
+) -> Result<(uuid::Uuid, uuid::Uuid), AccountError> {
+    use crate::schema::{accounts, accounts_x_inventories, inventories};
+
+    let hash =
+        hash_password(&account.password).map_err(|e| AccountError::Internal(e.to_string()))?;
+
+    conn.transaction(|conn| {
+        let account_id = diesel::insert_into(accounts::table)
+            .values((
+                accounts::username.eq(&account.username),
+                accounts::password_hash.eq(&hash),
+                accounts::email.eq(&account.email),
+            ))
+            .returning(accounts::id)
+            .get_result::<uuid::Uuid>(conn)?;
+
+        let inventory_id = diesel::insert_into(inventories::table)
+            .default_values()
+            .returning(inventories::id)
+            .get_result::<uuid::Uuid>(conn)?;
+
+        diesel::insert_into(accounts_x_inventories::table)
+            .values((
+                accounts_x_inventories::account_id.eq(account_id),
+                accounts_x_inventories::inventory_id.eq(inventory_id),
+            ))
+            .execute(conn)?;
+
+        Ok((account_id, inventory_id))
+    })
+}
 
-    let result = account_sessions
-        .filter(session_token.eq(auth))
-        .select(id)
-        .first::<uuid::Uuid>(conn)
-        .optional()?;
+/// Checks the password and returns the role and state of the account, whether it may log in
+/// is up to the caller. Unknown usernames cost a password verification too, so the response
+/// time doesn't tell which accounts exist.
+pub async fn authenticate(
+    conn: &mut PgConnection,
+    user: &str,
+    password: &str,
+) -> Result<Option<AccountStatus>, diesel::result::Error> {
+    use crate::schema::accounts::dsl::*;
 
-    Ok(result)
+    let Some((user_id, stored, account_role, account_state, reason)) = accounts
+        .filter(username.eq(user))
+        .select((id, password_hash, role, state, state_reason))
+        .first::<(uuid::Uuid, String, String, String, Option<String>)>(conn)
+        .optional()?
+    else {
+        verify_unknown(password);
+        return Ok(None);
+    };
+
+    match verify_password(password, &stored) {
+        Verification::Invalid => return Ok(None),
+        Verification::Valid => {}
+        Verification::NeedsRehash => {
+            // failing to upgrade the hash shouldn't fail the login, it is retried next time
+            match hash_password(password) {
+                Ok(hash) => {
+                    diesel::update(accounts.find(user_id))
+                        .set((
+                            password_hash.eq(hash),
+                            updated_at.eq(Some(crate::clock::now().naive_utc())),
+                        ))
+                        .execute(conn)?;
+                    tracing::info!(user_id = user_id.to_string(), "upgraded password hash");
+                }
+                Err(e) => tracing::warn!(
+                    user_id = user_id.to_string(),
+                    "failed to upgrade password hash: {}",
+                    e
+                ),
+            }
+        }
+    }
+
+    account_status(user_id, &account_role, &account_state, reason).map(Some)
 }
 
-pub async fn _save_account(conn: &mut PgConnection, account: &crate::model::AccountRequest) {
-    use crate::schema::accounts;
+pub async fn get_account_status(
+    conn: &mut PgConnection,
+    account: uuid::Uuid,
+) -> Result<Option<AccountStatus>, diesel::result::Error> {
+    use crate::schema::accounts::dsl::*;
+
+    let Some((account_role, account_state, reason)) = accounts
+        .find(account)
+        .select((role, state, state_reason))
+        .first::<(String, String, Option<String>)>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
 
-    let hash = hash_str(&account.password);
+    account_status(account, &account_role, &account_state, reason).map(Some)
+}
 
-    let new_account = (
-        accounts::username.eq(&account.username),
-        accounts::password_hash.eq(&hash),
-        accounts::email.eq(&account.email),
-    );
+/// Changes the state of an account the caller outranks, if the caller may move it out of its
+/// current state too. The reason is cleared on reactivation.
+pub async fn set_account_state(
+    conn: &mut PgConnection,
+    caller: TokenClaims,
+    account: uuid::Uuid,
+    new_state: AccountState,
+    reason: Option<String>,
+) -> Result<AccountStatus, AccountError> {
+    use crate::schema::accounts::dsl::*;
 
-    diesel::insert_into(accounts::table)
-        .values(&new_account)
-        .execute(conn)
-        .expect("Error saving new account");
+    let current = outranked_account(conn, caller.role, account).await?;
+    let permission = current.state.transition_permission(new_state);
+    if !caller.can(permission) {
+        return Err(AccountError::Forbidden(format!(
+            "changing a {} account needs the {} permission",
+            current.state, permission
+        )));
+    }
+    let reason = reason.filter(|_| new_state != AccountState::Active);
+
+    diesel::update(accounts.find(account))
+        .set((
+            state.eq(new_state.as_str()),
+            state_reason.eq(&reason),
+            updated_at.eq(Some(crate::clock::now().naive_utc())),
+        ))
+        .execute(conn)?;
+
+    Ok(AccountStatus {
+        state: new_state,
+        reason,
+        ..current
+    })
 }
 
-pub async fn authenticate(
+/// Gives an account the caller outranks a role below the caller's own.
+pub async fn set_account_role(
     conn: &mut PgConnection,
-    user: &str,
-    password: &str,
-) -> Result<Option<uuid::Uuid>, diesel::result::Error> {
+    caller: Role,
+    account: uuid::Uuid,
+    new_role: Role,
+) -> Result<AccountStatus, AccountError> {
     use crate::schema::accounts::dsl::*;
 
-    let hash = hash_str(password);
+    if new_role >= caller {
+        return Err(AccountError::Forbidden(format!(
+            "a {} cannot grant the {} role",
+            caller, new_role
+        )));
+    }
+
+    let current = outranked_account(conn, caller, account).await?;
+
+    diesel::update(accounts.find(account))
+        .set((
+            role.eq(new_role.as_str()),
+            updated_at.eq(Some(crate::clock::now().naive_utc())),
+        ))
+        .execute(conn)?;
+
+    Ok(AccountStatus {
+        role: new_role,
+        ..current
+    })
+}
 
-    let user_id = accounts
-        .filter(username.eq(user))
-        .filter(password_hash.eq(hash)) // In real code, use hashed passwords!
-        .select(id)
-        .first::<uuid::Uuid>(conn)
-        .optional()?;
-    Ok(user_id)
+async fn outranked_account(
+    conn: &mut PgConnection,
+    caller: Role,
+    account: uuid::Uuid,
+) -> Result<AccountStatus, AccountError> {
+    let current = get_account_status(conn, account)
+        .await?
+        .ok_or(AccountError::NotFound)?;
+
+    if current.role >= caller {
+        return Err(AccountError::Forbidden(format!(
+            "a {} cannot manage a {} account",
+            caller, current.role
+        )));
+    }
+
+    Ok(current)
+}
+
+fn account_status(
+    account: uuid::Uuid,
+    account_role: &str,
+    account_state: &str,
+    reason: Option<String>,
+) -> Result<AccountStatus, diesel::result::Error> {
+    let parse_error = |e: anyhow::Error| diesel::result::Error::DeserializationError(e.into());
+
+    Ok(AccountStatus {
+        account_id: account,
+        role: account_role.parse().map_err(parse_error)?,
+        state: account_state.parse().map_err(parse_error)?,
+        reason,
+    })
 }
 
 pub async fn get_inventory_id_for_user(
@@ -0,0 +1,28 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use crate::model::WorldSettings;
+
+pub async fn get_world_settings(conn: &mut PgConnection) -> QueryResult<WorldSettings> {
+    use crate::schema::world_settings::dsl::*;
+
+    world_settings
+        .select(WorldSettings::as_select())
+        .first(conn)
+}
+
+/// Records the last tick sent so the ticker can pick up from it after a restart.
+pub async fn save_tick(
+    conn: &mut PgConnection,
+    seq: u64,
+    timestamp: chrono::DateTime<chrono::Utc>,
+) -> QueryResult<usize> {
+    use crate::schema::world_settings::dsl::*;
+
+    diesel::update(world_settings)
+        .set((
+            tick_seq.eq(seq as i64),
+            last_tick_at.eq(Some(timestamp.naive_utc())),
+        ))
+        .execute(conn)
+}
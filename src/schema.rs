@@ -9,6 +9,22 @@ diesel::table! {
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
         expires_at -> Nullable<Timestamp>,
+        family_id -> Uuid,
+        rotated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    account_tokens (id) {
+        id -> Uuid,
+        account_id -> Uuid,
+        #[max_length = 20]
+        purpose -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
     }
 }
 
@@ -25,6 +41,10 @@ diesel::table! {
         state -> Varchar,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        #[max_length = 20]
+        role -> Varchar,
+        state_reason -> Nullable<Text>,
+        email_verified_at -> Nullable<Timestamp>,
     }
 }
 
@@ -35,6 +55,24 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    api_keys (id) {
+        id -> Uuid,
+        account_id -> Uuid,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 16]
+        prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     blueprints (slug) {
         slug -> Text,
@@ -50,6 +88,7 @@ diesel::table! {
         id -> Uuid,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        construction_slots -> Int4,
     }
 }
 
@@ -62,6 +101,9 @@ diesel::table! {
         progress -> Int4,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        queue_position -> Nullable<Int4>,
+        level -> Int4,
+        condition -> Int4,
     }
 }
 
@@ -73,28 +115,84 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    inventories_x_techs (inventory_id, tech_slug) {
+        inventory_id -> Uuid,
+        tech_slug -> Text,
+        researched_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    inventory_policies (id) {
+        id -> Uuid,
+        inventory_id -> Uuid,
+        name -> Text,
+        rule -> Jsonb,
+        interval_ticks -> Int4,
+        enabled -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     resources (slug) {
         slug -> Text,
         name -> Text,
+        base_capacity -> Int4,
+        overflow_resource -> Nullable<Text>,
+        overflow_rate -> Float4,
+    }
+}
+
+diesel::table! {
+    techs (slug) {
+        slug -> Text,
+        name -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    world_settings (id) {
+        id -> Int4,
+        tick_seq -> Int8,
+        last_tick_at -> Nullable<Timestamptz>,
+        catch_up_mode -> Text,
+        catch_up_batch -> Int4,
+        max_catch_up_ticks -> Int8,
+        ticks_per_day -> Int4,
+        days_per_season -> Int4,
     }
 }
 
 diesel::joinable!(account_sessions -> accounts (account_id));
+diesel::joinable!(account_tokens -> accounts (account_id));
 diesel::joinable!(accounts_x_inventories -> accounts (account_id));
 diesel::joinable!(accounts_x_inventories -> inventories (inventory_id));
+diesel::joinable!(api_keys -> accounts (account_id));
 diesel::joinable!(inventories_x_buildings -> blueprints (blueprint_slug));
 diesel::joinable!(inventories_x_buildings -> inventories (inventory_id));
 diesel::joinable!(inventories_x_resources -> inventories (inventory_id));
 diesel::joinable!(inventories_x_resources -> resources (resource));
+diesel::joinable!(inventories_x_techs -> inventories (inventory_id));
+diesel::joinable!(inventories_x_techs -> techs (tech_slug));
+diesel::joinable!(inventory_policies -> inventories (inventory_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     account_sessions,
+    account_tokens,
     accounts,
     accounts_x_inventories,
+    api_keys,
     blueprints,
     inventories,
     inventories_x_buildings,
     inventories_x_resources,
+    inventories_x_techs,
+    inventory_policies,
     resources,
+    techs,
+    world_settings,
 );
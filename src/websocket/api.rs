@@ -1,4 +1,4 @@
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
 use futures_util::SinkExt;
 use tokio::sync::mpsc;
 use uuid::Uuid;
@@ -8,10 +8,12 @@ use futures::StreamExt;
 use tokio_stream::wrappers::ReceiverStream;
 
 use crate::{
+    auth::token::TokenClaims,
     messaging::{
         broker::MessageBroker,
         model::{Message as BusMessage, MessageBody},
     },
+    model::{BuildingCommand, DEFAULT_POLICY_INTERVAL},
     websocket::model::{RtcRequest, RtcRequestBody, RtcResponse},
 };
 
@@ -69,7 +71,13 @@ impl Bouncer {
         }
     }
 
-    pub async fn handle_connection(self, user_id: Uuid, ws: WebSocket) {
+    pub async fn handle_connection(self, claims: TokenClaims, ws: WebSocket) {
+        let TokenClaims {
+            account_id: user_id,
+            session_id,
+            role,
+            ..
+        } = claims;
         tracing::info!(user_id = user_id.to_string(), "new WebSocket connection");
 
         let (mut sink, mut stream) = ws.split();
@@ -101,7 +109,7 @@ impl Bouncer {
 
         let (internal_sink_tx, mut internal_sink_rx) = mpsc::channel::<RtcResponse>(100);
 
-        let (_, global_rx) = match self.broker.subscribe("global").await {
+        let (global_sub_id, global_rx) = match self.broker.subscribe("global").await {
             Ok(id) => id,
             Err(e) => {
                 tracing::error!("Failed to subscribe to global topic: {}", e);
@@ -115,7 +123,7 @@ impl Bouncer {
                 return;
             }
         };
-        let (_, account_rx) = match self
+        let (account_sub_id, account_rx) = match self
             .broker
             .subscribe(&format!("out:account:{user_id}"))
             .await
@@ -133,7 +141,7 @@ impl Bouncer {
                 return;
             }
         };
-        let (_, inventory_rx) = match self
+        let (inventory_sub_id, inventory_rx) = match self
             .broker
             .subscribe(&format!("out:inventory:{inventory_id}"))
             .await
@@ -152,6 +160,26 @@ impl Bouncer {
             }
         };
 
+        // revoking the session closes the connection
+        let (session_sub_id, mut session_rx) = match self
+            .broker
+            .subscribe(&format!("out:session:{session_id}"))
+            .await
+        {
+            Ok(id) => id,
+            Err(e) => {
+                tracing::error!("Failed to subscribe to session topic: {}", e);
+                sink.send(Message::Text(
+                    format!("failed to subscribe to session topic: {}", e).into(),
+                ))
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("Failed to send error message: {}", e);
+                });
+                return;
+            }
+        };
+
         let combined_tx = internal_sink_tx.clone();
         let combined_stream = select_all(vec![
             ReceiverStream::new(global_rx),
@@ -171,6 +199,7 @@ impl Bouncer {
                             id: Uuid::new_v4(),
                             success: false,
                             message: Some(format!("Internal server error: {}", e)),
+                            data: None,
                         }
                     }
                 };
@@ -182,8 +211,6 @@ impl Bouncer {
         });
 
         let outgoing = tokio::spawn(async move {
-            let sink = &mut sink;
-
             while let Some(msg) = internal_sink_rx.recv().await {
                 if let Ok(text) = serde_json::to_string(&msg) {
                     sink.send(Message::Text(text.into()))
@@ -193,10 +220,29 @@ impl Bouncer {
                         });
                 }
             }
+
+            sink
         });
 
         let internal_tx = internal_sink_tx.clone();
-        while let Some(Ok(message)) = stream.next().await {
+        let mut revoked = false;
+        loop {
+            let message = tokio::select! {
+                message = stream.next() => match message {
+                    Some(Ok(message)) => message,
+                    _ => break,
+                },
+                Some(_) = session_rx.recv() => {
+                    tracing::info!(
+                        user_id = user_id.to_string(),
+                        session_id = session_id.to_string(),
+                        "session revoked, closing WebSocket connection"
+                    );
+                    revoked = true;
+                    break;
+                }
+            };
+
             if let Message::Text(msg) = message {
                 let request = match serde_json::from_str::<RtcRequest>(&msg) {
                     Ok(req) => req,
@@ -207,6 +253,7 @@ impl Bouncer {
                                 id: Uuid::new_v4(),
                                 success: false,
                                 message: Some(format!("Invalid request format: {}", e)),
+                                data: None,
                             })
                             .await
                             .unwrap_or_else(|e| {
@@ -223,16 +270,103 @@ impl Bouncer {
                     "received RTC request"
                 );
 
-                let message = match request.body {
-                    RtcRequestBody::Build { blueprint } => BusMessage::new(
-                        MessageBody::BuildRequest {
+                let permission = request.body.permission();
+                if !claims.can(permission) {
+                    tracing::warn!(
+                        user_id = user_id.to_string(),
+                        role = role.as_str(),
+                        "refused RTC request without permission {}",
+                        permission
+                    );
+                    internal_tx
+                        .send(RtcResponse {
+                            id: Uuid::new_v4(),
+                            success: false,
+                            message: Some(format!("missing permission {}", permission)),
+                            data: None,
+                        })
+                        .await
+                        .unwrap_or_else(|e| {
+                            tracing::error!("Failed to send error message: {}", e);
+                        });
+                    continue;
+                }
+
+                let body = match request.body {
+                    RtcRequestBody::Build { blueprint } => MessageBody::BuildRequest {
+                        inventory_id,
+                        blueprint_slug: blueprint,
+                    },
+                    RtcRequestBody::Queue {} => MessageBody::BuildQueueRequest { inventory_id },
+                    RtcRequestBody::Reorder { building, position } => {
+                        MessageBody::ReorderBuildQueueRequest {
                             inventory_id,
-                            blueprint_slug: blueprint,
-                        },
-                        Some(format!("in:inventory:{}", inventory_id)),
-                        true,
-                    ),
+                            building_id: building,
+                            position,
+                        }
+                    }
+                    RtcRequestBody::Dequeue { building } => {
+                        MessageBody::RemoveFromBuildQueueRequest {
+                            inventory_id,
+                            building_id: building,
+                        }
+                    }
+                    RtcRequestBody::Cancel { building } => MessageBody::BuildingCommandRequest {
+                        inventory_id,
+                        building_id: building,
+                        command: BuildingCommand::Cancel,
+                    },
+                    RtcRequestBody::Pause { building } => MessageBody::BuildingCommandRequest {
+                        inventory_id,
+                        building_id: building,
+                        command: BuildingCommand::Pause,
+                    },
+                    RtcRequestBody::Resume { building } => MessageBody::BuildingCommandRequest {
+                        inventory_id,
+                        building_id: building,
+                        command: BuildingCommand::Resume,
+                    },
+                    RtcRequestBody::Demolish { building } => MessageBody::BuildingCommandRequest {
+                        inventory_id,
+                        building_id: building,
+                        command: BuildingCommand::Demolish,
+                    },
+                    RtcRequestBody::Upgrade { building } => MessageBody::BuildingCommandRequest {
+                        inventory_id,
+                        building_id: building,
+                        command: BuildingCommand::Upgrade,
+                    },
+                    RtcRequestBody::Repair { building } => MessageBody::BuildingCommandRequest {
+                        inventory_id,
+                        building_id: building,
+                        command: BuildingCommand::Repair,
+                    },
+                    RtcRequestBody::Blueprints {} => {
+                        MessageBody::BlueprintsRequest { inventory_id }
+                    }
+                    RtcRequestBody::Resources {} => MessageBody::ResourcesRequest { inventory_id },
+                    RtcRequestBody::Policies {} => MessageBody::PoliciesRequest { inventory_id },
+                    RtcRequestBody::AddPolicy {
+                        name,
+                        rule,
+                        interval,
+                    } => MessageBody::CreatePolicyRequest {
+                        inventory_id,
+                        name,
+                        rule,
+                        interval_ticks: interval.unwrap_or(DEFAULT_POLICY_INTERVAL),
+                    },
+                    RtcRequestBody::RemovePolicy { policy } => MessageBody::DeletePolicyRequest {
+                        inventory_id,
+                        policy_id: policy,
+                    },
+                    RtcRequestBody::DryRunPolicy { policy } => MessageBody::PolicyDryRunRequest {
+                        inventory_id,
+                        policy_id: policy,
+                    },
                 };
+                let message =
+                    BusMessage::new(body, Some(format!("in:inventory:{}", inventory_id)), true);
 
                 match self.broker.request(message).await {
                     Ok(result) => {
@@ -258,6 +392,7 @@ impl Bouncer {
                                             id: Uuid::new_v4(),
                                             success: false,
                                             message: Some(format!("Internal server error: {}", e)),
+                                            data: None,
                                         })
                                         .await
                                         .unwrap_or_else(|e| {
@@ -276,6 +411,7 @@ impl Bouncer {
                                 id: Uuid::new_v4(),
                                 success: false,
                                 message: Some(format!("Invalid request format: {}", e)),
+                                data: None,
                             })
                             .await
                             .unwrap_or_else(|e| {
@@ -289,8 +425,32 @@ impl Bouncer {
             }
         }
 
-        let _ = outgoing.await;
+        combiner.abort();
         let _ = combiner.await;
+        for sub_id in [
+            global_sub_id,
+            account_sub_id,
+            inventory_sub_id,
+            session_sub_id,
+        ] {
+            let _ = self.broker.unsubscribe(sub_id).await;
+        }
+
+        // the outgoing task finishes once every sender is gone
+        drop(internal_tx);
+        drop(internal_sink_tx);
+        if let Ok(mut sink) = outgoing.await {
+            if revoked {
+                sink.send(Message::Close(Some(CloseFrame {
+                    code: close_code::POLICY,
+                    reason: "session revoked".into(),
+                })))
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("Failed to send close message: {}", e);
+                });
+            }
+        }
     }
 }
 
@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+
+use crate::{
+    auth::token::{self, TokenClaims},
+    error::AuthError,
+    messaging::{
+        broker::MessageBroker,
+        model::{Message as BusMessage, MessageBody},
+    },
+};
+
+/// The subprotocol the server selects, browsers passing the token as a subprotocol must offer
+/// it too since they fail the handshake when none of theirs is selected.
+pub const PROTOCOL: &str = "an-daghdha.v1";
+/// Offered next to `PROTOCOL` as `bearer.<access token>`, never echoed back.
+const BEARER_PROTOCOL_PREFIX: &str = "bearer.";
+/// How long a connection without credentials in the handshake has to send its first frame.
+pub const AUTHENTICATION_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Query of `GET /rtc`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ConnectParams {
+    pub ticket: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Credentials {
+    Token(String),
+    Ticket(String),
+    /// Nothing in the handshake, the first frame must be an `Authentication`.
+    FirstFrame,
+}
+
+/// First frame of a connection opened without credentials, `{"token": "..."}` or
+/// `{"ticket": "..."}`.
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Authentication {
+    Token(String),
+    Ticket(String),
+}
+
+impl From<Authentication> for Credentials {
+    fn from(value: Authentication) -> Self {
+        match value {
+            Authentication::Token(token) => Credentials::Token(token),
+            Authentication::Ticket(ticket) => Credentials::Ticket(ticket),
+        }
+    }
+}
+
+/// Picks the credentials of the handshake, the `Authorization` header first, then a ticket, then
+/// a bearer subprotocol.
+pub fn credentials(headers: &HeaderMap, params: &ConnectParams) -> Credentials {
+    if let Ok(token) = token::bearer_token(headers) {
+        return Credentials::Token(token.to_string());
+    }
+    if let Some(ticket) = &params.ticket {
+        return Credentials::Ticket(ticket.clone());
+    }
+    match protocol_token(headers) {
+        Some(token) => Credentials::Token(token),
+        None => Credentials::FirstFrame,
+    }
+}
+
+fn protocol_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|protocol| protocol.trim().strip_prefix(BEARER_PROTOCOL_PREFIX))
+        .map(str::to_string)
+}
+
+/// Validates the credentials like `token::validate_headers` does the header, waiting up
+/// to `AUTHENTICATION_DEADLINE` for the first frame when the handshake had none.
+pub async fn authenticate(
+    ws: &mut WebSocket,
+    credentials: Credentials,
+    broker: &MessageBroker,
+) -> Result<TokenClaims, anyhow::Error> {
+    let credentials = match credentials {
+        Credentials::FirstFrame => {
+            tokio::time::timeout(AUTHENTICATION_DEADLINE, first_frame(ws)).await??
+        }
+        credentials => credentials,
+    };
+
+    match credentials {
+        Credentials::Token(token) => token::validate_bearer(&token, broker).await,
+        Credentials::Ticket(ticket) => {
+            token::validate_session(redeem(&ticket, broker).await?, broker).await
+        }
+        Credentials::FirstFrame => Err(anyhow::anyhow!("Missing credentials")),
+    }
+}
+
+async fn first_frame(ws: &mut WebSocket) -> Result<Credentials, anyhow::Error> {
+    loop {
+        match ws.recv().await {
+            Some(Ok(Message::Text(text))) => {
+                return serde_json::from_str::<Authentication>(&text)
+                    .map(Credentials::from)
+                    .map_err(|e| anyhow::anyhow!("Invalid authentication frame: {}", e));
+            }
+            // pings are answered by axum
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+            Some(Ok(_)) => return Err(anyhow::anyhow!("Expected an authentication frame")),
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(anyhow::anyhow!("Connection closed before authenticating")),
+        }
+    }
+}
+
+async fn redeem(ticket: &str, broker: &MessageBroker) -> Result<TokenClaims, anyhow::Error> {
+    let response = broker
+        .request(BusMessage::new(
+            MessageBody::RedeemTicketRequest {
+                ticket_hash: token::hash_token(ticket),
+            },
+            Some("auth".to_string()),
+            true,
+        ))
+        .await?;
+
+    match response.map(|msg| msg.body) {
+        Some(MessageBody::RedeemTicketResponse(Some(claims))) => Ok(claims),
+        Some(MessageBody::RedeemTicketResponse(None)) => {
+            Err(anyhow::anyhow!("Unknown, expired or used ticket"))
+        }
+        _ => Err(anyhow::anyhow!("Unexpected response to ticket redemption")),
+    }
+}
+
+/// Closes a connection that failed to authenticate. Blocked accounts are told why, with the same
+/// close code as revoked sessions.
+pub async fn refuse(ws: &mut WebSocket, err: &anyhow::Error) {
+    let reason = match err.downcast_ref::<AuthError>() {
+        Some(err @ AuthError::AccountBlocked { .. }) => err.to_string(),
+        _ if err.is::<tokio::time::error::Elapsed>() => "authentication timed out".to_string(),
+        _ => "unauthorized".to_string(),
+    };
+
+    ws.send(Message::Close(Some(CloseFrame {
+        code: close_code::POLICY,
+        reason: reason.into(),
+    })))
+    .await
+    .unwrap_or_else(|e| {
+        tracing::error!("Failed to close unauthenticated connection: {}", e);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn params(ticket: Option<&str>) -> ConnectParams {
+        ConnectParams {
+            ticket: ticket.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_credentials_precedence() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static("an-daghdha.v1, bearer.v4.local.protocol"),
+        );
+        assert_eq!(
+            credentials(&headers, &params(None)),
+            Credentials::Token("v4.local.protocol".into())
+        );
+        assert_eq!(
+            credentials(&headers, &params(Some("abc"))),
+            Credentials::Ticket("abc".into())
+        );
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer v4.local.header"),
+        );
+        assert_eq!(
+            credentials(&headers, &params(Some("abc"))),
+            Credentials::Token("v4.local.header".into())
+        );
+
+        assert_eq!(
+            credentials(&HeaderMap::new(), &params(None)),
+            Credentials::FirstFrame
+        );
+    }
+
+    #[test]
+    fn test_protocol_without_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(PROTOCOL),
+        );
+        assert_eq!(protocol_token(&headers), None);
+    }
+
+    #[test]
+    fn test_authentication_frame() {
+        assert_eq!(
+            serde_json::from_str::<Authentication>(r#"{"token": "v4.local.frame"}"#).unwrap(),
+            Authentication::Token("v4.local.frame".into())
+        );
+        assert_eq!(
+            serde_json::from_str::<Authentication>(r#"{"ticket": "abc"}"#).unwrap(),
+            Authentication::Ticket("abc".into())
+        );
+        assert!(serde_json::from_str::<Authentication>(r#"{"user": "brvy"}"#).is_err());
+    }
+}
@@ -1,9 +1,75 @@
+use uuid::Uuid;
+
+use crate::auth::permission::Permission;
 use crate::messaging::model::{Message, MessageBody};
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub enum RtcRequestBody {
     #[serde(rename = "build")]
     Build { blueprint: String },
+    #[serde(rename = "queue")]
+    Queue {},
+    #[serde(rename = "reorder")]
+    Reorder { building: Uuid, position: i32 },
+    #[serde(rename = "dequeue")]
+    Dequeue { building: Uuid },
+    #[serde(rename = "cancel")]
+    Cancel { building: Uuid },
+    #[serde(rename = "pause")]
+    Pause { building: Uuid },
+    #[serde(rename = "resume")]
+    Resume { building: Uuid },
+    #[serde(rename = "demolish")]
+    Demolish { building: Uuid },
+    #[serde(rename = "upgrade")]
+    Upgrade { building: Uuid },
+    #[serde(rename = "repair")]
+    Repair { building: Uuid },
+    #[serde(rename = "blueprints")]
+    Blueprints {},
+    #[serde(rename = "resources")]
+    Resources {},
+    #[serde(rename = "policies")]
+    Policies {},
+    #[serde(rename = "add_policy")]
+    AddPolicy {
+        name: String,
+        rule: serde_json::Value,
+        #[serde(default)]
+        interval: Option<i32>,
+    },
+    #[serde(rename = "remove_policy")]
+    RemovePolicy { policy: Uuid },
+    #[serde(rename = "dry_run_policy")]
+    DryRunPolicy {
+        #[serde(default)]
+        policy: Option<Uuid>,
+    },
+}
+
+impl RtcRequestBody {
+    /// What the connected account's role needs to send this request.
+    pub fn permission(&self) -> Permission {
+        match self {
+            RtcRequestBody::Queue {}
+            | RtcRequestBody::Blueprints {}
+            | RtcRequestBody::Resources {}
+            | RtcRequestBody::Policies {}
+            | RtcRequestBody::DryRunPolicy { .. } => Permission::ViewInventory,
+            RtcRequestBody::Build { .. }
+            | RtcRequestBody::Reorder { .. }
+            | RtcRequestBody::Dequeue { .. }
+            | RtcRequestBody::Cancel { .. }
+            | RtcRequestBody::Pause { .. }
+            | RtcRequestBody::Resume { .. }
+            | RtcRequestBody::Demolish { .. }
+            | RtcRequestBody::Upgrade { .. }
+            | RtcRequestBody::Repair { .. } => Permission::ManageBuildings,
+            RtcRequestBody::AddPolicy { .. } | RtcRequestBody::RemovePolicy { .. } => {
+                Permission::ManagePolicies
+            }
+        }
+    }
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -16,35 +82,143 @@ pub struct RtcResponse {
     pub id: uuid::Uuid,
     pub success: bool,
     pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
 
 impl RtcResponse {
     pub fn from_message(msg: Message) -> Result<Self, anyhow::Error> {
         let response = match msg.body {
             MessageBody::AuthenticationResponse(result) => match result {
-                Ok(token) => Self {
+                Ok(tokens) => Self {
                     id: msg.id,
                     success: true,
-                    message: Some(token),
+                    message: Some(tokens.access_token),
+                    data: None,
                 },
-                Err(err_msg) => Self {
+                Err(err) => Self {
                     id: msg.id,
                     success: false,
-                    message: Some(err_msg),
+                    message: Some(err.to_string()),
+                    data: None,
                 },
             },
             MessageBody::BuildResponse(result) => match result {
                 Ok(building) => Self {
                     id: msg.id,
                     success: true,
-                    message: Some(building.to_string()),
+                    message: Some(building.id.to_string()),
+                    data: Some(serde_json::to_value(building)?),
+                },
+                Err(err_msg) => Self {
+                    id: msg.id,
+                    success: false,
+                    message: Some(err_msg),
+                    data: None,
+                },
+            },
+            MessageBody::BuildQueueResponse(result) => match result {
+                Ok(queue) => Self {
+                    id: msg.id,
+                    success: true,
+                    message: None,
+                    data: Some(serde_json::to_value(queue)?),
                 },
                 Err(err_msg) => Self {
                     id: msg.id,
                     success: false,
                     message: Some(err_msg),
+                    data: None,
                 },
             },
+            MessageBody::BuildingCommandResponse(result) => match result {
+                Ok(outcome) => Self {
+                    id: msg.id,
+                    success: true,
+                    message: Some(outcome.building_id.to_string()),
+                    data: Some(serde_json::to_value(outcome)?),
+                },
+                Err(err_msg) => Self {
+                    id: msg.id,
+                    success: false,
+                    message: Some(err_msg),
+                    data: None,
+                },
+            },
+            MessageBody::BlueprintsResponse(result) => match result {
+                Ok(blueprints) => Self {
+                    id: msg.id,
+                    success: true,
+                    message: None,
+                    data: Some(serde_json::to_value(blueprints)?),
+                },
+                Err(err_msg) => Self {
+                    id: msg.id,
+                    success: false,
+                    message: Some(err_msg),
+                    data: None,
+                },
+            },
+            MessageBody::ResourcesResponse(result) => match result {
+                Ok(stock) => Self {
+                    id: msg.id,
+                    success: true,
+                    message: None,
+                    data: Some(serde_json::to_value(stock)?),
+                },
+                Err(err_msg) => Self {
+                    id: msg.id,
+                    success: false,
+                    message: Some(err_msg),
+                    data: None,
+                },
+            },
+            MessageBody::PoliciesResponse(result) => match result {
+                Ok(policies) => Self {
+                    id: msg.id,
+                    success: true,
+                    message: None,
+                    data: Some(serde_json::to_value(policies)?),
+                },
+                Err(err_msg) => Self {
+                    id: msg.id,
+                    success: false,
+                    message: Some(err_msg),
+                    data: None,
+                },
+            },
+            MessageBody::PolicyReport(result) => match result {
+                Ok(outcomes) => Self {
+                    id: msg.id,
+                    success: true,
+                    message: Some("policies".into()),
+                    data: Some(serde_json::to_value(outcomes)?),
+                },
+                Err(err_msg) => Self {
+                    id: msg.id,
+                    success: false,
+                    message: Some(err_msg),
+                    data: None,
+                },
+            },
+            MessageBody::ProductionReport(report) => Self {
+                id: msg.id,
+                success: report.stalled.is_empty(),
+                message: Some("production".into()),
+                data: Some(serde_json::to_value(report)?),
+            },
+            MessageBody::UpkeepReport(report) => Self {
+                id: msg.id,
+                success: report.degraded.is_empty(),
+                message: Some("upkeep".into()),
+                data: Some(serde_json::to_value(report)?),
+            },
+            MessageBody::CalendarEvent(event) => Self {
+                id: msg.id,
+                success: true,
+                message: Some("calendar".into()),
+                data: Some(serde_json::to_value(event)?),
+            },
             _ => {
                 tracing::debug!("Unsupported message body for RtcResponse: {:?}", msg.body);
                 return Err(anyhow::anyhow!("Unsupported message body for RtcResponse"));
@@ -75,6 +249,111 @@ mod tests {
             RtcRequestBody::Build { blueprint } => {
                 assert_eq!(blueprint, "example_blueprint");
             }
+            other => panic!("unexpected request body: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rtc_queue_request_deserialization() {
+        let building = Uuid::new_v4();
+
+        let raw = format!(r#"{{"body":{{"reorder":{{"building":"{building}","position":2}}}}}}"#);
+        let request: RtcRequest = serde_json::from_str(&raw).unwrap();
+        match request.body {
+            RtcRequestBody::Reorder {
+                building: id,
+                position,
+            } => {
+                assert_eq!(id, building);
+                assert_eq!(position, 2);
+            }
+            other => panic!("unexpected request body: {:?}", other),
+        }
+
+        let raw = format!(r#"{{"body":{{"dequeue":{{"building":"{building}"}}}}}}"#);
+        let request: RtcRequest = serde_json::from_str(&raw).unwrap();
+        assert!(matches!(request.body, RtcRequestBody::Dequeue { building: id } if id == building));
+
+        let request: RtcRequest = serde_json::from_str(r#"{"body":{"queue":{}}}"#).unwrap();
+        assert!(matches!(request.body, RtcRequestBody::Queue {}));
+    }
+
+    #[test]
+    fn test_rtc_lifecycle_request_deserialization() {
+        let building = Uuid::new_v4();
+
+        for kind in ["cancel", "pause", "resume", "demolish", "upgrade", "repair"] {
+            let raw = format!(r#"{{"body":{{"{kind}":{{"building":"{building}"}}}}}}"#);
+            let request: RtcRequest = serde_json::from_str(&raw).unwrap();
+            let id = match (kind, request.body) {
+                ("cancel", RtcRequestBody::Cancel { building })
+                | ("pause", RtcRequestBody::Pause { building })
+                | ("resume", RtcRequestBody::Resume { building })
+                | ("demolish", RtcRequestBody::Demolish { building })
+                | ("upgrade", RtcRequestBody::Upgrade { building })
+                | ("repair", RtcRequestBody::Repair { building }) => building,
+                (kind, other) => panic!("unexpected request body for {kind}: {:?}", other),
+            };
+            assert_eq!(id, building);
         }
     }
+
+    #[test]
+    fn test_rtc_policy_request_deserialization() {
+        let raw = r#"{"body":{"add_policy":{"name":"wood","rule":{"then":{"kind":"build","blueprint":"sawmill"}}}}}"#;
+        let request: RtcRequest = serde_json::from_str(raw).unwrap();
+        match request.body {
+            RtcRequestBody::AddPolicy {
+                name,
+                rule,
+                interval,
+            } => {
+                assert_eq!(name, "wood");
+                assert_eq!(rule["then"]["blueprint"], "sawmill");
+                assert_eq!(interval, None);
+            }
+            other => panic!("unexpected request body: {:?}", other),
+        }
+
+        let request: RtcRequest =
+            serde_json::from_str(r#"{"body":{"dry_run_policy":{}}}"#).unwrap();
+        assert!(matches!(
+            request.body,
+            RtcRequestBody::DryRunPolicy { policy: None }
+        ));
+    }
+
+    #[test]
+    fn test_rtc_request_permissions() {
+        let building = Uuid::new_v4();
+
+        assert_eq!(
+            RtcRequestBody::Queue {}.permission(),
+            Permission::ViewInventory
+        );
+        assert_eq!(
+            RtcRequestBody::Demolish { building }.permission(),
+            Permission::ManageBuildings
+        );
+        assert_eq!(
+            RtcRequestBody::RemovePolicy { policy: building }.permission(),
+            Permission::ManagePolicies
+        );
+    }
+
+    #[test]
+    fn test_rtc_response_omits_empty_data() {
+        let response = RtcResponse {
+            id: Uuid::nil(),
+            success: true,
+            message: None,
+            data: None,
+        };
+
+        let serialized = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"id":"00000000-0000-0000-0000-000000000000","success":true,"message":null}"#
+        );
+    }
 }